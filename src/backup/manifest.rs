@@ -0,0 +1,311 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::archive::Archive;
+use crate::configuration::database::Kind as DatabaseKind;
+use crate::helper::checksum::ChecksumReader;
+
+/// Describes the content of an archive file; it's stored as last entry of the tar-stream and as
+/// separate file next to the archive file on the destination.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    /// The version of rusty-backup, which created the archive file.
+    pub tool_version: String,
+    /// The SHA-256 checksum of the settings file, the archive file was created with.
+    pub config_hash: String,
+    /// The creation time in UTC (RFC 3339).
+    pub created: String,
+    pub directories: Vec<ManifestDirectory>,
+    pub files: Vec<ManifestFile>,
+    pub databases: Vec<ManifestDatabase>,
+    /// The outputs of the archive's commands; missing in manifests of older versions.
+    #[serde(default)]
+    pub commands: Vec<ManifestCommand>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestDirectory {
+    /// The source directory, e.g. "/var/www".
+    pub path: String,
+    /// The directory's path in the archive, e.g. "www".
+    pub entry: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestFile {
+    /// The file's path in the archive, e.g. "www/index.html".
+    pub path: String,
+    pub size: u64,
+    /// The modification time in seconds since the epoch.
+    pub mtime: u64,
+    /// The permissions in octal notation, e.g. "0644".
+    pub mode: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestDatabase {
+    pub kind: DatabaseKind,
+    pub name: String,
+    /// The dump's path in the archive, e.g. "shop.sql".
+    pub file: String,
+    pub size: u64,
+    pub sha256: String,
+    /// The version of the dump program, if it could be determined.
+    pub dump_tool_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestCommand {
+    /// The output's path in the archive, e.g. "etcd.db".
+    pub name: String,
+    pub exec: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Manifest {
+    /// The path of the manifest in the tar-stream.
+    pub const ENTRY_NAME: &'static str = ".rusty-backup-manifest.json";
+    pub const EXTENSION: &'static str = ".manifest.json";
+
+    fn map_error(err: impl std::fmt::Debug) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Gets the name of the manifest file next to the given archive file, e.g.
+    /// "2024-03-01_www.manifest.json.enc" for "2024-03-01_www.tar.bz2.enc".
+    pub fn get_file_name(archive: &Archive, archive_file_name: &str) -> String {
+        let mut file_name = format!(
+            "{}{}",
+            archive.strip_file_extension(archive_file_name),
+            Self::EXTENSION
+        );
+        if let Some(encryption) = &archive.encryption {
+            file_name.push_str(&encryption.to_extension_string());
+        }
+        file_name
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(Self::map_error)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Manifest, String> {
+        serde_json::from_reader(reader).map_err(Self::map_error)
+    }
+
+    /// Checks the size and checksum of a restored file against the listed file with the given
+    /// path; files, which aren't listed, are accepted.
+    pub fn validate_file(&self, entry_path: &str, file_name: &str) -> Result<(), String> {
+        let listed_file = match self.files.iter().find(|file| file.path == entry_path) {
+            Some(listed_file) => listed_file,
+            None => return Ok(()),
+        };
+
+        let file = File::open(file_name).map_err(Self::map_error)?;
+        let mut reader = ChecksumReader::new(file);
+        io::copy(&mut reader, &mut io::sink()).map_err(Self::map_error)?;
+        let (size, sha256) = reader.finish();
+        if size != listed_file.size {
+            return Err(format!(
+                "size of '{}' is {} instead of {} bytes",
+                file_name, size, listed_file.size
+            ));
+        }
+        if sha256 != listed_file.sha256 {
+            return Err(format!(
+                "checksum of '{}' is {} instead of {}",
+                file_name, sha256, listed_file.sha256
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Writes the manifest next to the archive file with the given name and returns its name.
+    pub fn write_file(&self, archive: &Archive, archive_file_name: &str) -> Result<String, String> {
+        let file_name = format!(
+            "{}{}",
+            archive.strip_file_extension(archive_file_name),
+            Self::EXTENSION
+        );
+        fs::write(&file_name, self.to_json()?).map_err(Self::map_error)?;
+
+        Ok(file_name)
+    }
+}
+
+impl ManifestFile {
+    pub fn new(path: String, metadata: &fs::Metadata, size: u64, sha256: String) -> Self {
+        Self {
+            path,
+            size,
+            mtime: metadata.mtime().max(0) as u64,
+            mode: format!("{:04o}", metadata.mode() & 0o7777),
+            sha256,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    use crate::configuration::compression::Compression;
+    use crate::configuration::encryption::Encryption;
+
+    /// The SHA-256 checksum of "content".
+    const CONTENT_SHA256: &str = "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73";
+
+    fn manifest() -> Manifest {
+        Manifest {
+            tool_version: String::from("0.1.0"),
+            config_hash: String::from("abc"),
+            created: String::from("2024-03-01T18:30:00Z"),
+            directories: vec![ManifestDirectory {
+                path: String::from("/var/www"),
+                entry: String::from("www"),
+            }],
+            files: vec![ManifestFile {
+                path: String::from("www/index.html"),
+                size: 7,
+                mtime: 1_700_000_000,
+                mode: String::from("0644"),
+                sha256: String::from(CONTENT_SHA256),
+            }],
+            databases: vec![ManifestDatabase {
+                kind: DatabaseKind::PostgreSql,
+                name: String::from("shop"),
+                file: String::from("shop.sql"),
+                size: 42,
+                sha256: String::from("def"),
+                dump_tool_version: None,
+            }],
+            commands: vec![ManifestCommand {
+                name: String::from("crontab"),
+                exec: String::from("crontab -l"),
+                size: 0,
+                sha256: String::new(),
+            }],
+        }
+    }
+
+    fn write_temp_file(name: &str, content: &str) -> String {
+        let file_name = env::temp_dir()
+            .join(format!(
+                "rusty-backup-manifest-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&file_name, content).unwrap();
+        file_name
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let json = manifest().to_json().unwrap();
+        let parsed = Manifest::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(parsed.to_json().unwrap(), json);
+        assert_eq!(parsed.directories[0].entry, "www");
+        assert_eq!(parsed.files[0].mode, "0644");
+        assert_eq!(parsed.databases[0].kind, DatabaseKind::PostgreSql);
+        assert_eq!(parsed.commands[0].exec, "crontab -l");
+    }
+
+    #[test]
+    fn reads_manifests_without_commands() {
+        let json = r#"{
+            "tool_version": "0.1.0",
+            "config_hash": "abc",
+            "created": "2024-03-01T18:30:00Z",
+            "directories": [],
+            "files": [],
+            "databases": []
+        }"#;
+        let parsed = Manifest::from_reader(json.as_bytes()).unwrap();
+        assert!(parsed.commands.is_empty());
+        assert!(Manifest::from_reader(&b"{}"[..]).is_err());
+    }
+
+    #[test]
+    fn gets_the_file_name_next_to_the_archive_file() {
+        let mut archive = Archive::new();
+        archive.compression = Compression::TarBZ2;
+        assert_eq!(
+            Manifest::get_file_name(&archive, "www_01.tar.bz2"),
+            "www_01.manifest.json"
+        );
+
+        archive.encryption = Some(Encryption::new());
+        assert_eq!(
+            Manifest::get_file_name(&archive, "www_01.tar.bz2.enc"),
+            "www_01.manifest.json.enc"
+        );
+    }
+
+    #[test]
+    fn validates_listed_files() {
+        let manifest = manifest();
+        let file_name = write_temp_file("valid", "content");
+        assert!(manifest.validate_file("www/index.html", &file_name).is_ok());
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn accepts_files_which_are_not_listed() {
+        let manifest = manifest();
+        let file_name = write_temp_file("unlisted", "other content");
+        assert!(manifest.validate_file("www/other.html", &file_name).is_ok());
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn rejects_files_of_another_size() {
+        let manifest = manifest();
+        let file_name = write_temp_file("size", "content!");
+        let why = manifest
+            .validate_file("www/index.html", &file_name)
+            .unwrap_err();
+        assert!(why.contains("is 8 instead of 7 bytes"), "{}", why);
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn rejects_files_with_another_checksum() {
+        let manifest = manifest();
+        let file_name = write_temp_file("checksum", "CONTENT");
+        let why = manifest
+            .validate_file("www/index.html", &file_name)
+            .unwrap_err();
+        assert!(why.contains("checksum of"), "{}", why);
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        assert!(manifest()
+            .validate_file("www/index.html", "/nonexistent/index.html")
+            .is_err());
+    }
+
+    #[test]
+    fn writes_the_manifest_file() {
+        let mut archive = Archive::new();
+        archive.compression = Compression::Tar;
+        let archive_file_name = write_temp_file("write.tar", "");
+        let file_name = manifest().write_file(&archive, &archive_file_name).unwrap();
+        assert!(file_name.ends_with("write.manifest.json"));
+
+        let parsed = Manifest::from_reader(File::open(&file_name).unwrap()).unwrap();
+        assert_eq!(parsed.files[0].path, "www/index.html");
+        fs::remove_file(file_name).unwrap_or_default();
+        fs::remove_file(archive_file_name).unwrap_or_default();
+    }
+}
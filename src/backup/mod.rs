@@ -1,27 +1,38 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::{ErrorKind, Read, Write};
-use std::net::TcpStream;
-use std::os::unix::fs::MetadataExt;
-use std::path::Path;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 
-use bytes::Bytes;
 use bzip2::write::BzEncoder;
 use chrono::{Datelike, Utc};
-use futures::{FutureExt, TryStreamExt};
-use futures_fs::FsPool;
-use log::{error, info};
+use log::{error, info, warn};
+use nix::sys::stat::{major, minor};
 use regex::Regex;
-use rusoto_s3::{PutObjectRequest, S3Client, StreamingBody, S3};
-use ssh2::Session;
-use tar::Builder;
+use tar::{Builder, EntryType, Header};
+use walkdir::WalkDir;
 
 use crate::configuration::{
-    compression::Compression, database::Database, destination::Kind as DestinationKind,
-    directory::Directory, Configuration,
+    archive::Archive,
+    command::CommandSource,
+    compression::Compression,
+    database::Database,
+    directory::{Directory, SpecialFiles},
+    hook::{HookEvent, Hooks},
+    Configuration,
 };
+use crate::helper::attributes::FileAttributes;
+use crate::helper::checksum::{Checksum, ChecksumReader};
+use crate::helper::sparse::{SparseMap, SparseReader};
+
+pub mod manifest;
+mod snapshot;
+
+use manifest::{Manifest, ManifestCommand, ManifestDatabase, ManifestDirectory, ManifestFile};
+use snapshot::Snapshot;
 
 pub struct Backup {}
 
@@ -67,203 +78,141 @@ impl Backup {
         fs::create_dir_all(&configuration.working_directory).map_err(Backup::map_error)?;
         env::set_current_dir(&configuration.working_directory).map_err(Backup::map_error)?;
 
-        for archive in configuration.archives {
-            let mut real_archive_name = Backup::build_real_archive_name(archive.name);
-
-            info!("creating archive: {}", real_archive_name);
-            let mut files_to_move_to_destination: Vec<String> = Vec::new();
-            let mut temporary_files: Vec<String> = Vec::new();
-
-            if archive.compression == Compression::Tar || archive.compression == Compression::TarBZ2
-            {
-                match Backup::tar_archive(
-                    &real_archive_name,
-                    &archive.directories,
-                    &archive.databases,
-                ) {
-                    Ok(tar_file) => {
-                        real_archive_name = tar_file.clone();
-                        if archive.compression == Compression::Tar {
-                            files_to_move_to_destination.push(tar_file);
-                        }
-                    }
-                    Err(error) => {
-                        return Err(error);
-                    }
-                }
+        let environment = vec![
+            ("RUSTY_BACKUP_OPERATION", String::from("backup")),
+            (
+                "RUSTY_BACKUP_ARCHIVES",
+                configuration
+                    .archives
+                    .iter()
+                    .map(|archive| Backup::build_real_archive_name(archive.name.clone()))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            ),
+        ];
+        let result = match configuration.hooks.run(HookEvent::PreBackup, &environment) {
+            Ok(()) => Backup::backup_archives(&configuration).await,
+            Err(why) => Err(why),
+        };
+        configuration
+            .hooks
+            .finish(HookEvent::PostBackup, result, environment)
+    }
 
-                if archive.compression == Compression::TarBZ2 {
-                    temporary_files.push(real_archive_name.clone());
-                    match Backup::bz2_archive(&real_archive_name) {
-                        Ok(bz2_file) => {
-                            files_to_move_to_destination.push(bz2_file);
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
+    /// Creates and uploads the archives; each one is surrounded by its hooks.
+    async fn backup_archives(configuration: &Configuration) -> Result<(), String> {
+        for archive in &configuration.archives {
+            let real_archive_name = Backup::build_real_archive_name(archive.name.clone());
+            let mut environment = Hooks::build_environment(archive, &real_archive_name);
+            environment.push(("RUSTY_BACKUP_OPERATION", String::from("backup")));
+
+            let result = match archive.hooks.run(HookEvent::PreBackup, &environment) {
+                Ok(()) => Backup::backup_archive(archive, real_archive_name, &configuration.hash)
+                    .await
+                    .map(|files| environment.push(("RUSTY_BACKUP_FILES", files.join("\n")))),
+                Err(why) => Err(why),
+            };
+            archive
+                .hooks
+                .finish(HookEvent::PostBackup, result, environment)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the archive and uploads it to its destination; gets the uploaded files.
+    async fn backup_archive(
+        archive: &Archive,
+        mut real_archive_name: String,
+        config_hash: &str,
+    ) -> Result<Vec<String>, String> {
+        info!("creating archive: {}", real_archive_name);
+        let mut files_to_move_to_destination: Vec<String> = Vec::new();
+        let mut temporary_files: Vec<String> = Vec::new();
+        let mut manifest: Option<Manifest> = None;
+
+        if archive.compression == Compression::Tar || archive.compression == Compression::TarBZ2 {
+            match Backup::tar_archive(
+                &real_archive_name,
+                &archive.directories,
+                &archive.databases,
+                &archive.commands,
+                config_hash,
+            ) {
+                Ok((tar_file, tar_manifest)) => {
+                    manifest = Some(tar_manifest);
+                    real_archive_name = tar_file.clone();
+                    if archive.compression == Compression::Tar {
+                        files_to_move_to_destination.push(tar_file);
                     }
                 }
+                Err(error) => {
+                    return Err(error);
+                }
             }
 
-            match archive.encryption {
-                Some(encryption) => {
-                    for file in &files_to_move_to_destination {
-                        if let Err(err) = encryption.encrypt_file(file) {
-                            return Err(err);
-                        }
+            if archive.compression == Compression::TarBZ2 {
+                temporary_files.push(real_archive_name.clone());
+                match Backup::bz2_archive(&real_archive_name) {
+                    Ok(bz2_file) => {
+                        files_to_move_to_destination.push(bz2_file);
                     }
-
-                    let cloned = files_to_move_to_destination.clone();
-                    files_to_move_to_destination.clear();
-                    for mut cloned_element in cloned {
-                        cloned_element.push_str(".enc");
-                        files_to_move_to_destination.push(cloned_element);
+                    Err(error) => {
+                        return Err(error);
                     }
                 }
-                None => {}
             }
+        }
 
-            match archive.destination.kind {
-                DestinationKind::Directory => {
-                    let mut archive_path = archive.destination.path;
-                    archive_path.push_str("/");
-                    if fs::create_dir_all(&archive_path).is_err() {
-                        return Err(format!("unable to create archive_path: '{}'", archive_path));
-                    }
+        if let Some(encryption) = &archive.encryption {
+            for file in &files_to_move_to_destination {
+                encryption.encrypt_file(file)?;
+            }
 
-                    for file in files_to_move_to_destination {
-                        let mut new_archive_name = archive_path.clone();
-                        new_archive_name.push_str(file.as_str());
-                        match fs::rename(&file, &new_archive_name) {
-                            Ok(_) => {}
-                            Err(_rename_err) => match fs::copy(&file, &new_archive_name) {
-                                Ok(_) => match fs::remove_file(&file) {
-                                    Ok(_) => {}
-                                    Err(_) => {}
-                                },
-                                Err(copy_err) => {
-                                    return Err(format!(
-                                        "unable to rename and copy '{}' to '{}'! error: {:?}",
-                                        file, new_archive_name, copy_err
-                                    ));
-                                }
-                            },
-                        }
-                    }
-                }
-                DestinationKind::S3 => {
-                    let fs = FsPool::default();
-                    let client = S3Client::new(archive.destination.s3_region);
-
-                    for file in files_to_move_to_destination {
-                        match fs::metadata(&file) {
-                            Ok(meta) => {
-                                info!("uploading file: {}", &file);
-                                let object_key = file.clone();
-                                let read_stream = tokio::fs::read(file)
-                                    .into_stream()
-                                    .map_ok(|b| Bytes::from(b));
-
-                                let put_object_request = PutObjectRequest {
-                                    bucket: archive.destination.s3_bucket.clone(),
-                                    key: object_key.clone(),
-                                    content_length: Some(meta.len() as i64),
-                                    body: Some(StreamingBody::new(read_stream)),
-                                    server_side_encryption: Some(String::from("AES256")),
-                                    ..Default::default()
-                                };
-                                match client.put_object(put_object_request).await {
-                                    Ok(foo) => {
-                                        info!("put_object ok: {:?}", foo);
-                                        fs.delete(object_key.clone());
-                                    }
-                                    Err(err) => {
-                                        error!("put_object err: {:?}", err);
-                                    }
-                                }
-                            }
-                            Err(err) => {
-                                error!("fs::metadata({}) err: {}", file, err);
-                            }
-                        }
-                    }
-                }
-                DestinationKind::SSH => {
-                    let fs = FsPool::default();
-
-                    let addr = format!("{}:22", archive.destination.server);
-                    let tcp = TcpStream::connect(addr).unwrap();
-                    let mut ssh2_session = Session::new().unwrap();
-                    ssh2_session.set_tcp_stream(tcp);
-                    if let Err(e) = ssh2_session.handshake() {
-                        error!("ssh2_session.handshake() err: {:?}", e);
-                        info!("fallback to scp");
-
-                        for filename in files_to_move_to_destination {
-                            let dest_address = format!(
-                                "{}@{}:/",
-                                archive.destination.username, archive.destination.server
-                            );
-                            let mut scp_command = std::process::Command::new("sshpass");
-                            scp_command.arg("-p").arg(&archive.destination.password);
-                            scp_command.arg("scp");
-                            scp_command.arg(&filename);
-                            scp_command.arg(&dest_address);
-                            scp_command.spawn().unwrap().wait().unwrap();
-
-                            info!("file upload ok: {}", filename);
-                        }
-                        continue;
-                    }
-                    ssh2_session
-                        .userauth_password(
-                            &archive.destination.username,
-                            &archive.destination.password,
-                        )
-                        .unwrap();
-
-                    for filename in files_to_move_to_destination {
-                        match fs::metadata(&filename) {
-                            Ok(meta) => {
-                                info!("uploading file: {}", &filename);
-
-                                let mut remote_file = ssh2_session
-                                    .scp_send(Path::new(&filename), 0o644, meta.size(), None)
-                                    .unwrap();
-
-                                let mut file = fs::File::open(&filename).unwrap();
-                                let mut buf = [0; 1_024];
-                                let mut read_bytes = file.read(&mut buf).unwrap();
-                                while read_bytes > 0 {
-                                    remote_file.write(&buf[..read_bytes]).unwrap();
-                                    read_bytes = file.read(&mut buf).unwrap();
-                                }
+            let cloned = files_to_move_to_destination.clone();
+            files_to_move_to_destination.clear();
+            for mut cloned_element in cloned {
+                cloned_element.push_str(".enc");
+                files_to_move_to_destination.push(cloned_element);
+            }
+        }
 
-                                remote_file.send_eof().unwrap();
-                                remote_file.wait_eof().unwrap();
-                                remote_file.close().unwrap();
-                                remote_file.wait_close().unwrap();
+        // Every archive file is uploaded with a sidecar file containing its checksum.
+        for file in files_to_move_to_destination.clone() {
+            files_to_move_to_destination.push(Checksum::write_sidecar(&file)?);
+        }
 
-                                info!("file upload ok: {}", filename);
-                                fs.delete(filename);
-                            }
-                            Err(err) => {
-                                error!("fs::metadata({}) err: {}", filename, err);
-                            }
-                        }
-                    }
+        // The manifest is uploaded next to the archive file, encrypted like it.
+        if let (Some(manifest), Some(archive_file)) =
+            (&manifest, files_to_move_to_destination.first())
+        {
+            let manifest_file = manifest.write_file(archive, archive_file)?;
+            match &archive.encryption {
+                Some(encryption) => {
+                    encryption.encrypt_file(&manifest_file)?;
+                    fs::remove_file(&manifest_file).map_err(Backup::map_error)?;
+                    files_to_move_to_destination.push(format!("{}.enc", manifest_file));
                 }
-                DestinationKind::None => {}
+                None => files_to_move_to_destination.push(manifest_file),
             }
+        }
 
-            for file in temporary_files {
-                if fs::remove_file(&file).is_err() {
-                    return Err(format!("unable to remove temporary file: '{}'", file));
-                }
+        archive
+            .destination
+            .upload_files(files_to_move_to_destination.clone())
+            .await?;
+        archive
+            .destination
+            .delete_outdated_archive_files(archive)
+            .await?;
+
+        for file in temporary_files {
+            if fs::remove_file(&file).is_err() {
+                return Err(format!("unable to remove temporary file: '{}'", file));
             }
         }
 
-        Ok(())
+        Ok(files_to_move_to_destination)
     }
 
     fn bz2_archive(archive_name: &String) -> Result<String, String> {
@@ -327,16 +276,210 @@ impl Backup {
         Ok(bz2_archive_name)
     }
 
+    /// Appends the given directory recursively as `entry_name`, and lists its files with their
+    /// checksums in the manifest. Sparse files are archived without their holes, hardlinks
+    /// only once, and special files as configured by the directory. The files are read from
+    /// `source`, which is the directory itself or its location in a snapshot.
+    fn append_directory(
+        tar: &mut Builder<File>,
+        directory: &Directory,
+        source: &Path,
+        entry_name: &str,
+        manifest: &mut Manifest,
+    ) -> Result<(), String> {
+        let mut attributes = FileAttributes::new();
+        let mut linked_files: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        for dir_entry in WalkDir::new(source).follow_links(true) {
+            let dir_entry = dir_entry.map_err(|err| {
+                format!(
+                    "unable to read directory: {}\nerror: {:?}",
+                    directory.name, err
+                )
+            })?;
+            let relative_path = dir_entry
+                .path()
+                .strip_prefix(source)
+                .unwrap_or(dir_entry.path());
+            let entry_path = Path::new(entry_name).join(relative_path);
+            let map_append_error = |err: std::io::Error| {
+                format!(
+                    "tar: unable to append: {}\nerror: {:?}",
+                    dir_entry.path().display(),
+                    err
+                )
+            };
+
+            let metadata = dir_entry.metadata().map_err(|err| {
+                format!(
+                    "tar: unable to append: {}\nerror: {:?}",
+                    dir_entry.path().display(),
+                    err
+                )
+            })?;
+            let file_type = metadata.file_type();
+            let is_special = file_type.is_fifo()
+                || file_type.is_char_device()
+                || file_type.is_block_device()
+                || file_type.is_socket();
+            if is_special {
+                match directory.special_files {
+                    SpecialFiles::Error => {
+                        return Err(format!(
+                            "tar: the special file {} can't be archived.",
+                            dir_entry.path().display()
+                        ))
+                    }
+                    SpecialFiles::Skip => continue,
+                    SpecialFiles::Archive if file_type.is_socket() => {
+                        warn!("skipping the socket {}", dir_entry.path().display());
+                        continue;
+                    }
+                    SpecialFiles::Archive => {}
+                }
+            }
+
+            let mut header = Header::new_gnu();
+            header.set_metadata(&metadata);
+            if file_type.is_char_device() || file_type.is_block_device() {
+                header
+                    .set_device_major(major(metadata.rdev()) as u32)
+                    .map_err(map_append_error)?;
+                header
+                    .set_device_minor(minor(metadata.rdev()) as u32)
+                    .map_err(map_append_error)?;
+            }
+            let pax_records = attributes.read(dir_entry.path(), &metadata, &mut header);
+            if !pax_records.is_empty() {
+                tar.append_pax_extensions(
+                    pax_records
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), value.as_slice())),
+                )
+                .map_err(map_append_error)?;
+            }
+
+            if !file_type.is_file() {
+                header.set_size(0);
+                tar.append_data(&mut header, &entry_path, io::empty())
+                    .map_err(map_append_error)?;
+                continue;
+            }
+
+            // Further links to an already archived file are archived as hardlinks to it.
+            if metadata.nlink() > 1 {
+                match linked_files.get(&(metadata.dev(), metadata.ino())) {
+                    Some(link_target) => {
+                        header.set_entry_type(EntryType::Link);
+                        header.set_size(0);
+                        tar.append_link(&mut header, &entry_path, link_target)
+                            .map_err(map_append_error)?;
+                        continue;
+                    }
+                    None => {
+                        linked_files.insert((metadata.dev(), metadata.ino()), entry_path.clone());
+                    }
+                }
+            }
+
+            let file = File::open(dir_entry.path()).map_err(map_append_error)?;
+            let (size, sha256) = match SparseMap::detect(&file, &metadata)? {
+                Some(sparse_map) => {
+                    let extension = sparse_map.prepare_header(&mut header);
+                    let mut reader = SparseReader::new(file, &sparse_map);
+                    tar.append_data(
+                        &mut header,
+                        &entry_path,
+                        extension.as_slice().chain(&mut reader),
+                    )
+                    .map_err(map_append_error)?;
+                    reader.finish()
+                }
+                None => {
+                    let mut reader = ChecksumReader::new(file);
+                    tar.append_data(&mut header, &entry_path, &mut reader)
+                        .map_err(map_append_error)?;
+                    reader.finish()
+                }
+            };
+            manifest.files.push(ManifestFile::new(
+                entry_path.to_string_lossy().to_string(),
+                &metadata,
+                size,
+                sha256,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Appends the output of the command as entry with the command's name; the output is
+    /// buffered in a temporary file, as the size of an entry precedes its data.
+    fn append_command(
+        tar: &mut Builder<File>,
+        command: &CommandSource,
+        manifest: &mut Manifest,
+    ) -> Result<(), String> {
+        let output_filename = format!(".rusty-backup-command-{}", std::process::id());
+        let result = File::create(&output_filename)
+            .map_err(Backup::map_error)
+            .and_then(|output_file| command.capture(output_file))
+            .and_then(|_| {
+                let output_file = File::open(&output_filename).map_err(Backup::map_error)?;
+                let mut header = Header::new_gnu();
+                header.set_metadata(&output_file.metadata().map_err(Backup::map_error)?);
+                let mut reader = ChecksumReader::new(output_file);
+                tar.append_data(&mut header, &command.name, &mut reader)
+                    .map_err(|err| {
+                        format!(
+                            "tar: unable to append the output of: {}\nerror: {:?}",
+                            command.exec, err
+                        )
+                    })?;
+                let (size, sha256) = reader.finish();
+                manifest.commands.push(ManifestCommand {
+                    name: command.name.clone(),
+                    exec: command.exec.clone(),
+                    size,
+                    sha256,
+                });
+                Ok(())
+            });
+        fs::remove_file(&output_filename).unwrap_or_default();
+        result
+    }
+
+    /// Appends the manifest as last entry of the archive.
+    fn append_manifest(tar: &mut Builder<File>, manifest: &Manifest) -> Result<(), String> {
+        let json = manifest.to_json()?;
+        let mut header = Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, Manifest::ENTRY_NAME, json.as_bytes())
+            .map_err(|err| format!("tar: unable to append the manifest\nerror: {:?}", err))?;
+        tar.finish()
+            .map_err(|err| format!("tar: unable to finish the archive\nerror: {:?}", err))
+    }
+
     fn tar_archive(
         archive_name: &String,
         directories: &Vec<Directory>,
         databases: &Vec<Database>,
-    ) -> Result<String, String> {
+        commands: &[CommandSource],
+        config_hash: &str,
+    ) -> Result<(String, Manifest), String> {
         let archive_name = format!("{}.tar", archive_name);
         lazy_static! {
             static ref REGEX_PATH: Regex = Regex::new(r".*/").unwrap();
         }
 
+        let mut manifest = Manifest {
+            tool_version: String::from(crate_version!()),
+            config_hash: String::from(config_hash),
+            created: Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            ..Default::default()
+        };
         match File::create(&archive_name) {
             Ok(file) => {
                 let mut tar = Builder::new(file);
@@ -344,15 +487,26 @@ impl Backup {
                     let archive_directory_string = REGEX_PATH
                         .replace_all(directory.name.as_str(), "")
                         .into_owned();
-                    match tar.append_dir_all(archive_directory_string, &directory.name) {
-                        Ok(_) => {}
-                        Err(error) => {
-                            return Err(format!(
-                                "tar.append_dir_all: unable to append directory: {}\nerror: {:?}",
-                                directory.name, error
-                            ));
-                        }
+                    // The snapshot is removed, when it goes out of scope, even on errors.
+                    let mut snapshot = Snapshot::create(directory)?;
+                    let source = match &snapshot {
+                        Some(snapshot) => snapshot.path.clone(),
+                        None => PathBuf::from(&directory.name),
+                    };
+                    Backup::append_directory(
+                        &mut tar,
+                        directory,
+                        &source,
+                        &archive_directory_string,
+                        &mut manifest,
+                    )?;
+                    if let Some(snapshot) = snapshot.as_mut() {
+                        snapshot.remove()?;
                     }
+                    manifest.directories.push(ManifestDirectory {
+                        path: directory.name.clone(),
+                        entry: archive_directory_string,
+                    });
                 }
 
                 for database in databases {
@@ -373,11 +527,22 @@ impl Backup {
 						match output.status.code() {
 							Some(0) => {
 								info!("tar file: '{}' ...", &db_filename);
-								match File::open(&db_filename) {
-									Ok(mut db_file) => {
-										match tar.append_file(&db_filename, &mut db_file) {
+								match File::open(&db_filename).and_then(|db_file| Ok((db_file.metadata()?, db_file))) {
+									Ok((metadata, db_file)) => {
+										let mut header = Header::new_gnu();
+										header.set_metadata(&metadata);
+										let mut reader = ChecksumReader::new(db_file);
+										match tar.append_data(&mut header, &db_filename, &mut reader) {
 											Ok(_) => {
-
+												let (size, sha256) = reader.finish();
+												manifest.databases.push(ManifestDatabase {
+													kind: database.kind.clone(),
+													name: database.name.clone(),
+													file: db_filename.clone(),
+													size,
+													sha256,
+													dump_tool_version: database.get_dump_tool_version(),
+												});
 											},
 											Err(_) => {
 												return Err(format!("tar.append_file: unable to append directory: {}", db_filename));
@@ -393,17 +558,21 @@ impl Backup {
 									}
 								}
 							},
-							Some(_) => {
-
-							},
-							None => {
-
+							_ => {
+								fs::remove_file(&db_filename).unwrap_or_default();
+								return Err(format!("the dump of database '{}' failed: {}", database.name, output.status));
 							}
 						}
 
 						Ok(())
-					}).unwrap();
+					})?;
+                }
+
+                for command in commands {
+                    Backup::append_command(&mut tar, command, &mut manifest)?;
                 }
+
+                Backup::append_manifest(&mut tar, &manifest)?;
             }
             Err(why) => match why.kind() {
                 ErrorKind::AlreadyExists => {
@@ -424,6 +593,128 @@ impl Backup {
             },
         }
 
-        Ok(archive_name)
+        Ok((archive_name, manifest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::FileExt;
+
+    use nix::sys::stat::Mode;
+    use nix::unistd::mkfifo;
+    use sha2::{Digest, Sha256};
+
+    fn encode(content: &[u8]) -> String {
+        Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[test]
+    fn archives_sparse_files_hardlinks_and_fifos() {
+        let root = env::temp_dir().join(format!("rusty-backup-append-{}", std::process::id()));
+        let source = root.join("data");
+        fs::create_dir_all(&source).unwrap();
+        let sparse_file = File::create(source.join("sparse")).unwrap();
+        sparse_file.set_len(1 << 20).unwrap();
+        sparse_file.write_all_at(&[1; 4096], 64 << 10).unwrap();
+        fs::write(source.join("original"), "content").unwrap();
+        fs::hard_link(source.join("original"), source.join("link")).unwrap();
+        mkfifo(&source.join("pipe"), Mode::from_bits_truncate(0o640)).unwrap();
+
+        let mut directory = Directory::new();
+        directory.name = source.to_string_lossy().to_string();
+        let tar_name = root.join("data.tar");
+        let mut manifest = Manifest::default();
+        let mut tar = Builder::new(File::create(&tar_name).unwrap());
+        Backup::append_directory(&mut tar, &directory, &source, "data", &mut manifest).unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+
+        let sparse_content = fs::read(source.join("sparse")).unwrap();
+        let mut archive = tar::Archive::new(File::open(&tar_name).unwrap());
+        let mut entry_types = HashMap::new();
+        let mut link_name = None;
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().to_string();
+            entry_types.insert(path.clone(), entry.header().entry_type());
+            match path.as_str() {
+                "data/sparse" => {
+                    let sparse_map =
+                        SparseMap::from_header_blocks(entry.header().as_bytes()).unwrap();
+                    assert_eq!(sparse_map.size, 1 << 20);
+                    assert_eq!(sparse_map.data_size(), 4096);
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content).unwrap();
+                    assert!(content == sparse_content);
+                }
+                "data/original" | "data/link" if entry.header().entry_type().is_hard_link() => {
+                    link_name = entry
+                        .link_name()
+                        .unwrap()
+                        .map(|link_name| link_name.to_string_lossy().to_string());
+                }
+                _ => {}
+            }
+        }
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(entry_types["data/sparse"], EntryType::GNUSparse);
+        assert_eq!(entry_types["data/pipe"], EntryType::Fifo);
+        let mut link_types = [entry_types["data/original"], entry_types["data/link"]];
+        link_types.sort_by_key(|entry_type| entry_type.as_byte());
+        assert_eq!(link_types, [EntryType::Regular, EntryType::Link]);
+        // The first link of the file is archived with its data, the other one refers to it.
+        let link_name = link_name.unwrap();
+        assert_eq!(entry_types[&link_name], EntryType::Regular);
+
+        let sparse = manifest
+            .files
+            .iter()
+            .find(|file| file.path == "data/sparse")
+            .unwrap();
+        assert_eq!(sparse.size, 1 << 20);
+        assert_eq!(sparse.sha256, encode(&sparse_content));
+        // Hardlinks and special files aren't listed with a checksum in the manifest.
+        assert_eq!(manifest.files.len(), 2);
+    }
+
+    #[test]
+    fn archives_the_command_output_under_its_name() {
+        let command = CommandSource {
+            name: String::from("crontabs/root"),
+            exec: String::from("echo jobs"),
+            restore: None,
+        };
+        let tar_name =
+            env::temp_dir().join(format!("rusty-backup-command-{}.tar", std::process::id()));
+        let mut manifest = Manifest::default();
+        let mut tar = Builder::new(File::create(&tar_name).unwrap());
+        Backup::append_command(&mut tar, &command, &mut manifest).unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+
+        let mut archive = tar::Archive::new(File::open(&tar_name).unwrap());
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            entries.push((entry.path().unwrap().to_string_lossy().to_string(), content));
+        }
+        fs::remove_file(&tar_name).unwrap();
+
+        assert_eq!(
+            entries,
+            [(String::from("crontabs/root"), String::from("jobs\n"))]
+        );
+        assert_eq!(manifest.commands.len(), 1);
+        assert_eq!(manifest.commands[0].name, "crontabs/root");
+        assert_eq!(manifest.commands[0].size, 5);
+        assert_eq!(manifest.commands[0].sha256, encode(b"jobs\n"));
     }
 }
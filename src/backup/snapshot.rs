@@ -0,0 +1,331 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::Utc;
+use log::{error, info};
+
+use crate::configuration::directory::{Directory, SnapshotKind};
+
+/// A mounted filesystem, as listed in /proc/self/mountinfo.
+struct MountPoint {
+    path: PathBuf,
+    fs_type: String,
+    source: String,
+}
+
+/// A read-only snapshot of the filesystem of a directory, which is archived instead of the live
+/// directory; it's removed, when it's dropped.
+pub struct Snapshot {
+    kind: SnapshotKind,
+    /// The path of the directory within the snapshot.
+    pub path: PathBuf,
+    /// The btrfs subvolume, LVM volume or ZFS snapshot, which is removed.
+    name: String,
+    mountpoint: Option<PathBuf>,
+    removed: bool,
+}
+
+impl Snapshot {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn execute_command(command: &mut Command) -> Result<String, String> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = command
+            .output()
+            .map_err(|err| format!("error while executing {}: {}", program, err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "error while executing {}: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Decodes the octal escapes of a field of /proc/self/mountinfo, e.g. "\040" for a space.
+    fn decode_mountinfo_field(field: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = field.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            match (byte, tail.get(..3)) {
+                (b'\\', Some(octal)) if octal.iter().all(|digit| (b'0'..=b'7').contains(digit)) => {
+                    bytes.push(
+                        octal
+                            .iter()
+                            .fold(0u8, |value, digit| value * 8 + (digit - b'0')),
+                    );
+                    rest = &tail[3..];
+                }
+                _ => {
+                    bytes.push(byte);
+                    rest = tail;
+                }
+            }
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+
+    /// Finds the mounted filesystem, which contains the path.
+    fn find_mount_point(path: &Path) -> Result<MountPoint, String> {
+        let mountinfo = fs::read_to_string("/proc/self/mountinfo").map_err(Self::map_error)?;
+        mountinfo
+            .lines()
+            .filter_map(|line| {
+                let (mount_fields, fs_fields) = line.split_once(" - ")?;
+                let mount_path = Self::decode_mountinfo_field(mount_fields.split(' ').nth(4)?);
+                let mut fs_fields = fs_fields.split(' ');
+                Some(MountPoint {
+                    path: PathBuf::from(mount_path),
+                    fs_type: fs_fields.next()?.to_string(),
+                    source: Self::decode_mountinfo_field(fs_fields.next()?),
+                })
+            })
+            .filter(|mount_point| path.starts_with(&mount_point.path))
+            .max_by_key(|mount_point| mount_point.path.as_os_str().len())
+            .ok_or_else(|| format!("no filesystem found for {}", path.display()))
+    }
+
+    fn get_snapshot_name(path: &Path) -> String {
+        let name: String = path
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!(
+            "rusty_backup{}_{}",
+            name.trim_end_matches('_'),
+            Utc::now().format("%Y%m%d%H%M%S")
+        )
+    }
+
+    /// Creates a snapshot of the directory's filesystem as configured by its `snapshot`.
+    pub fn create(directory: &Directory) -> Result<Option<Snapshot>, String> {
+        let kind = match directory.snapshot {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+        let path = fs::canonicalize(&directory.name).map_err(Self::map_error)?;
+        let snapshot_name = Self::get_snapshot_name(&path);
+
+        let snapshot = match kind {
+            SnapshotKind::Btrfs => Self::create_btrfs(&path, &snapshot_name)?,
+            SnapshotKind::Lvm => Self::create_lvm(directory, &path, &snapshot_name)?,
+            SnapshotKind::Zfs => Self::create_zfs(&path, &snapshot_name)?,
+        };
+        info!(
+            "created {:?} snapshot of {} at {}",
+            kind,
+            directory.name,
+            snapshot.path.display()
+        );
+        Ok(Some(snapshot))
+    }
+
+    /// Snapshots the btrfs subvolume, which contains the path, into the subvolume itself; a
+    /// subvolume's root directory has the inode 256, and subvolumes have their own devices.
+    fn create_btrfs(path: &Path, snapshot_name: &str) -> Result<Snapshot, String> {
+        if Self::find_mount_point(path)?.fs_type != "btrfs" {
+            return Err(format!(
+                "{} isn't located on a btrfs filesystem.",
+                path.display()
+            ));
+        }
+        let device = fs::metadata(path).map_err(Self::map_error)?.dev();
+        let mut subvolume = path.to_path_buf();
+        loop {
+            if fs::metadata(&subvolume).map_err(Self::map_error)?.ino() == 256 {
+                break;
+            }
+            match subvolume.parent() {
+                Some(parent) if fs::metadata(parent).map_err(Self::map_error)?.dev() == device => {
+                    subvolume = parent.to_path_buf();
+                }
+                _ => {
+                    return Err(format!(
+                        "{} isn't located on a btrfs subvolume.",
+                        path.display()
+                    ))
+                }
+            }
+        }
+
+        let snapshot_path = subvolume.join(format!(".{}", snapshot_name));
+        Self::execute_command(
+            Command::new("btrfs")
+                .arg("subvolume")
+                .arg("snapshot")
+                .arg("-r")
+                .arg(&subvolume)
+                .arg(&snapshot_path),
+        )?;
+
+        Ok(Snapshot {
+            kind: SnapshotKind::Btrfs,
+            path: snapshot_path.join(path.strip_prefix(&subvolume).unwrap_or(Path::new(""))),
+            name: snapshot_path.to_string_lossy().to_string(),
+            mountpoint: None,
+            removed: false,
+        })
+    }
+
+    /// Snapshots the logical volume, which is mounted at the path's filesystem, and mounts the
+    /// snapshot read-only in the temporary directory.
+    fn create_lvm(
+        directory: &Directory,
+        path: &Path,
+        snapshot_name: &str,
+    ) -> Result<Snapshot, String> {
+        let mount_point = Self::find_mount_point(path)?;
+        let volume = Self::execute_command(
+            Command::new("lvs")
+                .arg("--noheadings")
+                .arg("--separator=/")
+                .arg("-o")
+                .arg("vg_name,lv_name")
+                .arg(&mount_point.source),
+        )
+        .map_err(|why| format!("{} isn't a logical volume: {}", mount_point.source, why))?;
+        let volume_group = volume.split('/').next().unwrap_or_default();
+
+        let snapshot_size = directory.snapshot_size.as_deref().unwrap_or("10%ORIGIN");
+        let mut create_command = Command::new("lvcreate");
+        create_command
+            .arg("--snapshot")
+            .arg("--name")
+            .arg(snapshot_name);
+        match snapshot_size.contains('%') {
+            true => create_command.arg("--extents").arg(snapshot_size),
+            false => create_command.arg("--size").arg(snapshot_size),
+        };
+        Self::execute_command(create_command.arg(&volume))?;
+
+        let mut snapshot = Snapshot {
+            kind: SnapshotKind::Lvm,
+            path: PathBuf::new(),
+            name: format!("{}/{}", volume_group, snapshot_name),
+            mountpoint: None,
+            removed: false,
+        };
+        // From here on, the snapshot is removed on errors, when it's dropped.
+        let mountpoint = env::temp_dir().join(snapshot_name);
+        fs::create_dir_all(&mountpoint).map_err(Self::map_error)?;
+        snapshot.mountpoint = Some(mountpoint.clone());
+        // XFS refuses to mount a snapshot with the same UUID as its origin.
+        let mount_options = match mount_point.fs_type.as_str() {
+            "xfs" => "ro,nouuid",
+            _ => "ro",
+        };
+        Self::execute_command(
+            Command::new("mount")
+                .arg("-o")
+                .arg(mount_options)
+                .arg(format!("/dev/{}", snapshot.name))
+                .arg(&mountpoint),
+        )?;
+
+        snapshot.path = mountpoint.join(path.strip_prefix(&mount_point.path).unwrap_or(path));
+        Ok(snapshot)
+    }
+
+    /// Snapshots the ZFS dataset, which is mounted at the path's filesystem; the snapshot is
+    /// accessible in the hidden ".zfs/snapshot" directory of the dataset.
+    fn create_zfs(path: &Path, snapshot_name: &str) -> Result<Snapshot, String> {
+        let mount_point = Self::find_mount_point(path)?;
+        if mount_point.fs_type != "zfs" {
+            return Err(format!(
+                "{} isn't located on a ZFS dataset.",
+                path.display()
+            ));
+        }
+        let name = format!("{}@{}", mount_point.source, snapshot_name);
+        Self::execute_command(Command::new("zfs").arg("snapshot").arg(&name))?;
+
+        Ok(Snapshot {
+            kind: SnapshotKind::Zfs,
+            path: mount_point
+                .path
+                .join(".zfs/snapshot")
+                .join(snapshot_name)
+                .join(path.strip_prefix(&mount_point.path).unwrap_or(path)),
+            name,
+            mountpoint: None,
+            removed: false,
+        })
+    }
+
+    /// Unmounts and removes the snapshot.
+    pub fn remove(&mut self) -> Result<(), String> {
+        if self.removed {
+            return Ok(());
+        }
+        self.removed = true;
+
+        match self.kind {
+            SnapshotKind::Btrfs => {
+                Self::execute_command(
+                    Command::new("btrfs")
+                        .arg("subvolume")
+                        .arg("delete")
+                        .arg(&self.name),
+                )?;
+            }
+            SnapshotKind::Lvm => {
+                if let Some(mountpoint) = &self.mountpoint {
+                    // The snapshot may not have been mounted, so unmounting mustn't fail.
+                    if let Err(why) = Self::execute_command(Command::new("umount").arg(mountpoint))
+                    {
+                        info!("{}", why);
+                    }
+                    fs::remove_dir(mountpoint).unwrap_or_default();
+                }
+                Self::execute_command(Command::new("lvremove").arg("--force").arg(&self.name))?;
+            }
+            SnapshotKind::Zfs => {
+                Self::execute_command(Command::new("zfs").arg("destroy").arg(&self.name))?;
+            }
+        }
+        info!("removed {:?} snapshot {}", self.kind, self.name);
+
+        Ok(())
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Err(why) = self.remove() {
+            error!("unable to remove the snapshot {}: {}", self.name, why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn requires_the_filesystem_of_the_snapshot_kind() {
+        let path = env::temp_dir();
+        let fs_type = Snapshot::find_mount_point(&path).unwrap().fs_type;
+        if fs_type != "btrfs" {
+            let err = Snapshot::create_btrfs(&path, "snapshot").err().unwrap();
+            assert!(
+                err.ends_with("isn't located on a btrfs filesystem."),
+                "{}",
+                err
+            );
+        }
+        if fs_type != "zfs" {
+            let err = Snapshot::create_zfs(&path, "snapshot").err().unwrap();
+            assert!(err.ends_with("isn't located on a ZFS dataset."), "{}", err);
+        }
+    }
+}
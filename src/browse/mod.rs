@@ -0,0 +1,179 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use chrono::{DateTime, Local};
+use log::warn;
+use tar::{EntryType, Header};
+
+use crate::configuration::archive::Archive;
+use crate::configuration::destination::{Destination, Kind as DestinationKind};
+use crate::configuration::Configuration;
+use crate::formatter::Formatter;
+use crate::restore::options::RestoreOptions;
+use crate::restore::selection::ArchiveSelection;
+
+pub struct Browse {}
+
+impl Browse {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Formats the type and permissions like `ls -l`, e.g. "drwxr-xr-x".
+    fn format_mode(header: &Header) -> String {
+        let mode = header.mode().unwrap_or_default();
+        let mut formatted_mode = String::from(match header.entry_type() {
+            EntryType::Directory => "d",
+            EntryType::Symlink => "l",
+            EntryType::Char => "c",
+            EntryType::Block => "b",
+            EntryType::Fifo => "p",
+            _ => "-",
+        });
+        for (bit, character) in [
+            (0o400, 'r'),
+            (0o200, 'w'),
+            (0o100, 'x'),
+            (0o040, 'r'),
+            (0o020, 'w'),
+            (0o010, 'x'),
+            (0o004, 'r'),
+            (0o002, 'w'),
+            (0o001, 'x'),
+        ] {
+            formatted_mode.push(if mode & bit != 0 { character } else { '-' });
+        }
+        formatted_mode
+    }
+
+    fn format_owner(header: &Header) -> String {
+        let user = match header.username() {
+            Ok(Some(username)) if !username.is_empty() => username.to_string(),
+            _ => header.uid().unwrap_or_default().to_string(),
+        };
+        let group = match header.groupname() {
+            Ok(Some(groupname)) if !groupname.is_empty() => groupname.to_string(),
+            _ => header.gid().unwrap_or_default().to_string(),
+        };
+        format!("{}/{}", user, group)
+    }
+
+    fn format_mtime(header: &Header) -> String {
+        match header
+            .mtime()
+            .ok()
+            .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0))
+        {
+            Some(mtime) => mtime
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+            None => String::from("-"),
+        }
+    }
+
+    /// Decrypts and decompresses the downloaded archive file on the fly and prints its entries,
+    /// which match the options; returns the number of printed entries.
+    fn print_entries(
+        archive: &Archive,
+        file_name: &str,
+        options: &RestoreOptions,
+    ) -> Result<usize, String> {
+        let (reader, mut child) = archive.open_tar_stream(file_name)?;
+        let mut tar = tar::Archive::new(reader);
+
+        let mut printed_entries = 0;
+        for entry in tar.entries().map_err(Self::map_error)? {
+            let entry = entry.map_err(Self::map_error)?;
+            let entry_path = entry
+                .path()
+                .map_err(Self::map_error)?
+                .to_string_lossy()
+                .to_string();
+            let original_path = archive
+                .directories
+                .iter()
+                .find_map(|directory| directory.get_original_path(&entry_path));
+            if !options.matches(&entry_path, original_path.as_deref()) {
+                continue;
+            }
+
+            let header = entry.header();
+            println!(
+                "  {} {:>17} {:>10}  {}  {}",
+                Self::format_mode(header),
+                Self::format_owner(header),
+                Formatter::format_size(entry.size() as usize, 2),
+                Self::format_mtime(header),
+                original_path.unwrap_or(entry_path)
+            );
+            printed_entries += 1;
+        }
+
+        // The rest of the stream is drained, so the decryption-program doesn't fail on writing.
+        io::copy(&mut tar.into_inner(), &mut io::sink()).map_err(Self::map_error)?;
+        if let Some(child) = &mut child {
+            let status = child.wait().map_err(Self::map_error)?;
+            if !status.success() {
+                return Err(format!(
+                    "decryption of '{}' failed with: {}",
+                    file_name, status
+                ));
+            }
+        }
+
+        Ok(printed_entries)
+    }
+
+    /// Prints the entries of the selected archive file (or of all matching archive files with
+    /// `all_versions`) of every archive; the include and path options search for entries.
+    pub async fn start(
+        configuration: Configuration,
+        archive_selection: ArchiveSelection,
+        options: RestoreOptions,
+        all_versions: bool,
+    ) -> Result<(), String> {
+        fs::create_dir_all(&configuration.working_directory).map_err(Self::map_error)?;
+        env::set_current_dir(&configuration.working_directory).map_err(Self::map_error)?;
+
+        for archive in &configuration.archives {
+            if archive.destination.kind == DestinationKind::None {
+                warn!(
+                    "the archive '{}' has no destination to browse.",
+                    archive.name
+                );
+                continue;
+            }
+
+            let storage = archive.destination.storage().await?;
+            let archive_files =
+                Destination::list_storage_archive_files(storage.as_ref(), archive).await?;
+            let mut archive_files = archive_selection.filter(archive, archive_files);
+            if !all_versions && archive_files.len() > 1 {
+                archive_files.drain(..archive_files.len() - 1);
+            }
+            if archive_files.is_empty() {
+                warn!(
+                    "no archive file found on destination '{}'.",
+                    archive.destination.id
+                );
+            }
+
+            for archive_file in archive_files {
+                storage.get_file(&archive_file.name).await?;
+                println!(
+                    "{} (destination: {})",
+                    archive_file.name, archive.destination.id
+                );
+                let result = Self::print_entries(archive, &archive_file.name, &options);
+                fs::remove_file(&archive_file.name).unwrap_or_default();
+                if result? == 0 {
+                    println!("  no matching entries");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,24 +1,210 @@
-use crate::configuration::{Compression, Database, Destination, Directory, Encryption};
+use std::fs::File;
+use std::io::Read;
+use std::process::Child;
+
+use regex::Regex;
+
+use crate::configuration::{
+    CommandSource, Compression, Database, Destination, Directory, Encryption, Hooks,
+};
 
 #[derive(Clone, Debug)]
 pub struct Archive {
+    pub commands: Vec<CommandSource>,
     pub compression: Compression,
     pub databases: Vec<Database>,
     pub destination: Destination,
     pub directories: Vec<Directory>,
     pub encryption: Option<Encryption>,
+    pub hooks: Hooks,
     pub name: String,
 }
 
 impl Archive {
     pub fn new() -> Archive {
         Archive {
+            commands: Vec::new(),
             compression: Compression::None,
             databases: Vec::new(),
             destination: Destination::new(),
             directories: Vec::new(),
             encryption: None,
+            hooks: Hooks::default(),
             name: String::new(),
         }
     }
+
+    /// Gets the extension of the stored archive files, e.g. ".tar.bz2.enc".
+    pub fn get_file_extension(&self) -> String {
+        let mut extension = self.compression.to_extension_string();
+        if let Some(encryption) = &self.encryption {
+            extension.push_str(encryption.to_extension_string().as_str());
+        }
+
+        extension
+    }
+
+    /// Gets the fixed part of the archive name in front of the first placeholder.
+    pub fn get_name_prefix(&self) -> Option<String> {
+        match self.name.find('{') {
+            Some(0) => None,
+            Some(index) => Some(String::from(&self.name[..index])),
+            None if self.name.is_empty() => None,
+            None => Some(self.name.clone()),
+        }
+    }
+
+    /// Opens the tar-stream of a downloaded archive file, which is decrypted and decompressed on
+    /// the fly; the returned decryption-program (if any) has to be waited for by the caller.
+    pub fn open_tar_stream(
+        &self,
+        file_name: &str,
+    ) -> Result<(Box<dyn Read + Send>, Option<Child>), String> {
+        let (reader, child): (Box<dyn Read + Send>, Option<Child>) = match &self.encryption {
+            Some(encryption) => {
+                let mut child = encryption.spawn_decryption(file_name)?;
+                match child.stdout.take() {
+                    Some(stdout) => (Box::new(stdout), Some(child)),
+                    None => return Err(String::from("no output of decryption-program.")),
+                }
+            }
+            None => (
+                Box::new(File::open(file_name).map_err(|err| format!("error: {:?}", err))?),
+                None,
+            ),
+        };
+
+        Ok((self.compression.open_tar_reader(reader)?, child))
+    }
+
+    /// Removes the compression and encryption extensions from the given file name.
+    pub fn strip_file_extension(&self, file_name: &str) -> String {
+        let mut archive_name = file_name.to_string();
+        if let Some(encryption) = &self.encryption {
+            let enc_ext = encryption.to_extension_string();
+            if archive_name.ends_with(&enc_ext) {
+                archive_name.truncate(archive_name.len() - enc_ext.len());
+            }
+        }
+        let comp_ext = self.compression.to_extension_string();
+        if archive_name.ends_with(&comp_ext) {
+            archive_name.truncate(archive_name.len() - comp_ext.len());
+        }
+
+        archive_name
+    }
+
+    /// Builds a regex, which only matches the file names, this archive produces, e.g.
+    /// `^\d{4}-\d{2}-\d{2}_www\.tar\.bz2$` for the name "{date:year}-{date:month}-{date:day}_www".
+    pub fn build_file_name_regex(&self) -> Regex {
+        lazy_static! {
+            static ref REGEX_PLACEHOLDER: Regex =
+                Regex::new(r"\{date:(year|month|day|weekday)\}").unwrap();
+        }
+
+        let mut pattern = String::from("^");
+        let mut literal_start = 0;
+        for caps in REGEX_PLACEHOLDER.captures_iter(&self.name) {
+            let placeholder = caps.get(0).unwrap();
+            pattern.push_str(&regex::escape(
+                &self.name[literal_start..placeholder.start()],
+            ));
+            pattern.push_str(match &caps[1] {
+                "year" => r"\d{4,}",
+                "month" | "day" => r"\d{2}",
+                _ => "(Mon|Tue|Wed|Thu|Fri|Sat|Sun)",
+            });
+            literal_start = placeholder.end();
+        }
+        pattern.push_str(&regex::escape(&self.name[literal_start..]));
+        pattern.push_str(&regex::escape(&self.get_file_extension()));
+        pattern.push('$');
+
+        // all literal parts are escaped, so the pattern is always valid.
+        Regex::new(&pattern).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(name: &str, compression: Compression, encrypted: bool) -> Archive {
+        let mut archive = Archive::new();
+        archive.name = String::from(name);
+        archive.compression = compression;
+        if encrypted {
+            archive.encryption = Some(Encryption::new());
+        }
+        archive
+    }
+
+    #[test]
+    fn gets_the_name_prefix_in_front_of_the_placeholders() {
+        let www = archive("www_{date:year}{date:month}", Compression::Tar, false);
+        assert_eq!(www.get_name_prefix().as_deref(), Some("www_"));
+        let dated = archive("{date:year}_www", Compression::Tar, false);
+        assert_eq!(dated.get_name_prefix(), None);
+        let fixed = archive("www", Compression::Tar, false);
+        assert_eq!(fixed.get_name_prefix().as_deref(), Some("www"));
+        assert_eq!(Archive::new().get_name_prefix(), None);
+    }
+
+    #[test]
+    fn file_name_regex_matches_the_placeholders() {
+        let regex = archive(
+            "{date:year}-{date:month}-{date:day}_www",
+            Compression::TarBZ2,
+            false,
+        )
+        .build_file_name_regex();
+        assert!(regex.is_match("2024-03-01_www.tar.bz2"));
+        assert!(!regex.is_match("24-03-01_www.tar.bz2"));
+        assert!(!regex.is_match("2024-3-01_www.tar.bz2"));
+
+        let regex = archive("www_{date:weekday}", Compression::Tar, false).build_file_name_regex();
+        assert!(regex.is_match("www_Fri.tar"));
+        assert!(!regex.is_match("www_Friday.tar"));
+    }
+
+    #[test]
+    fn file_name_regex_is_anchored() {
+        let regex = archive("www_{date:day}", Compression::TarBZ2, true).build_file_name_regex();
+        assert!(regex.is_match("www_01.tar.bz2.enc"));
+        // Archives sharing a prefix or a suffix and the companion files don't match.
+        assert!(!regex.is_match("old_www_01.tar.bz2.enc"));
+        assert!(!regex.is_match("www_01_full.tar.bz2.enc"));
+        assert!(!regex.is_match("www_01.tar.bz2"));
+        assert!(!regex.is_match("www_01.tar.bz2.enc.sha256"));
+        assert!(!regex.is_match("www_01.manifest.json.enc"));
+    }
+
+    #[test]
+    fn file_name_regex_escapes_the_literal_parts() {
+        // Unescaped, these names would be invalid regexes or match any character.
+        for name in ["www[1", "www(1", "www+{date:day}", "w.w*w?{date:day}"] {
+            let regex = archive(name, Compression::Tar, false).build_file_name_regex();
+            let file_name = format!("{}.tar", name.replace("{date:day}", "01"));
+            assert!(regex.is_match(&file_name), "{}", file_name);
+        }
+
+        let regex = archive("w.w_{date:day}", Compression::Tar, false).build_file_name_regex();
+        assert!(!regex.is_match("wXw_01.tar"));
+        assert!(!regex.is_match("w.w_01Xtar"));
+    }
+
+    #[test]
+    fn file_name_regex_keeps_unknown_placeholders_literal() {
+        let regex = archive("www_{date:hour}", Compression::Tar, false).build_file_name_regex();
+        assert!(regex.is_match("www_{date:hour}.tar"));
+        assert!(!regex.is_match("www_12.tar"));
+    }
+
+    #[test]
+    fn strips_the_file_extensions() {
+        let archive = archive("www", Compression::TarBZ2, true);
+        assert_eq!(archive.strip_file_extension("www_01.tar.bz2.enc"), "www_01");
+        assert_eq!(archive.strip_file_extension("www_01.tar.bz2"), "www_01");
+        assert_eq!(archive.strip_file_extension("www_01.zip"), "www_01.zip");
+    }
 }
@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+
+use log::info;
+
+/// A source of an archive, which only exists as the output of a command (e.g. `crontab -l`); the
+/// output is archived as the entry `name` and restored by piping it into the `restore` command.
+#[derive(Clone, Debug)]
+pub struct CommandSource {
+    pub name: String,
+    pub exec: String,
+    pub restore: Option<String>,
+}
+
+impl CommandSource {
+    pub fn new() -> Self {
+        Self {
+            name: String::new(),
+            exec: String::new(),
+            restore: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.exec.is_empty() {
+            return Err(format!("the command-exec must be set for '{}'", self.name));
+        }
+        let path = Path::new(&self.name);
+        if self.name.is_empty()
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!(
+                "the command-name '{}' must be a relative path",
+                self.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Executes `exec` with `sh -c` and writes its output into the file.
+    pub fn capture(&self, file: File) -> Result<(), String> {
+        info!("executing command: {}", self.exec);
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.exec)
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(file))
+            .status()
+            .map_err(|err| format!("unable to execute the command '{}': {}", self.exec, err))?;
+        if !status.success() {
+            return Err(format!("the command '{}' failed: {}", self.exec, status));
+        }
+
+        Ok(())
+    }
+
+    /// Executes the `restore` command with `sh -c`, which reads the archived output from its
+    /// input.
+    pub fn restore_from<R: Read>(&self, reader: &mut R) -> Result<(), String> {
+        let restore = match &self.restore {
+            Some(restore) => restore,
+            None => return Err(format!("no restore command is set for '{}'", self.name)),
+        };
+
+        info!("executing restore command: {}", restore);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(restore)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("unable to execute the command '{}': {}", restore, err))?;
+        let copy_result = match child.stdin.take() {
+            Some(mut stdin) => io::copy(reader, &mut stdin).map(|_| ()),
+            None => Ok(()),
+        };
+        let status = child
+            .wait()
+            .map_err(|err| format!("error while waiting for the command: {}", err))?;
+        if !status.success() {
+            return Err(format!("the command '{}' failed: {}", restore, status));
+        }
+        copy_result
+            .map_err(|err| format!("unable to write into the command '{}': {}", restore, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn temp_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!(
+                "rusty-backup-command-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn command(exec: &str, restore: Option<String>) -> CommandSource {
+        CommandSource {
+            name: String::from("crontab"),
+            exec: String::from(exec),
+            restore,
+        }
+    }
+
+    #[test]
+    fn captures_the_output() {
+        let output_path = temp_path("capture");
+        let result =
+            command("echo '0 3 * * * backup'", None).capture(File::create(&output_path).unwrap());
+        let output = fs::read_to_string(&output_path).unwrap();
+        fs::remove_file(&output_path).unwrap();
+
+        result.unwrap();
+        assert_eq!(output, "0 3 * * * backup\n");
+    }
+
+    #[test]
+    fn fails_on_the_exit_status_of_the_command() {
+        let output_path = temp_path("status");
+        let result =
+            command("echo partial; exit 3", None).capture(File::create(&output_path).unwrap());
+        fs::remove_file(&output_path).unwrap();
+
+        let err = result.unwrap_err();
+        assert!(err.contains("exit status: 3"), "{}", err);
+    }
+
+    #[test]
+    fn restores_by_piping_into_the_restore_command() {
+        let restored_path = temp_path("restored");
+        let command = command("crontab -l", Some(format!("cat > {}", restored_path)));
+        let result = command.restore_from(&mut "0 3 * * * backup\n".as_bytes());
+        let restored = fs::read_to_string(&restored_path).unwrap();
+        fs::remove_file(&restored_path).unwrap();
+
+        result.unwrap();
+        assert_eq!(restored, "0 3 * * * backup\n");
+
+        let failing = self::command("crontab -l", Some(String::from("cat > /dev/null; exit 2")));
+        assert!(failing.restore_from(&mut "".as_bytes()).is_err());
+        assert!(self::command("crontab -l", None)
+            .restore_from(&mut "".as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn requires_a_relative_name() {
+        let mut command = command("crontab -l", None);
+        assert!(command.validate().is_ok());
+        command.name = String::from("crontabs/root");
+        assert!(command.validate().is_ok());
+        for name in ["", "/etc/crontab", "../crontab"] {
+            command.name = String::from(name);
+            assert!(command.validate().is_err(), "{}", name);
+        }
+    }
+}
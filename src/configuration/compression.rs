@@ -1,17 +1,25 @@
 use std::{
+    collections::HashMap,
     fs,
     fs::File,
-    io::{Read, Write},
-    os::unix::fs::chown,
+    io::{ErrorKind, Read, Write},
+    os::unix::fs::PermissionsExt,
     path::Path,
 };
 
 use bzip2::read::BzDecoder;
-use log::{error, info};
+use log::{error, info, warn};
+use nix::sys::stat::{makedev, mknod, Mode, SFlag};
 use regex::Regex;
+use tar::EntryType;
 
+use crate::backup::manifest::Manifest;
 use crate::configuration::{Configuration, Directory};
+use crate::helper::attributes::FileAttributes;
+use crate::restore::diff::RestoreDiff;
+use crate::restore::options::RestoreOptions;
 
+use super::command::CommandSource;
 use super::database::Database;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -27,11 +35,13 @@ impl Compression {
         file: S,
         output_dirs: &Vec<Directory>,
         dbs: &Vec<Database>,
+        commands: &[CommandSource],
+        options: &RestoreOptions,
     ) -> Result<(), String> {
         match self {
             Self::None => Ok(()),
-            Self::Tar => self.decompress_tar_file(file, output_dirs, dbs),
-            Self::TarBZ2 => self.decompress_tar_bz2_file(file, output_dirs, dbs),
+            Self::Tar => self.decompress_tar_file(file, output_dirs, dbs, commands, options),
+            Self::TarBZ2 => self.decompress_tar_bz2_file(file, output_dirs, dbs, commands, options),
         }
     }
 
@@ -40,6 +50,8 @@ impl Compression {
         file: S,
         output_dirs: &Vec<Directory>,
         dbs: &Vec<Database>,
+        commands: &[CommandSource],
+        options: &RestoreOptions,
     ) -> Result<(), String> {
         let file = file.as_ref();
         info!("extracting bz2-file: {}", file);
@@ -78,7 +90,7 @@ impl Compression {
 
         info!("completed!");
 
-        self.decompress_tar_file(tar_filename, output_dirs, dbs)
+        self.decompress_tar_file(tar_filename, output_dirs, dbs, commands, options)
     }
 
     fn decompress_tar_file<S: AsRef<str>>(
@@ -86,6 +98,8 @@ impl Compression {
         tar_filename: S,
         output_dirs: &Vec<Directory>,
         dbs: &Vec<Database>,
+        commands: &[CommandSource],
+        options: &RestoreOptions,
     ) -> Result<(), String> {
         let tar_filename = tar_filename.as_ref();
 
@@ -102,6 +116,12 @@ impl Compression {
             Err(err) => return Err(format!("{}", err)),
         };
 
+        let mut manifest: Option<Manifest> = None;
+        let mut restored_files: Vec<(String, String)> = Vec::new();
+        // The restored paths of the entries, which hardlinks may refer to.
+        let mut restored_paths: HashMap<String, String> = HashMap::new();
+        let mut diff = RestoreDiff::default();
+        let mut found_directories: Vec<&Directory> = Vec::new();
         entries.for_each(|e| {
             let mut entry = match e {
                 Ok(entry) => entry,
@@ -112,6 +132,13 @@ impl Compression {
                 Ok(entry_path) => entry_path.to_string_lossy().to_string(),
                 Err(_) => return,
             };
+            if entry_str == Manifest::ENTRY_NAME {
+                match Manifest::from_reader(&mut entry) {
+                    Ok(archive_manifest) => manifest = Some(archive_manifest),
+                    Err(err) => error!("invalid manifest: {}", err),
+                }
+                return;
+            }
             let mut entry_directory_found = false;
             for directory in output_dirs {
                 let dir_path = Path::new(&directory.name);
@@ -130,10 +157,57 @@ impl Compression {
                     Some(parent_dir) => parent_dir,
                     None => break,
                 };
-                let dst = format!("{}/{}", parent_dir.to_string_lossy(), entry_str);
+                let original_dst = format!("{}/{}", parent_dir.to_string_lossy(), entry_str);
+                entry_directory_found = true;
+                if !found_directories
+                    .iter()
+                    .any(|found| found.name == directory.name)
+                {
+                    found_directories.push(directory);
+                }
+                if options.databases_only || !options.matches(&entry_str, Some(&original_dst)) {
+                    break;
+                }
+                let dst = match &options.target {
+                    Some(target) => format!("{}/{}", target.trim_end_matches('/'), entry_str),
+                    None => original_dst,
+                };
                 let dst_path = Path::new(dst.as_str());
-                match entry.unpack(dst_path) {
+                if options.dry_run {
+                    if let Err(err) = diff.compare_entry(&mut entry, dst_path) {
+                        error!("{}", err);
+                    }
+                    break;
+                }
+                // The entries of the parent directories may have been filtered out.
+                if let Some(dst_parent) = dst_path.parent() {
+                    fs::create_dir_all(dst_parent).unwrap_or_default();
+                }
+                let pax_records = match FileAttributes::read_pax_records(&mut entry) {
+                    Ok(pax_records) => pax_records,
+                    Err(err) => {
+                        error!("{}", err);
+                        Vec::new()
+                    }
+                };
+                entry.set_preserve_permissions(true);
+                let entry_type = entry.header().entry_type();
+                let unpack_result = match entry_type {
+                    EntryType::Link => Self::unpack_hardlink(&entry, dst_path, &restored_paths),
+                    EntryType::Fifo | EntryType::Char | EntryType::Block => {
+                        Self::unpack_special_file(entry.header(), dst_path)
+                    }
+                    _ => entry
+                        .unpack(dst_path)
+                        .map(|_| ())
+                        .map_err(|err| format!("{}", err)),
+                };
+                match unpack_result {
                     Ok(_) => {
+                        restored_paths.insert(entry_str.clone(), dst.clone());
+                        if entry_type.is_file() || entry_type.is_gnu_sparse() {
+                            restored_files.push((entry_str.clone(), dst.clone()));
+                        }
                         let uid_opt = match directory.get_uid() {
                             Some(uid) => Some(uid.as_raw()),
                             None => None,
@@ -142,23 +216,34 @@ impl Compression {
                             Some(gid) => Some(gid.as_raw()),
                             None => None,
                         };
-                        if uid_opt.is_some() || gid_opt.is_some() {
-                            chown(dst_path, uid_opt, gid_opt).unwrap_or_default();
+                        if let Err(err) = FileAttributes::restore(
+                            dst_path,
+                            entry.header(),
+                            &pax_records,
+                            options.owners,
+                            uid_opt,
+                            gid_opt,
+                        ) {
+                            error!("{}: {}", dst, err);
                         }
                     }
                     Err(err) => {
                         error!("{}", err);
                     }
                 }
-                entry_directory_found = true;
                 break;
             }
 
-            if !entry_directory_found {
+            if !entry_directory_found && !options.files_only {
                 for db in dbs {
-                    let expected_string = format!("{}{}", db.name, db.kind.to_extension_string());
+                    let expected_string = db.build_dump_filename();
                     let expected_str = expected_string.as_str();
-                    if expected_str != entry_str {
+                    if expected_str != entry_str || !options.matches(expected_str, None) {
+                        continue;
+                    }
+                    if options.dry_run {
+                        diff.databases
+                            .push(options.describe_database_restore(&db.name));
                         continue;
                     }
 
@@ -167,6 +252,28 @@ impl Compression {
                         continue;
                     };
 
+                    if options.is_safe_database_restore() {
+                        let db = Database {
+                            host: options.database_host.clone().or_else(|| db.host.clone()),
+                            ..db.clone()
+                        };
+                        let result = File::open(expected_str)
+                            .map_err(|err| format!("file-error: {}", err))
+                            .and_then(|file| {
+                                db.restore_into(
+                                    file,
+                                    options.build_database_name(&db.name).as_deref(),
+                                    options.swap_databases,
+                                )
+                            });
+                        match result {
+                            Ok(name) => info!("restored database '{}' as '{}'", db.name, name),
+                            Err(err) => error!("db-error: {}", err),
+                        }
+                        fs::remove_file(expected_str).unwrap_or_default();
+                        continue;
+                    }
+
                     if let Err(err) = db.delete_database() {
                         error!("db-error: {}", err);
                         continue;
@@ -195,6 +302,47 @@ impl Compression {
                     }
                 }
             }
+
+            if entry_directory_found || options.files_only || options.databases_only {
+                return;
+            }
+            let command = match commands.iter().find(|command| command.name == entry_str) {
+                Some(command) if options.matches(&entry_str, None) => command,
+                _ => return,
+            };
+            // With a target, the output is restored as file instead of running the command.
+            let dst = options
+                .target
+                .as_ref()
+                .map(|target| format!("{}/{}", target.trim_end_matches('/'), entry_str));
+            if options.dry_run {
+                diff.commands.push(match (&dst, &command.restore) {
+                    (Some(dst), _) => format!("{} => {}", entry_str, dst),
+                    (None, Some(restore)) => format!("{} => {}", entry_str, restore),
+                    (None, None) => format!("{} (no restore command)", entry_str),
+                });
+                return;
+            }
+            let result = match dst {
+                Some(dst) => {
+                    if let Some(dst_parent) = Path::new(&dst).parent() {
+                        fs::create_dir_all(dst_parent).unwrap_or_default();
+                    }
+                    entry
+                        .unpack(&dst)
+                        .map(|_| ())
+                        .map_err(|err| format!("{}", err))
+                }
+                None if command.restore.is_none() => {
+                    warn!("no restore command is set for '{}'; skipped.", entry_str);
+                    return;
+                }
+                None => command.restore_from(&mut entry),
+            };
+            match result {
+                Ok(_) => info!("restored the output of the command '{}'", entry_str),
+                Err(err) => error!("command-error: {}", err),
+            }
         });
 
         if let Err(err) = fs::remove_file(&tar_filename) {
@@ -204,11 +352,145 @@ impl Compression {
             );
         }
 
+        if options.dry_run {
+            for directory in found_directories {
+                let dir_path = Path::new(&directory.name);
+                let dir_name = dir_path.file_name().unwrap_or_default();
+                let dst_root = match &options.target {
+                    Some(target) => Path::new(target).join(dir_name),
+                    None => dir_path.to_path_buf(),
+                };
+                diff.find_deleted(&dst_root, &dir_name.to_string_lossy(), dir_path, options);
+            }
+            diff.print(tar_filename);
+            return Ok(());
+        }
+
         info!("completed!");
 
+        Self::validate_restored_files(manifest.as_ref(), &restored_files, tar_filename)
+    }
+
+    /// Links the hardlink entry to the restored path of its target, which precedes it in the
+    /// archive.
+    fn unpack_hardlink<R: Read>(
+        entry: &tar::Entry<R>,
+        dst_path: &Path,
+        restored_paths: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let link_name = match entry.link_name().map_err(|err| format!("{}", err))? {
+            Some(link_name) => link_name.to_string_lossy().to_string(),
+            None => {
+                return Err(format!(
+                    "the hardlink {} has no target.",
+                    dst_path.display()
+                ))
+            }
+        };
+        let target = restored_paths.get(&link_name).ok_or_else(|| {
+            format!(
+                "the target '{}' of the hardlink {} wasn't restored.",
+                link_name,
+                dst_path.display()
+            )
+        })?;
+
+        match fs::remove_file(dst_path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(format!("{}", err)),
+            _ => {}
+        }
+        fs::hard_link(target, dst_path).map_err(|err| format!("{}", err))
+    }
+
+    /// Creates the FIFO or device file of the entry, replacing an existing file.
+    fn unpack_special_file(header: &tar::Header, dst_path: &Path) -> Result<(), String> {
+        let mode = header.mode().map_err(|err| format!("{}", err))? & 0o7777;
+        let kind = match header.entry_type() {
+            EntryType::Fifo => SFlag::S_IFIFO,
+            EntryType::Char => SFlag::S_IFCHR,
+            _ => SFlag::S_IFBLK,
+        };
+        let device = makedev(
+            header
+                .device_major()
+                .map_err(|err| format!("{}", err))?
+                .unwrap_or_default() as u64,
+            header
+                .device_minor()
+                .map_err(|err| format!("{}", err))?
+                .unwrap_or_default() as u64,
+        );
+
+        match fs::remove_file(dst_path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(format!("{}", err)),
+            _ => {}
+        }
+        mknod(dst_path, kind, Mode::from_bits_truncate(mode), device)
+            .map_err(|errno| format!("{}: {}", dst_path.display(), errno))?;
+        // The mode passed to mknod is reduced by the umask.
+        fs::set_permissions(dst_path, fs::Permissions::from_mode(mode))
+            .map_err(|err| format!("{}", err))
+    }
+
+    /// Checks the restored files (entry path and restored file) against the manifest of the
+    /// archive, if it has one.
+    fn validate_restored_files(
+        manifest: Option<&Manifest>,
+        restored_files: &[(String, String)],
+        tar_filename: &str,
+    ) -> Result<(), String> {
+        let manifest = match manifest {
+            Some(manifest) => manifest,
+            None => {
+                warn!(
+                    "no manifest found in '{}', the restored files aren't validated.",
+                    tar_filename
+                );
+                return Ok(());
+            }
+        };
+
+        let invalid_files = restored_files
+            .iter()
+            .filter(
+                |(entry_path, file_name)| match manifest.validate_file(entry_path, file_name) {
+                    Ok(_) => false,
+                    Err(err) => {
+                        error!("{}", err);
+                        true
+                    }
+                },
+            )
+            .count();
+        if invalid_files > 0 {
+            return Err(format!(
+                "{} of {} restored files don't match the manifest.",
+                invalid_files,
+                restored_files.len()
+            ));
+        }
+
+        info!(
+            "validated {} restored files against the manifest.",
+            restored_files.len()
+        );
         Ok(())
     }
 
+    /// Wraps the given reader of a compressed archive into a reader of the tar-stream.
+    pub fn open_tar_reader<'a, R: Read + Send + 'a>(
+        &self,
+        reader: R,
+    ) -> Result<Box<dyn Read + Send + 'a>, String> {
+        match self {
+            Self::None => Err(String::from(
+                "archives without compression contain no tar-stream.",
+            )),
+            Self::Tar => Ok(Box::new(reader)),
+            Self::TarBZ2 => Ok(Box::new(BzDecoder::new(reader))),
+        }
+    }
+
     pub fn to_extension_string(&self) -> String {
         match self {
             Self::None => String::new(),
@@ -217,3 +499,152 @@ impl Compression {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    use tar::{Builder, Header};
+
+    /// Builds a tar-stream with a file, a hardlink to it and a FIFO.
+    fn build_tar() -> Vec<u8> {
+        let mut tar = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(7);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "data/original", "content".as_bytes())
+            .unwrap();
+
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Link);
+        header.set_size(0);
+        tar.append_link(&mut header, "data/link", "data/original")
+            .unwrap();
+
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Fifo);
+        header.set_size(0);
+        header.set_mode(0o640);
+        // As set from the metadata of a FIFO.
+        header.set_device_major(0).unwrap();
+        header.set_device_minor(0).unwrap();
+        tar.append_data(&mut header, "data/pipe", std::io::empty())
+            .unwrap();
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn unpacks_hardlinks_and_fifos() {
+        let root = env::temp_dir().join(format!("rusty-backup-unpack-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let content = build_tar();
+        let mut archive = tar::Archive::new(content.as_slice());
+        let mut restored_paths = HashMap::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let entry_str = entry.path().unwrap().to_string_lossy().to_string();
+            let dst = root.join(entry_str.trim_start_matches("data/"));
+            match entry.header().entry_type() {
+                EntryType::Link => Compression::unpack_hardlink(&entry, &dst, &restored_paths),
+                EntryType::Fifo => Compression::unpack_special_file(entry.header(), &dst),
+                _ => entry
+                    .unpack(&dst)
+                    .map(|_| ())
+                    .map_err(|err| format!("{}", err)),
+            }
+            .unwrap();
+            restored_paths.insert(entry_str, dst.to_string_lossy().to_string());
+        }
+
+        let original = fs::metadata(root.join("original")).unwrap();
+        let link = fs::metadata(root.join("link")).unwrap();
+        let pipe = fs::symlink_metadata(root.join("pipe")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(link.ino(), original.ino());
+        assert_eq!(original.nlink(), 2);
+        assert!(pipe.file_type().is_fifo());
+        assert_eq!(pipe.mode() & 0o7777, 0o640);
+    }
+
+    #[test]
+    fn fails_on_a_hardlink_to_a_file_not_restored() {
+        let content = build_tar();
+        let mut archive = tar::Archive::new(content.as_slice());
+        let entry = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.header().entry_type() == EntryType::Link)
+            .unwrap();
+
+        let result = Compression::unpack_hardlink(
+            &entry,
+            &env::temp_dir().join("rusty-backup-unrestored-link"),
+            &HashMap::new(),
+        );
+        assert!(result.unwrap_err().contains("wasn't restored"));
+    }
+
+    /// Writes an archive with the output of the command "crontab" and restores it.
+    fn restore_command_output(name: &str, target: Option<String>, restore: String) {
+        let tar_filename = env::temp_dir()
+            .join(format!(
+                "rusty-backup-commands-{}-{}.tar",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string();
+        let mut tar = Builder::new(File::create(&tar_filename).unwrap());
+        let mut header = Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "crontab", "jobs\n".as_bytes())
+            .unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+
+        let commands = [CommandSource {
+            name: String::from("crontab"),
+            exec: String::from("crontab -l"),
+            restore: Some(restore),
+        }];
+        let options = RestoreOptions {
+            target,
+            ..RestoreOptions::default()
+        };
+        Compression::Tar
+            .decompress_tar_file(&tar_filename, &Vec::new(), &Vec::new(), &commands, &options)
+            .unwrap();
+    }
+
+    #[test]
+    fn restores_command_outputs_with_the_restore_command() {
+        let restored = env::temp_dir().join(format!("rusty-backup-crontab-{}", std::process::id()));
+        restore_command_output("restore", None, format!("cat > {}", restored.display()));
+        let content = fs::read_to_string(&restored).unwrap();
+        fs::remove_file(&restored).unwrap();
+
+        assert_eq!(content, "jobs\n");
+    }
+
+    #[test]
+    fn restores_command_outputs_into_the_target_as_files() {
+        let target = env::temp_dir().join(format!("rusty-backup-target-{}", std::process::id()));
+        let restored = env::temp_dir().join(format!("rusty-backup-not-run-{}", std::process::id()));
+        restore_command_output(
+            "target",
+            Some(target.to_string_lossy().to_string()),
+            format!("cat > {}", restored.display()),
+        );
+        let content = fs::read_to_string(target.join("crontab"));
+        fs::remove_dir_all(&target).unwrap_or_default();
+
+        assert_eq!(content.unwrap(), "jobs\n");
+        assert!(!restored.exists());
+    }
+}
@@ -1,16 +1,24 @@
 use std::{
-    fs::File,
-    io::{Read, Write},
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::fs::{lchown, MetadataExt},
+    path::Path,
     process::{Command, Stdio},
 };
 
-use log::info;
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::configuration::{Configuration, Credential};
 
 #[derive(Clone, Debug)]
 pub struct Database {
     pub credential: Credential,
+    /// The server, the database is dumped from and restored to (default: the local server).
+    pub host: Option<String>,
     pub id: String,
     pub kind: Kind,
     pub name: String,
@@ -21,6 +29,7 @@ impl Database {
     pub fn new() -> Database {
         Database {
             credential: Credential::new(),
+            host: None,
             id: String::new(),
             kind: Kind::MySql,
             name: String::new(),
@@ -29,16 +38,47 @@ impl Database {
     }
 
     pub fn build_dump_filename(&self) -> String {
-        let mut db_filename = format!("{}.sql", self.name);
-
         match self.kind {
-            Kind::MongoDB => {
-                db_filename = format!("{}.bson", self.name);
-            }
-            _ => {}
+            // The name of a SQLite database is the path of its file.
+            Kind::Sqlite => format!(
+                "{}{}",
+                self.build_sqlite_name(),
+                self.kind.to_extension_string()
+            ),
+            _ => format!("{}{}", self.name, self.kind.to_extension_string()),
         }
+    }
+
+    /// Gets the file name of the SQLite database with a hash of its path, so databases with the
+    /// same file name in different directories are kept apart, e.g. "app.db_3f2a9c1e".
+    fn build_sqlite_name(&self) -> String {
+        let file_name = Path::new(&self.name)
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.name.clone());
+        let path_hash: String = Sha256::digest(self.name.as_bytes())
+            .iter()
+            .take(4)
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        format!("{}_{}", file_name, path_hash)
+    }
 
-        db_filename
+    /// Gets the first line of the dump program's version, e.g. "pg_dump (PostgreSQL) 15.6".
+    pub fn get_dump_tool_version(&self) -> Option<String> {
+        let program = match self.kind {
+            Kind::MongoDB => "mongodump",
+            Kind::MySql => "mysqldump",
+            Kind::PostgreSql => "pg_dump",
+            Kind::Sqlite => "sqlite3",
+        };
+        let output = Command::new(program).arg("--version").output().ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(String::from)
     }
 
     pub fn build_dump_command(&self) -> Command {
@@ -46,6 +86,9 @@ impl Database {
             Kind::MongoDB => {
                 let mut cmd = Command::new("mongodump");
                 cmd.arg("--archive");
+                if let Some(host) = &self.host {
+                    cmd.arg(format!("--host={}", host));
+                }
 
                 if self.name != "*" {
                     cmd.arg(format!("--db='{}'", self.name));
@@ -65,6 +108,9 @@ impl Database {
                         cmd.arg(format!("-p{}", self.credential.password));
                     }
                 }
+                if let Some(host) = &self.host {
+                    cmd.arg(format!("--host={}", host));
+                }
                 cmd.arg("--databases");
 
                 if self.name_is_regex {
@@ -83,12 +129,31 @@ impl Database {
                         cmd.env("PGPASSWORD", &self.credential.password);
                     }
                 }
-                cmd.arg("--host=localhost");
+                cmd.arg(format!(
+                    "--host={}",
+                    self.host.as_deref().unwrap_or("localhost")
+                ));
                 cmd.arg(format!("--dbname={}", self.name));
 
                 info!("dumping postgresql-database: {}", self.name);
                 cmd
             }
+            Kind::Sqlite => {
+                // The online backup API copies a consistent state of the database, even while
+                // it's written to; the copy is written to the dump file instead of stdout.
+                let mut cmd = Command::new("sqlite3");
+                cmd.arg("-bail")
+                    .arg("-cmd")
+                    .arg(".timeout 10000")
+                    .arg(&self.name)
+                    .arg(format!(
+                        ".backup {}",
+                        Self::quote_sqlite_argument(&self.build_dump_filename())
+                    ));
+
+                info!("dumping sqlite-database: {}", self.name);
+                cmd
+            }
         }
     }
 
@@ -96,16 +161,13 @@ impl Database {
         match self.kind {
             Kind::MongoDB => Command::new("echo"),
             Kind::MySql => {
-                let mut cmd = Command::new("mysql");
-                cmd.arg("-u")
-                    .arg(self.credential.username.clone())
-                    .arg(format!("-p{}", self.credential.password))
-                    .arg("-e")
+                let mut cmd = self.build_mysql_command();
+                cmd.arg("-e")
                     .arg(format!("CREATE DATABASE IF NOT EXISTS `{}`", self.name));
 
                 cmd
             }
-            Kind::PostgreSql => Command::new("echo"),
+            Kind::PostgreSql | Kind::Sqlite => Command::new("echo"),
         }
     }
 
@@ -113,16 +175,13 @@ impl Database {
         match self.kind {
             Kind::MongoDB => Command::new("echo"),
             Kind::MySql => {
-                let mut cmd = Command::new("mysql");
-                cmd.arg("-u")
-                    .arg(self.credential.username.clone())
-                    .arg(format!("-p{}", self.credential.password))
-                    .arg("-e")
+                let mut cmd = self.build_mysql_command();
+                cmd.arg("-e")
                     .arg(format!("DROP DATABASE IF EXISTS `{}`", self.name));
 
                 cmd
             }
-            Kind::PostgreSql => Command::new("echo"),
+            Kind::PostgreSql | Kind::Sqlite => Command::new("echo"),
         }
     }
 
@@ -137,15 +196,12 @@ impl Database {
                 cmd
             }
             Kind::MySql => {
-                let mut cmd = Command::new("mysql");
-                cmd.arg("-u")
-                    .arg(self.credential.username.clone())
-                    .arg(format!("-p{}", self.credential.password))
-                    .arg(self.name.clone());
+                let mut cmd = self.build_mysql_command();
+                cmd.arg(self.name.clone());
 
                 cmd
             }
-            Kind::PostgreSql => {
+            Kind::PostgreSql | Kind::Sqlite => {
                 let cmd = Command::new("echo");
 
                 cmd
@@ -194,6 +250,10 @@ impl Database {
     }
 
     pub fn import_database(&self, mut file: File) -> Result<(), String> {
+        if self.kind == Kind::Sqlite {
+            return Self::replace_sqlite_database(&self.name, file);
+        }
+
         let mut db_import_command = self.build_import_command();
         db_import_command.stdin(Stdio::piped());
         let child = match db_import_command.spawn() {
@@ -229,13 +289,529 @@ impl Database {
 
         Ok(())
     }
+
+    /// Gets the name of the scratch database, a dump of this database is test-imported into; for
+    /// SQLite, it's a file in the temporary directory.
+    pub fn build_scratch_name(&self) -> String {
+        match self.kind {
+            Kind::Sqlite => env::temp_dir()
+                .join(format!("rusty_backup_verify_{}", self.build_sqlite_name()))
+                .to_string_lossy()
+                .to_string(),
+            _ => format!("rusty_backup_verify_{}", self.name),
+        }
+    }
+
+    fn build_mysql_command(&self) -> Command {
+        let mut cmd = Command::new("mysql");
+        if !self.credential.username.is_empty() {
+            cmd.arg("-u").arg(&self.credential.username);
+            if !self.credential.password.is_empty() {
+                cmd.arg(format!("-p{}", self.credential.password));
+            }
+        }
+        if let Some(host) = &self.host {
+            cmd.arg(format!("--host={}", host));
+        }
+
+        cmd
+    }
+
+    fn build_psql_command(&self, dbname: &str) -> Command {
+        let mut cmd = Command::new("psql");
+        if !self.credential.username.is_empty() {
+            cmd.arg(format!("--username={}", self.credential.username));
+            if !self.credential.password.is_empty() {
+                cmd.env("PGPASSWORD", &self.credential.password);
+            }
+        }
+        cmd.arg(format!(
+            "--host={}",
+            self.host.as_deref().unwrap_or("localhost")
+        ))
+        .arg(format!("--dbname={}", dbname))
+        .arg("--quiet")
+        .arg("--set=ON_ERROR_STOP=1");
+
+        cmd
+    }
+
+    fn build_mongo_command(&self, program: &str) -> Command {
+        let mut cmd = Command::new(program);
+        if let Some(host) = &self.host {
+            cmd.arg(format!("--host={}", host));
+        }
+
+        cmd
+    }
+
+    /// Quotes the name as an identifier of the database's query language (a string for MongoDB).
+    fn quote_identifier(&self, name: &str) -> String {
+        match self.kind {
+            Kind::MongoDB => Self::quote_literal(name),
+            Kind::MySql => format!("`{}`", name.replace('`', "``")),
+            Kind::PostgreSql | Kind::Sqlite => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    fn quote_literal(value: &str) -> String {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+
+    /// Quotes an argument of a dot-command of the sqlite3 shell.
+    fn quote_sqlite_argument(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    /// Gets the journal files of a SQLite database, which belong to the database file only.
+    fn get_sqlite_journal_files(path: &str) -> [String; 3] {
+        ["-wal", "-shm", "-journal"].map(|suffix| format!("{}{}", path, suffix))
+    }
+
+    /// Replaces the SQLite database file at `path` by the copy atomically: the copy is written
+    /// next to it, checked and renamed over it, keeping the mode and owner of the replaced file;
+    /// the journal files of the replaced database are removed, so they aren't applied to the copy.
+    fn replace_sqlite_database(path: &str, mut file: File) -> Result<(), String> {
+        let staging_path = format!("{}.rusty-backup-restore", path);
+        let result = File::create(&staging_path)
+            .and_then(|mut staging_file| {
+                io::copy(&mut file, &mut staging_file)?;
+                if let Ok(metadata) = fs::metadata(path) {
+                    staging_file.set_permissions(metadata.permissions())?;
+                    if let Err(err) =
+                        lchown(&staging_path, Some(metadata.uid()), Some(metadata.gid()))
+                    {
+                        warn!("couldn't keep the owner of '{}': {}", path, err);
+                    }
+                }
+                staging_file.sync_all()
+            })
+            .map_err(|err| format!("unable to write '{}': {}", staging_path, err))
+            .and_then(|_| {
+                let mut check_command = Command::new("sqlite3");
+                check_command
+                    .arg(&staging_path)
+                    .arg("PRAGMA integrity_check");
+                match Self::execute_command(check_command)?.as_str() {
+                    "ok" => Ok(()),
+                    errors => Err(format!("the restored database is corrupt: {}", errors)),
+                }
+            })
+            .and_then(|_| {
+                fs::rename(&staging_path, path)
+                    .map_err(|err| format!("unable to replace '{}': {}", path, err))
+            });
+        if result.is_err() {
+            fs::remove_file(&staging_path).unwrap_or_default();
+            return result;
+        }
+
+        for journal_file in Self::get_sqlite_journal_files(path) {
+            match fs::remove_file(&journal_file) {
+                Err(err) if err.kind() != ErrorKind::NotFound => {
+                    warn!("couldn't remove '{}': {}", journal_file, err);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the command and returns its output.
+    fn execute_command(mut command: Command) -> Result<String, String> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = command
+            .output()
+            .map_err(|err| format!("error while executing {}: {}", program, err))?;
+        if !output.status.success() {
+            return Err(format!(
+                "error while executing {}: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Executes the statement (JavaScript for MongoDB) on the server and returns its output.
+    fn execute_statement(&self, statement: &str) -> Result<String, String> {
+        let mut command = match self.kind {
+            Kind::MongoDB => self.build_mongo_command("mongosh"),
+            Kind::MySql => self.build_mysql_command(),
+            Kind::PostgreSql => self.build_psql_command("postgres"),
+            Kind::Sqlite => {
+                let mut cmd = Command::new("sqlite3");
+                cmd.arg(&self.name);
+                cmd
+            }
+        };
+        match self.kind {
+            Kind::MongoDB => command.arg("--quiet").arg("--eval").arg(statement),
+            Kind::MySql => command
+                .arg("--batch")
+                .arg("--skip-column-names")
+                .arg("-e")
+                .arg(statement),
+            Kind::PostgreSql => command
+                .arg("--tuples-only")
+                .arg("--no-align")
+                .arg("--command")
+                .arg(statement),
+            Kind::Sqlite => command.arg(statement),
+        };
+
+        Self::execute_command(command)
+    }
+
+    /// Pipes the dump into the stdin of the import command; with `skip_use_statements`, the
+    /// statements of a MySQL dump, which create or switch to the dumped database, are left out.
+    fn execute_import_command(
+        mut command: Command,
+        file: File,
+        skip_use_statements: bool,
+    ) -> Result<(), String> {
+        let program = command.get_program().to_string_lossy().to_string();
+        command.stdin(Stdio::piped()).stdout(Stdio::null());
+        let mut child = command
+            .spawn()
+            .map_err(|err| format!("error while executing {}: {}", program, err))?;
+
+        let mut copy_result = Ok(());
+        if let Some(mut stdin) = child.stdin.take() {
+            let mut reader = BufReader::new(file);
+            let mut line = Vec::new();
+            copy_result = loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break Ok(()),
+                    Ok(_) => {}
+                    Err(err) => break Err(format!("{:?}", err)),
+                }
+                if skip_use_statements
+                    && (line.starts_with(b"CREATE DATABASE ") || line.starts_with(b"USE `"))
+                {
+                    continue;
+                }
+                if let Err(err) = stdin.write_all(&line) {
+                    break Err(format!("{:?}", err));
+                }
+            };
+        }
+        // The import command may have quit early, so its status is checked first.
+        let status = child.wait().map_err(|err| format!("{}", err))?;
+        if !status.success() {
+            return Err(format!("error while executing {}: {}", program, status));
+        }
+
+        copy_result
+    }
+
+    fn database_exists(&self, name: &str) -> Result<bool, String> {
+        let statement = match self.kind {
+            Kind::Sqlite => return Ok(Path::new(name).exists()),
+            Kind::MongoDB => format!(
+                "db.adminCommand({{listDatabases: 1, nameOnly: true}}).databases.some(database => database.name == {})",
+                Self::quote_literal(name)
+            ),
+            Kind::MySql => format!(
+                "SELECT 'true' FROM information_schema.SCHEMATA WHERE SCHEMA_NAME = {}",
+                Self::quote_literal(name)
+            ),
+            Kind::PostgreSql => format!(
+                "SELECT 'true' FROM pg_database WHERE datname = '{}'",
+                name.replace('\'', "''")
+            ),
+        };
+
+        Ok(self.execute_statement(&statement)? == "true")
+    }
+
+    fn drop_named_database(&self, name: &str) -> Result<(), String> {
+        let statement = match self.kind {
+            Kind::Sqlite => {
+                for file_name in Self::get_sqlite_journal_files(name)
+                    .iter()
+                    .chain([name.to_string()].iter())
+                {
+                    match fs::remove_file(file_name) {
+                        Err(err) if err.kind() != ErrorKind::NotFound => {
+                            return Err(format!("unable to remove '{}': {}", file_name, err));
+                        }
+                        _ => {}
+                    }
+                }
+                return Ok(());
+            }
+            Kind::MongoDB => format!(
+                "db.getSiblingDB({}).dropDatabase()",
+                self.quote_identifier(name)
+            ),
+            _ => format!("DROP DATABASE IF EXISTS {}", self.quote_identifier(name)),
+        };
+
+        self.execute_statement(&statement).map(|_| ())
+    }
+
+    /// Creates the database `name`, which mustn't exist yet, and imports the dump of this
+    /// database into it; if the import fails, the new database is dropped again.
+    fn import_into(&self, name: &str, file: File) -> Result<(), String> {
+        if self.kind == Kind::MongoDB && self.name == "*" {
+            return Err(String::from(
+                "the dump of all mongodb-databases can't be imported under another name.",
+            ));
+        }
+        if self.database_exists(name)? {
+            return Err(format!("the database '{}' already exists.", name));
+        }
+
+        let result = match self.kind {
+            Kind::MongoDB => {
+                let mut import_command = self.build_mongo_command("mongorestore");
+                import_command
+                    .arg("--archive")
+                    .arg(format!("--nsInclude={}.*", self.name))
+                    .arg(format!("--nsFrom={}.*", self.name))
+                    .arg(format!("--nsTo={}.*", name));
+                Self::execute_import_command(import_command, file, false)
+            }
+            Kind::MySql => {
+                self.execute_statement(&format!(
+                    "CREATE DATABASE {}",
+                    self.quote_identifier(name)
+                ))?;
+                let mut import_command = self.build_mysql_command();
+                import_command.arg(name);
+                Self::execute_import_command(import_command, file, true)
+            }
+            Kind::PostgreSql => {
+                self.execute_statement(&format!(
+                    "CREATE DATABASE {}",
+                    self.quote_identifier(name)
+                ))?;
+                Self::execute_import_command(self.build_psql_command(name), file, false)
+            }
+            Kind::Sqlite => Self::replace_sqlite_database(name, file),
+        };
+        if result.is_err() {
+            if let Err(why) = self.drop_named_database(name) {
+                warn!("couldn't drop the database '{}': {}", name, why);
+            }
+        }
+
+        result
+    }
+
+    /// Builds the MySQL statement, which moves the `tables` of this database into the database
+    /// `replaced_name` and the `staging_tables` of `staging_name` into this database at once.
+    fn build_rename_table_statement(
+        &self,
+        tables: &[String],
+        replaced_name: &str,
+        staging_tables: &[String],
+        staging_name: &str,
+    ) -> Option<String> {
+        let rename = |from: &str, to: &str, table: &String| {
+            let table = self.quote_identifier(table);
+            format!(
+                "{}.{} TO {}.{}",
+                self.quote_identifier(from),
+                table,
+                self.quote_identifier(to),
+                table
+            )
+        };
+        let renames: Vec<String> = tables
+            .iter()
+            .map(|table| rename(&self.name, replaced_name, table))
+            .chain(
+                staging_tables
+                    .iter()
+                    .map(|table| rename(staging_name, &self.name, table)),
+            )
+            .collect();
+        if renames.is_empty() {
+            return None;
+        }
+
+        Some(format!("RENAME TABLE {}", renames.join(", ")))
+    }
+
+    /// Builds the MySQL query, which lists the views, routines and triggers of the database.
+    fn build_list_other_objects_statement(database: &str) -> String {
+        let database = Self::quote_literal(database);
+        format!(
+            "SELECT CONCAT('view ', TABLE_NAME) FROM information_schema.VIEWS WHERE TABLE_SCHEMA = {database} \
+             UNION ALL SELECT CONCAT(LOWER(ROUTINE_TYPE), ' ', ROUTINE_NAME) FROM information_schema.ROUTINES WHERE ROUTINE_SCHEMA = {database} \
+             UNION ALL SELECT CONCAT('trigger ', TRIGGER_NAME) FROM information_schema.TRIGGERS WHERE TRIGGER_SCHEMA = {database}"
+        )
+    }
+
+    /// Replaces this database by the database `staging_name`, a dump was imported into. The
+    /// replaced database is dropped only, after the swap succeeded; otherwise it's kept under
+    /// the name "<name>_replaced_<timestamp>" or its original name.
+    fn swap_database(&self, staging_name: &str) -> Result<(), String> {
+        let replaced_name = format!(
+            "{}_replaced_{}",
+            self.name,
+            Utc::now().format("%Y%m%d%H%M%S")
+        );
+        let name = self.quote_identifier(&self.name);
+        let staging = self.quote_identifier(staging_name);
+        let replaced = self.quote_identifier(&replaced_name);
+
+        match self.kind {
+            Kind::MongoDB => {
+                // There's no renaming of databases, so the collections are moved one by one.
+                self.execute_statement(&format!(
+                    "const move = (from, to) => db.getSiblingDB(from).getCollectionNames()
+                        .filter(collection => !collection.startsWith('system.'))
+                        .forEach(collection => {{
+                            const result = db.adminCommand({{
+                                renameCollection: from + '.' + collection,
+                                to: to + '.' + collection,
+                            }});
+                            if (!result.ok) throw new Error(result.errmsg);
+                        }});
+                    move({name}, {replaced});
+                    move({staging}, {name});
+                    db.getSiblingDB({replaced}).dropDatabase();
+                    db.getSiblingDB({staging}).dropDatabase();"
+                ))?;
+            }
+            Kind::MySql => {
+                // There's no renaming of databases either, but all tables are moved atomically
+                // by a single RENAME TABLE; views, routines and triggers aren't moved.
+                let list_tables = |database: &str| -> Result<Vec<String>, String> {
+                    let tables = self.execute_statement(&format!(
+                        "SELECT TABLE_NAME FROM information_schema.TABLES WHERE TABLE_SCHEMA = {} AND TABLE_TYPE = 'BASE TABLE'",
+                        Self::quote_literal(database)
+                    ))?;
+                    Ok(tables.lines().map(String::from).collect())
+                };
+                let rename_statement = self.build_rename_table_statement(
+                    &list_tables(&self.name)?,
+                    &replaced_name,
+                    &list_tables(staging_name)?,
+                    staging_name,
+                );
+                let other_objects = self
+                    .execute_statement(&Self::build_list_other_objects_statement(staging_name))?;
+                if !other_objects.is_empty() {
+                    warn!(
+                        "the restored views, routines and triggers of '{}' aren't swapped and are dropped: {}",
+                        self.name,
+                        other_objects.lines().collect::<Vec<&str>>().join(", ")
+                    );
+                }
+
+                self.execute_statement(&format!("CREATE DATABASE IF NOT EXISTS {}", name))?;
+                self.execute_statement(&format!("CREATE DATABASE {}", replaced))?;
+                if let Some(rename_statement) = rename_statement {
+                    self.execute_statement(&rename_statement)?;
+                }
+                self.drop_named_database(&replaced_name)?;
+                self.drop_named_database(staging_name)?;
+            }
+            Kind::PostgreSql => {
+                let exists = self.database_exists(&self.name)?;
+                if exists {
+                    self.execute_statement(&format!(
+                        "ALTER DATABASE {} RENAME TO {}",
+                        name, replaced
+                    ))?;
+                }
+                if let Err(why) = self
+                    .execute_statement(&format!("ALTER DATABASE {} RENAME TO {}", staging, name))
+                {
+                    if exists {
+                        self.execute_statement(&format!(
+                            "ALTER DATABASE {} RENAME TO {}",
+                            replaced, name
+                        ))?;
+                    }
+                    return Err(why);
+                }
+                if exists {
+                    self.drop_named_database(&replaced_name)?;
+                }
+            }
+            Kind::Sqlite => {
+                // The renaming replaces the database file atomically.
+                fs::rename(staging_name, &self.name)
+                    .map_err(|err| format!("unable to replace '{}': {}", self.name, err))?;
+                for journal_file in Self::get_sqlite_journal_files(&self.name) {
+                    fs::remove_file(journal_file).unwrap_or_default();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restores the dump without dropping this database beforehand: it's imported into the
+    /// new database `name` (default: "<name>_restore_<timestamp>"), and with `swap`, this
+    /// database is replaced by it after a successful import. Returns the name, it's imported to.
+    pub fn restore_into(
+        &self,
+        file: File,
+        name: Option<&str>,
+        swap: bool,
+    ) -> Result<String, String> {
+        let restore_name = match name {
+            Some(name) => name.to_string(),
+            None => format!(
+                "{}_restore_{}",
+                self.name,
+                Utc::now().format("%Y%m%d%H%M%S")
+            ),
+        };
+        if swap && restore_name == self.name {
+            return Err(format!(
+                "the database '{}' can't be swapped with itself.",
+                self.name
+            ));
+        }
+
+        self.import_into(&restore_name, file)?;
+        info!(
+            "imported the dump of '{}' into '{}'",
+            self.name, restore_name
+        );
+        if swap {
+            self.swap_database(&restore_name).map_err(|why| {
+                format!("{} (the restored database '{}' is kept)", why, restore_name)
+            })?;
+            info!("swapped '{}' into '{}'", restore_name, self.name);
+            return Ok(self.name.clone());
+        }
+
+        Ok(restore_name)
+    }
+
+    /// Imports the given dump into a scratch database (see `build_scratch_name`), which is dropped
+    /// afterwards, so a dump can be checked without touching the dumped database.
+    pub fn test_import(&self, file: File) -> Result<(), String> {
+        if self.kind == Kind::MongoDB && self.name == "*" {
+            warn!("the dump of all mongodb-databases can't be test-imported.");
+            return Ok(());
+        }
+
+        let scratch_name = self.build_scratch_name();
+        self.drop_named_database(&scratch_name)?;
+        self.import_into(&scratch_name, file)?;
+        self.drop_named_database(&scratch_name)
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Kind {
     MongoDB,
     MySql,
     PostgreSql,
+    Sqlite,
 }
 
 impl Kind {
@@ -244,6 +820,175 @@ impl Kind {
             Kind::MongoDB => String::from(".bson"),
             Kind::MySql => String::from(".sql"),
             Kind::PostgreSql => String::from(".sql"),
+            Kind::Sqlite => String::from(".sqlite"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn sqlite_database(path: &str) -> Database {
+        Database {
+            kind: Kind::Sqlite,
+            name: String::from(path),
+            ..Database::new()
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!(
+                "rusty-backup-database-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn execute_sqlite(path: &str, statement: &str) -> String {
+        let mut command = Command::new("sqlite3");
+        command.arg(path).arg(statement);
+        Database::execute_command(command).unwrap()
+    }
+
+    #[test]
+    fn names_the_dumps_of_sqlite_files_by_path() {
+        let a = sqlite_database("/srv/a/app.db");
+        let b = sqlite_database("/srv/b/app.db");
+        assert_eq!(a.build_dump_filename(), "app.db_129f3e27.sqlite");
+        assert_ne!(a.build_dump_filename(), b.build_dump_filename());
+        assert_ne!(a.build_scratch_name(), b.build_scratch_name());
+        assert!(Path::new(&a.build_scratch_name()).starts_with(env::temp_dir()));
+    }
+
+    #[test]
+    fn quotes_sqlite_arguments() {
+        assert_eq!(Database::quote_sqlite_argument("app.db"), "\"app.db\"");
+        assert_eq!(
+            Database::quote_sqlite_argument("my \"app\" \\ db"),
+            "\"my \\\"app\\\" \\\\ db\""
+        );
+    }
+
+    #[test]
+    fn gets_the_sqlite_journal_files() {
+        assert_eq!(
+            Database::get_sqlite_journal_files("/srv/app.db"),
+            ["/srv/app.db-wal", "/srv/app.db-shm", "/srv/app.db-journal"]
+        );
+    }
+
+    #[test]
+    fn replaces_a_sqlite_database() {
+        let path = temp_path("replaced.db");
+        let copy_path = temp_path("copy.db");
+        execute_sqlite(
+            &path,
+            "CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('old');",
+        );
+        execute_sqlite(
+            &copy_path,
+            "CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('new');",
+        );
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let journal_file = format!("{}-journal", path);
+        fs::write(&journal_file, "stale").unwrap();
+
+        let result = Database::replace_sqlite_database(&path, File::open(&copy_path).unwrap());
+        let content = execute_sqlite(&path, "SELECT v FROM t");
+        let mode = fs::metadata(&path).unwrap().mode() & 0o777;
+        let journal_exists = Path::new(&journal_file).exists();
+        let staging_exists = Path::new(&format!("{}.rusty-backup-restore", path)).exists();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&copy_path).unwrap();
+
+        result.unwrap();
+        assert_eq!(content, "new");
+        assert_eq!(mode, 0o600);
+        assert!(!journal_exists);
+        assert!(!staging_exists);
+    }
+
+    #[test]
+    fn keeps_a_sqlite_database_on_a_corrupt_copy() {
+        let path = temp_path("kept.db");
+        let copy_path = temp_path("corrupt.db");
+        execute_sqlite(
+            &path,
+            "CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('old');",
+        );
+        fs::write(&copy_path, "not a database").unwrap();
+
+        let result = Database::replace_sqlite_database(&path, File::open(&copy_path).unwrap());
+        let content = execute_sqlite(&path, "SELECT v FROM t");
+        let staging_exists = Path::new(&format!("{}.rusty-backup-restore", path)).exists();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&copy_path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(content, "old");
+        assert!(!staging_exists);
+    }
+
+    fn database(kind: Kind, name: &str) -> Database {
+        Database {
+            kind,
+            name: String::from(name),
+            ..Database::new()
+        }
+    }
+
+    #[test]
+    fn quotes_identifiers_of_each_kind() {
+        assert_eq!(
+            database(Kind::MySql, "shop").quote_identifier("my`table"),
+            "`my``table`"
+        );
+        assert_eq!(
+            database(Kind::PostgreSql, "shop").quote_identifier("my\"table"),
+            "\"my\"\"table\""
+        );
+        assert_eq!(
+            database(Kind::MongoDB, "shop").quote_identifier("it's"),
+            "'it\\'s'"
+        );
+    }
+
+    #[test]
+    fn quotes_literals() {
+        assert_eq!(Database::quote_literal("shop"), "'shop'");
+        assert_eq!(Database::quote_literal("it's \\"), "'it\\'s \\\\'");
+    }
+
+    #[test]
+    fn builds_the_rename_table_statement_of_a_swap() {
+        let database = database(Kind::MySql, "shop");
+        let statement = database.build_rename_table_statement(
+            &[String::from("orders")],
+            "shop_replaced",
+            &[String::from("orders"), String::from("odd`name")],
+            "shop_restore",
+        );
+        assert_eq!(
+            statement.unwrap(),
+            "RENAME TABLE `shop`.`orders` TO `shop_replaced`.`orders`, \
+             `shop_restore`.`orders` TO `shop`.`orders`, \
+             `shop_restore`.`odd``name` TO `shop`.`odd``name`"
+        );
+        assert!(database
+            .build_rename_table_statement(&[], "shop_replaced", &[], "shop_restore")
+            .is_none());
+    }
+
+    #[test]
+    fn lists_the_other_objects_of_the_quoted_database() {
+        let statement = Database::build_list_other_objects_statement("shop's");
+        for schema in ["TABLE_SCHEMA", "ROUTINE_SCHEMA", "TRIGGER_SCHEMA"] {
+            assert!(statement.contains(&format!("{} = 'shop\\'s'", schema)));
         }
     }
 }
@@ -1,407 +1,408 @@
-use std::{
-    fs::File,
-    io::{Read, Write},
-    net::TcpStream,
-    ops::Sub,
-    path::Path,
-    sync::{Arc, Mutex},
-    thread,
-    time::Duration,
-};
+use std::collections::HashMap;
+use std::time::Duration;
 
-use chrono::{DateTime, NaiveDateTime};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
 use log::{info, warn};
-use rusoto_core::{Region, RusotoError};
-use rusoto_s3::{GetObjectRequest, ListObjectsV2Error, ListObjectsV2Request, S3Client, S3};
-use ssh2::Session;
-use tokio::io::AsyncReadExt;
-
-use crate::configuration::Archive;
-use crate::formatter::Formatter;
-use crate::helper::ProgressStats;
+use rusoto_core::Region;
+
+use crate::backup::manifest::Manifest;
+use crate::configuration::{rate_limit::RateLimit, Archive};
+use crate::helper::checksum::Checksum;
+use crate::restore::selection::ArchiveSelection;
+use crate::storage::{
+    azure::AzureStorage, directory::DirectoryStorage, ftp::FtpStorage, gcs::GcsStorage,
+    s3::S3Storage, ssh::SshStorage, throttle::ThrottledStorage, webdav::WebDavStorage, ArchiveFile,
+    Storage,
+};
 
 #[derive(Clone, Debug)]
 pub struct Destination {
+    pub account: String,
+    pub account_key: String,
+    pub bucket: String,
+    pub container: String,
+    pub credentials_file: String,
+    pub endpoint: String,
+    pub ftp_passive: bool,
+    pub ftp_tls: FtpTls,
     pub kind: Kind,
     pub id: String,
     pub max_archive_age: Option<Duration>,
+    pub max_download_rate: RateLimit,
+    pub max_upload_rate: RateLimit,
     pub password: String,
     pub path: String,
-    pub s3_bucket: String,
+    pub port: Option<u16>,
+    pub s3_access_key: Option<String>,
+    pub s3_ca_file: Option<String>,
+    pub s3_external_id: Option<String>,
+    pub s3_insecure_skip_verify: bool,
+    pub s3_metadata: HashMap<String, String>,
+    pub s3_object_lock_mode: Option<String>,
+    pub s3_object_lock_retention: Option<LockRetention>,
+    pub s3_path_style: bool,
+    pub s3_profile: Option<String>,
     pub s3_region: Region,
+    pub s3_restore_days: i64,
+    /// How long a download waits for the restore of an archived object.
+    pub s3_restore_timeout: Duration,
+    pub s3_restore_tier: String,
+    pub s3_role_arn: Option<String>,
+    pub s3_secret_key: Option<String>,
+    pub s3_sse_customer_key: Option<String>,
+    pub s3_sse_kms_key_id: Option<String>,
+    pub s3_storage_class: Option<String>,
+    pub s3_tags: Vec<(String, String)>,
+    pub s3_web_identity_token_file: Option<String>,
+    pub sas_token: String,
     pub server: String,
+    pub url: String,
     pub username: String,
 }
 
 impl Destination {
     pub fn new() -> Destination {
         Destination {
+            account: String::new(),
+            account_key: String::new(),
+            bucket: String::new(),
+            container: String::new(),
+            credentials_file: String::new(),
+            endpoint: String::new(),
+            ftp_passive: true,
+            ftp_tls: FtpTls::None,
             kind: Kind::None,
             id: String::new(),
             max_archive_age: None,
+            max_download_rate: RateLimit::new(),
+            max_upload_rate: RateLimit::new(),
             password: String::new(),
             path: String::new(),
-            s3_bucket: String::new(),
+            port: None,
+            s3_access_key: None,
+            s3_ca_file: None,
+            s3_external_id: None,
+            s3_insecure_skip_verify: false,
+            s3_metadata: HashMap::new(),
+            s3_object_lock_mode: None,
+            s3_object_lock_retention: None,
+            s3_path_style: true,
+            s3_profile: None,
             s3_region: Region::EuCentral1,
+            s3_restore_days: 1,
+            s3_restore_timeout: Duration::from_secs(48 * 60 * 60),
+            s3_restore_tier: String::from("Standard"),
+            s3_role_arn: None,
+            s3_secret_key: None,
+            s3_sse_customer_key: None,
+            s3_sse_kms_key_id: None,
+            s3_storage_class: None,
+            s3_tags: Vec::new(),
+            s3_web_identity_token_file: None,
+            sas_token: String::new(),
             server: String::new(),
+            url: String::new(),
             username: String::new(),
         }
     }
 
-    pub async fn download_to_tmp(&self, archive: &Archive) -> Result<Option<String>, String> {
-        match self.kind {
-            Kind::Directory => Ok(None),
-            Kind::None => Ok(None),
-            Kind::S3 => self.download_from_s3_to_tmp(archive).await,
-            Kind::SSH => self.download_from_ssh_to_tmp(archive).await,
+    /// Checks, if the S3 object options of this destination can be combined.
+    pub fn validate_s3_options(&self) -> Result<(), String> {
+        if !self.s3_path_style {
+            return Err(format!(
+                "virtual-hosted-style requests are not supported, the destination-path-style must be true for destination '{}'",
+                self.id
+            ));
         }
-    }
-
-    async fn download_from_s3_to_tmp(&self, archive: &Archive) -> Result<Option<String>, String> {
-        let client = S3Client::new(self.s3_region.clone());
-
-        let prefix_opt = match archive.name.find("{") {
-            Some(index) => {
-                if index > 0 {
-                    let archive_name = archive.name.as_str();
-                    Some(String::from(&archive_name[..index]))
-                } else {
-                    None
+        if self.s3_access_key.is_some() != self.s3_secret_key.is_some() {
+            return Err(format!(
+                "the destination-access-key and -secret-key must be set together for destination '{}'",
+                self.id
+            ));
+        }
+        if self.s3_access_key.is_some() && self.s3_profile.is_some() {
+            return Err(format!(
+                "the destination-access-key and -profile can't be combined for destination '{}'",
+                self.id
+            ));
+        }
+        if self.s3_role_arn.is_none()
+            && (self.s3_external_id.is_some() || self.s3_web_identity_token_file.is_some())
+        {
+            return Err(format!(
+                "the destination-external-id and -web-identity-token-file require a role-arn for destination '{}'",
+                self.id
+            ));
+        }
+        if self.s3_web_identity_token_file.is_some()
+            && (self.s3_external_id.is_some()
+                || self.s3_access_key.is_some()
+                || self.s3_profile.is_some())
+        {
+            return Err(format!(
+                "the destination-web-identity-token-file can't be combined with an external-id, access-key or profile for destination '{}'",
+                self.id
+            ));
+        }
+        if self.s3_object_lock_mode.is_some() != self.s3_object_lock_retention.is_some() {
+            return Err(format!(
+                "the destination-object-lock-mode and -object-lock-retention must be set together for destination '{}'",
+                self.id
+            ));
+        }
+        if let Some(sse_customer_key) = &self.s3_sse_customer_key {
+            if self.s3_sse_kms_key_id.is_some() {
+                return Err(format!(
+                    "the destination-sse-customer-key and -sse-kms-key-id can't be combined for destination '{}'",
+                    self.id
+                ));
+            }
+            match BASE64.decode(sse_customer_key) {
+                Ok(key) if key.len() == 32 => {}
+                _ => {
+                    return Err(format!(
+                        "the destination-sse-customer-key must be a base64 encoded 256-bit key for destination '{}'",
+                        self.id
+                    ));
                 }
             }
-            None => None,
-        };
-        let list_objects_request = ListObjectsV2Request {
-            bucket: archive.destination.s3_bucket.clone(),
-            prefix: prefix_opt,
-            ..Default::default()
-        };
+        }
 
-        let objects = client
-            .list_objects_v2(list_objects_request)
-            .await
-            .map_err(Self::map_rusoto_list_objects_error)?;
+        Ok(())
+    }
 
-        let contents = match objects.contents {
-            Some(contents) => contents,
-            None => {
-                warn!("no S3 objects found.");
-                return Ok(None);
-            }
+    /// Creates the storage of this destination, depending on its kind.
+    pub async fn storage(&self) -> Result<Box<dyn Storage>, String> {
+        let storage: Box<dyn Storage> = match self.kind {
+            Kind::Azure => Box::new(AzureStorage::new(self)?),
+            Kind::Directory => Box::new(DirectoryStorage::new(self)),
+            Kind::Ftp => Box::new(FtpStorage::connect(self)?),
+            Kind::Gcs => Box::new(GcsStorage::new(self).await?),
+            Kind::None => return Err(format!("the destination '{}' has no storage.", self.id)),
+            Kind::S3 => Box::new(S3Storage::new(self)?),
+            Kind::SSH => Box::new(SshStorage::connect(self)?),
+            Kind::WebDav => Box::new(WebDavStorage::new(self)?),
         };
 
-        let mut last_known_key_opt: Option<String> = None;
-        let mut last_known_datetime_opt: Option<NaiveDateTime> = None;
-
-        for content in contents {
-            let key = match content.key {
-                Some(key) => key,
-                None => continue,
-            };
-
-            let current_datetime_opt = match content.last_modified {
-                Some(modified) => {
-                    match NaiveDateTime::parse_from_str(modified.as_str(), "%Y-%m-%dT%H:%M:%S%.fZ")
-                    {
-                        Ok(date) => Some(date),
-                        Err(_) => None,
-                    }
-                }
-                None => None,
-            };
-
-            match last_known_datetime_opt {
-                Some(last_known_datetime) => match current_datetime_opt {
-                    Some(current_datetime) => {
-                        let datetime_diff = last_known_datetime.sub(current_datetime);
-                        if datetime_diff.num_seconds() < 0 {
-                            last_known_datetime_opt = Some(current_datetime);
-                            last_known_key_opt = Some(key);
-                        }
-                    }
-                    None => {}
-                },
-                None => match current_datetime_opt {
-                    Some(current_datetime) => {
-                        last_known_datetime_opt = Some(current_datetime);
-                        last_known_key_opt = Some(key);
-                    }
-                    None => {}
-                },
-            }
+        match self.max_upload_rate.is_limited() || self.max_download_rate.is_limited() {
+            true => Ok(Box::new(ThrottledStorage::new(storage, self))),
+            false => Ok(storage),
         }
+    }
 
-        let key = match last_known_key_opt {
-            Some(key) => key,
-            None => {
-                warn!("no S3-key found.");
-                return Ok(None);
-            }
-        };
-
-        info!("found latest key: {:?}", key);
-        let object_request = GetObjectRequest {
-            bucket: archive.destination.s3_bucket.clone(),
-            key: key.clone(),
-            ..Default::default()
-        };
-        let object = client
-            .get_object(object_request)
-            .await
-            .map_err(Self::map_rusoto_get_object_error)?;
-        print!("downloading...");
-        let mut download_stats = ProgressStats::new();
-        if let Some(content_length) = object.content_length {
-            download_stats.total_length = Some(content_length as usize);
+    /// Downloads the selected (default: the newest) file of the given archive into the current
+    /// working directory and returns its name without the file extension.
+    pub async fn download_to_tmp(
+        &self,
+        archive: &Archive,
+        archive_selection: &ArchiveSelection,
+    ) -> Result<Option<String>, String> {
+        if self.kind == Kind::None {
+            return Ok(None);
         }
-        let arc_download_stats = Arc::new(Mutex::new(download_stats));
-        let cloned_arc_download_stats = Arc::clone(&arc_download_stats);
 
-        let streaming_body = match object.body {
-            Some(streaming_body) => streaming_body,
+        let storage = self.storage().await?;
+        let archive_files = Self::list_storage_archive_files(storage.as_ref(), archive).await?;
+        let selected_archive_file = match archive_selection.select(archive, archive_files) {
+            Some(archive_file) => archive_file,
             None => {
-                warn!("no body in S3-object.");
+                warn!("no archive file found on destination '{}'.", self.id);
                 return Ok(None);
             }
         };
 
-        let mut body = streaming_body.into_async_read();
-        let archive_filename = format!("{}", key);
-        let mut f = File::create(&archive_filename).map_err(Self::map_error)?;
-
-        let thread = thread::spawn(move || {
-            let cloned_arc_download_stats = Arc::clone(&arc_download_stats);
-            loop {
-                let mut output_string = String::from("downloading... ");
-                match cloned_arc_download_stats.lock() {
-                    Ok(download_stats) => {
-                        output_string.push_str(
-                            Formatter::format_size(download_stats.progressed_size, 2).as_str(),
-                        );
-                        if let Some(content_length) = download_stats.total_length {
-                            output_string.push_str(
-                                format!(
-                                    "/{} ({number:.2}%)",
-                                    Formatter::format_size(content_length, 2),
-                                    number = download_stats.get_progress_in_percentage().unwrap()
-                                )
-                                .as_str(),
-                            );
-                        }
-                        output_string.push_str(
-                            format!("; runtime: {}", download_stats.get_formatted_runtime())
-                                .as_str(),
-                        );
-                        if let Some(formatted_ete) = download_stats.get_formatted_ete() {
-                            output_string.push_str(format!("; ete: {}", formatted_ete).as_str());
-                        }
-                        output_string.push_str(
-                            format!(
-                                "; speed: {}/s",
-                                Formatter::format_size(download_stats.get_average_speed(), 2)
-                            )
-                            .as_str(),
-                        );
-                        output_string.push_str(
-                            format!(
-                                "; speed (<=1s): {}/s",
-                                Formatter::format_size(
-                                    download_stats.get_average_speed_for_last_second(),
-                                    2
-                                )
-                            )
-                            .as_str(),
-                        );
-                        output_string.push_str(
-                            format!(
-                                "; speed (<=10s): {}/s",
-                                Formatter::format_size(
-                                    download_stats.get_average_speed_for_last_10_seconds(),
-                                    2
-                                )
-                            )
-                            .as_str(),
-                        );
-
-                        print!("{}\r{}", termion::clear::CurrentLine, output_string);
-                        std::io::stdout().flush().unwrap_or_default();
-
-                        if download_stats.is_finished() {
-                            break;
-                        }
-                    }
-                    Err(_) => continue,
-                }
+        info!("found archive file: {:?}", selected_archive_file.name);
+        storage.get_file(&selected_archive_file.name).await?;
 
-                thread::sleep(Duration::from_millis(250));
-            }
-        });
-
-        let mut done = false;
-        while !done {
-            let mut buffer = vec![];
-            let read_bytes = body.read_buf(&mut buffer).await.map_err(Self::map_error)?;
-            if read_bytes > 0 {
-                f.write_all(&buffer[..read_bytes])
-                    .map_err(Self::map_error)?;
-                match cloned_arc_download_stats.lock() {
-                    Ok(mut download_stats) => {
-                        download_stats.add_progressed_size(read_bytes);
-                    }
-                    Err(_) => {}
-                }
-            } else {
-                done = true;
-                match cloned_arc_download_stats.lock() {
-                    Ok(mut download_stats) => {
-                        download_stats.set_finished();
-                    }
-                    Err(_) => {}
-                }
-            }
+        Ok(Some(
+            archive.strip_file_extension(&selected_archive_file.name),
+        ))
+    }
+
+    /// Lists all files of the given archive on this destination.
+    pub async fn list_archive_files(&self, archive: &Archive) -> Result<Vec<ArchiveFile>, String> {
+        if self.kind == Kind::None {
+            return Ok(Vec::new());
         }
 
-        thread.join().unwrap_or_default();
-        println!();
+        let storage = self.storage().await?;
+        Self::list_storage_archive_files(storage.as_ref(), archive).await
+    }
 
-        let mut archive_name = archive_filename.clone();
-        if let Some(encryption) = &archive.encryption {
-            let enc_ext = encryption.to_extension_string();
-            if archive_name.ends_with(&enc_ext) {
-                archive_name = archive_name[..archive_name.len() - enc_ext.len()].to_string();
-            }
+    /// Moves the given files from the working directory to this destination.
+    pub async fn upload_files(&self, file_names: Vec<String>) -> Result<(), String> {
+        if self.kind == Kind::None {
+            return Ok(());
         }
-        let comp_ext = archive.compression.to_extension_string();
-        if archive_name.ends_with(&comp_ext) {
-            archive_name = archive_name[..archive_name.len() - comp_ext.len()].to_string();
+
+        let storage = self.storage().await?;
+        for file_name in file_names {
+            storage.put_file(&file_name).await?;
         }
 
-        Ok(Some(archive_name))
+        Ok(())
     }
 
-    async fn download_from_ssh_to_tmp(&self, archive: &Archive) -> Result<Option<String>, String> {
-        let prefix_opt = match archive.name.find("{") {
-            Some(index) => {
-                if index > 0 {
-                    let archive_name = archive.name.as_str();
-                    Some(String::from(&archive_name[..index]))
-                } else {
-                    None
-                }
-            }
-            None => None,
+    /// Deletes all files of the given archive (with their companion files), which are older than
+    /// `max_archive_age`.
+    pub async fn delete_outdated_archive_files(&self, archive: &Archive) -> Result<(), String> {
+        let max_archive_age = match self.max_archive_age {
+            Some(max_archive_age) => max_archive_age,
+            None => return Ok(()),
         };
-
-        let addr = format!("{}:22", archive.destination.server);
-        let tcp = TcpStream::connect(addr).unwrap();
-        let mut ssh2_session = Session::new().unwrap();
-        ssh2_session.set_tcp_stream(tcp);
-        ssh2_session.handshake().unwrap();
-        ssh2_session.userauth_password(&archive.destination.username, &archive.destination.password).unwrap();
-
-        let sftp = ssh2_session.sftp().unwrap();
-        let paths = sftp.readdir(Path::new("")).unwrap();
-
-        let mut last_known_key_opt: Option<String> = None;
-        let mut last_known_datetime_opt: Option<NaiveDateTime> = None;
-
-        for path in paths {
-            let key = format!("{}", path.0.display());
-            if let Some(prefix) = &prefix_opt {
-                if !key.starts_with(prefix) {
-                    continue;
-                }
-            }
-
-            let current_datetime_opt = match path.1.mtime {
-                Some(modified) => {
-                    match DateTime::from_timestamp(modified as i64, 0) {
-                        Some(date) => Some(date.naive_utc()),
-                        None => None,
+        let max_archive_age = chrono::Duration::from_std(max_archive_age)
+            .map_err(|err| format!("invalid max-archive-age: {:?}", err))?;
+        let oldest_allowed_datetime = Utc::now().naive_utc() - max_archive_age;
+
+        let storage = self.storage().await?;
+        for archive_file in Self::list_storage_archive_files(storage.as_ref(), archive).await? {
+            match archive_file.modified {
+                Some(modified) if modified < oldest_allowed_datetime => {
+                    info!("deleting outdated archive file: {}", archive_file.name);
+                    storage.delete(&archive_file.name).await?;
+                    for companion_file in Self::get_companion_files(archive, &archive_file.name) {
+                        storage.delete(&companion_file).await?;
                     }
                 }
-                None => None,
-            };
-
-            match last_known_datetime_opt {
-                Some(last_known_datetime) => match current_datetime_opt {
-                    Some(current_datetime) => {
-                        let datetime_diff = last_known_datetime.sub(current_datetime);
-                        if datetime_diff.num_seconds() < 0 {
-                            last_known_datetime_opt = Some(current_datetime);
-                            last_known_key_opt = Some(key);
-                        }
-                    }
-                    None => {}
-                },
-                None => match current_datetime_opt {
-                    Some(current_datetime) => {
-                        last_known_datetime_opt = Some(current_datetime);
-                        last_known_key_opt = Some(key);
-                    }
-                    None => {}
-                },
+                _ => {}
             }
         }
 
-        let key = match last_known_key_opt {
-            Some(key) => key,
-            None => {
-                warn!("no SSH file found.");
-                return Ok(None);
-            }
-        };
-
-        info!("found latest key: {:?}", key);
+        Ok(())
+    }
 
-        print!("downloading... ");
+    /// Gets the names of the files, which are stored next to the given archive file: its
+    /// checksum sidecar file and its manifest.
+    pub fn get_companion_files(archive: &Archive, archive_file_name: &str) -> Vec<String> {
+        vec![
+            Checksum::get_sidecar_name(archive_file_name),
+            Manifest::get_file_name(archive, archive_file_name),
+        ]
+    }
 
-        let mut sftp_file = sftp.open(Path::new(&key)).unwrap();
-        let archive_filename = format!("{}", key);
-        let mut f = File::create(&archive_filename).map_err(Self::map_error)?;
-        let mut buf = [0; 32 * 1024];
-        let mut read_bytes = sftp_file.read(&mut buf).unwrap();
-        while read_bytes > 0 {
-            f.write_all(&buf[..read_bytes]).map_err(Self::map_error)?;
-            read_bytes = sftp_file.read(&mut buf).unwrap();
-        }
+    /// Lists all files on the given storage, which belong to the given archive.
+    pub async fn list_storage_archive_files(
+        storage: &dyn Storage,
+        archive: &Archive,
+    ) -> Result<Vec<ArchiveFile>, String> {
+        let prefix = archive.get_name_prefix();
+        let file_name_regex = archive.build_file_name_regex();
+        let mut archive_files = storage.list(prefix.as_deref()).await?;
+        archive_files.retain(|archive_file| file_name_regex.is_match(&archive_file.name));
+
+        Ok(archive_files)
+    }
+}
 
-        println!();
+/// The retention of an object lock: a duration from the upload on, or a fixed date.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LockRetention {
+    Duration(Duration),
+    Until(DateTime<Utc>),
+}
 
-        let mut archive_name = archive_filename.clone();
-        if let Some(encryption) = &archive.encryption {
-            let enc_ext = encryption.to_extension_string();
-            if archive_name.ends_with(&enc_ext) {
-                archive_name = archive_name[..archive_name.len() - enc_ext.len()].to_string();
-            }
-        }
-        let comp_ext = archive.compression.to_extension_string();
-        if archive_name.ends_with(&comp_ext) {
-            archive_name = archive_name[..archive_name.len() - comp_ext.len()].to_string();
+impl LockRetention {
+    /// Parses an RFC 3339 date, e.g. "2030-01-01T00:00:00Z", or a duration, e.g. "90 days".
+    pub fn parse(value: &str) -> Result<LockRetention, String> {
+        if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+            return Ok(LockRetention::Until(date.with_timezone(&Utc)));
         }
-
-        return Ok(Some(archive_name));
+        parse_duration0::parse(value)
+            .map(LockRetention::Duration)
+            .map_err(|_| {
+                format!(
+                    "invalid destination object-lock-retention value '{}'.",
+                    value
+                )
+            })
     }
 
-    fn map_error(err: std::io::Error) -> String {
-        format!("error: {:?}", err)
-    }
+    /// Gets the date, until which an object uploaded at `now` is retained.
+    pub fn retain_until(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+        let retain_until_date = match self {
+            LockRetention::Duration(duration) => {
+                now + chrono::Duration::from_std(*duration)
+                    .map_err(|err| format!("invalid object-lock-retention: {:?}", err))?
+            }
+            LockRetention::Until(date) => *date,
+        };
+        if retain_until_date <= now {
+            return Err(format!(
+                "the object-lock-retention date {} has already passed.",
+                retain_until_date.to_rfc3339()
+            ));
+        }
 
-    fn map_rusoto_get_object_error(
-        err: rusoto_core::RusotoError<rusoto_s3::GetObjectError>,
-    ) -> String {
-        format!("error: {:?}", err)
+        Ok(retain_until_date)
     }
+}
 
-    fn map_rusoto_list_objects_error(err: RusotoError<ListObjectsV2Error>) -> String {
-        format!("error: {:?}", err)
-    }
+#[derive(Clone, Debug, PartialEq)]
+pub enum FtpTls {
+    None,
+    Explicit,
+    Implicit,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Kind {
+    Azure,
     Directory,
+    Ftp,
+    Gcs,
     None,
     S3,
     SSH,
+    WebDav,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_path_style_requests_only() {
+        let mut destination = Destination::new();
+        destination.id = String::from("minio");
+        assert!(destination.validate_s3_options().is_ok());
+
+        destination.s3_path_style = false;
+        let err = destination.validate_s3_options().unwrap_err();
+        assert!(err.contains("virtual-hosted-style"), "{}", err);
+    }
+
+    #[test]
+    fn parses_the_object_lock_retention_as_date_or_duration() {
+        let now = DateTime::parse_from_rfc3339("2024-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let retention = LockRetention::parse("90 days").unwrap();
+        assert_eq!(
+            retention.retain_until(now).unwrap().to_rfc3339(),
+            "2024-05-30T00:00:00+00:00"
+        );
+
+        let retention = LockRetention::parse("2030-01-01T12:00:00+01:00").unwrap();
+        assert_eq!(
+            retention.retain_until(now).unwrap().to_rfc3339(),
+            "2030-01-01T11:00:00+00:00"
+        );
+
+        let retention = LockRetention::parse("2020-01-01T00:00:00Z").unwrap();
+        assert!(retention.retain_until(now).is_err());
+
+        assert!(LockRetention::parse("next year").is_err());
+    }
+
+    #[test]
+    fn requires_the_access_key_with_the_secret_key() {
+        let mut destination = Destination::new();
+        destination.s3_access_key = Some(String::from("access"));
+        assert!(destination.validate_s3_options().is_err());
+
+        destination.s3_secret_key = Some(String::from("secret"));
+        assert!(destination.validate_s3_options().is_ok());
+    }
 }
@@ -1,10 +1,34 @@
-use nix::unistd::{Gid, Uid, User};
+use std::path::Path;
+
+use nix::unistd::{Gid, Group, Uid, User};
+
+/// How sockets, FIFOs and device files are handled on a backup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpecialFiles {
+    /// FIFOs and device files are archived, sockets are skipped, as they can't be restored.
+    Archive,
+    Skip,
+    /// The backup fails on a special file.
+    Error,
+}
+
+/// The filesystem snapshot, which is archived instead of the live directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotKind {
+    Btrfs,
+    Lvm,
+    Zfs,
+}
 
 #[derive(Clone, Debug)]
 pub struct Directory {
     pub name: String,
     pub user: Option<String>,
     pub group: Option<String>,
+    pub special_files: SpecialFiles,
+    pub snapshot: Option<SnapshotKind>,
+    /// The size of an LVM snapshot, e.g. "5G" or "20%ORIGIN" (default: "10%ORIGIN").
+    pub snapshot_size: Option<String>,
 }
 
 impl Directory {
@@ -13,12 +37,32 @@ impl Directory {
             name: String::new(),
             user: None,
             group: None,
+            special_files: SpecialFiles::Archive,
+            snapshot: None,
+            snapshot_size: None,
         }
     }
 
+    /// Gets the original path of an entry in an archive, e.g. "/var/www/index.html" for
+    /// "www/index.html" of the directory "/var/www".
+    pub fn get_original_path(&self, entry_path: &str) -> Option<String> {
+        let dir_path = Path::new(&self.name);
+        let dir_name = dir_path.file_name()?.to_string_lossy();
+        let relative_path = entry_path.strip_prefix(dir_name.as_ref())?;
+        if !relative_path.is_empty() && !relative_path.starts_with('/') {
+            return None;
+        }
+
+        Some(format!(
+            "{}/{}",
+            dir_path.parent()?.to_string_lossy().trim_end_matches('/'),
+            entry_path
+        ))
+    }
+
     pub fn get_gid(&self) -> Option<Gid> {
         match &self.group {
-            Some(group) => match User::from_name(group.as_str()) {
+            Some(group) => match Group::from_name(group.as_str()) {
                 Ok(group_opt) => match group_opt {
                     Some(group) => Some(group.gid),
                     None => None,
@@ -1,4 +1,4 @@
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
 
 use log::info;
 use regex::Regex;
@@ -58,6 +58,25 @@ impl Encryption {
         }
     }
 
+    /// Spawns the decryption of the given file, which writes the decrypted content to the piped
+    /// stdout of the returned child.
+    pub fn spawn_decryption<S: AsRef<str>>(&self, input_filename: S) -> Result<Child, String> {
+        let mut cmd = Command::new("openssl");
+        cmd.arg(&self.cipher)
+            .arg("-d")
+            .arg("-pbkdf2")
+            .arg("-in")
+            .arg(input_filename.as_ref())
+            .arg("-k")
+            .arg(&self.password)
+            .stdout(Stdio::piped());
+
+        match cmd.spawn() {
+            Ok(child) => Ok(child),
+            Err(_) => Err(String::from("error while spawning decryption-program.")),
+        }
+    }
+
     pub fn encrypt_file<S: AsRef<str>>(&self, input_filename: S) -> Result<(), String> {
         let input_filename = input_filename.as_ref();
         let output_filename = format!("{}.enc", input_filename);
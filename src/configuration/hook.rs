@@ -0,0 +1,268 @@
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+
+use crate::configuration::archive::Archive;
+
+/// The events of a backup or restore run, on which the hook commands are executed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookEvent {
+    PreBackup,
+    /// Executed after every backup, also after a failed one, e.g. to undo the pre-backup hooks.
+    PostBackup,
+    OnSuccess,
+    OnFailure,
+    PreRestore,
+    /// Executed after every restore, also after a failed one.
+    PostRestore,
+}
+
+impl HookEvent {
+    pub fn parse(value: &str) -> Result<HookEvent, String> {
+        match value {
+            "pre-backup" => Ok(HookEvent::PreBackup),
+            "post-backup" => Ok(HookEvent::PostBackup),
+            "on-success" => Ok(HookEvent::OnSuccess),
+            "on-failure" => Ok(HookEvent::OnFailure),
+            "pre-restore" => Ok(HookEvent::PreRestore),
+            "post-restore" => Ok(HookEvent::PostRestore),
+            event => Err(format!("invalid hook event value '{}'.", event)),
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            HookEvent::PreBackup => "pre-backup",
+            HookEvent::PostBackup => "post-backup",
+            HookEvent::OnSuccess => "on-success",
+            HookEvent::OnFailure => "on-failure",
+            HookEvent::PreRestore => "pre-restore",
+            HookEvent::PostRestore => "post-restore",
+        }
+    }
+}
+
+/// A shell command, which is executed on an event; a failed command fails the backup or restore,
+/// if `fail_on_error` is set (default for the pre-backup and pre-restore hooks).
+#[derive(Clone, Debug)]
+pub struct Hook {
+    pub event: HookEvent,
+    pub command: String,
+    pub timeout: Duration,
+    pub fail_on_error: bool,
+}
+
+impl Hook {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+
+    pub fn new(event: HookEvent) -> Self {
+        Self {
+            event,
+            command: String::new(),
+            timeout: Hook::DEFAULT_TIMEOUT,
+            fail_on_error: matches!(event, HookEvent::PreBackup | HookEvent::PreRestore),
+        }
+    }
+
+    /// Executes the command with `sh -c` in its own process group, which is killed, when the
+    /// timeout is exceeded.
+    fn execute(&self, environment: &[(&str, String)]) -> Result<(), String> {
+        info!("executing {} hook: {}", self.event.to_str(), self.command);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .envs(environment.iter().map(|(key, value)| (key, value)))
+            .env("RUSTY_BACKUP_EVENT", self.event.to_str())
+            .stdin(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .map_err(|err| format!("unable to execute the hook '{}': {}", self.command, err))?;
+
+        let started = Instant::now();
+        loop {
+            let status = child
+                .try_wait()
+                .map_err(|err| format!("error while waiting for the hook: {}", err))?;
+            match status {
+                Some(status) if status.success() => return Ok(()),
+                Some(status) => {
+                    return Err(format!(
+                        "the {} hook '{}' failed: {}",
+                        self.event.to_str(),
+                        self.command,
+                        status
+                    ))
+                }
+                None if started.elapsed() >= self.timeout => {
+                    if let Err(errno) = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL) {
+                        warn!("unable to kill the hook: {:?}", errno);
+                    }
+                    child.wait().unwrap_or_default();
+                    return Err(format!(
+                        "the {} hook '{}' timed out after {:?}",
+                        self.event.to_str(),
+                        self.command,
+                        self.timeout
+                    ));
+                }
+                None => thread::sleep(Duration::from_millis(100)),
+            }
+        }
+    }
+}
+
+/// The hooks of an archive or of the whole backup configuration.
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    pub hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Gets the environment variables, which describe the archive to the hook commands; the
+    /// lists are separated by newlines.
+    pub fn build_environment(archive: &Archive, archive_name: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("RUSTY_BACKUP_ARCHIVE", String::from(archive_name)),
+            ("RUSTY_BACKUP_DESTINATION", archive.destination.id.clone()),
+            (
+                "RUSTY_BACKUP_DIRECTORIES",
+                archive
+                    .directories
+                    .iter()
+                    .map(|directory| directory.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n"),
+            ),
+            (
+                "RUSTY_BACKUP_DATABASES",
+                archive
+                    .databases
+                    .iter()
+                    .map(|database| database.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n"),
+            ),
+        ]
+    }
+
+    /// Executes the hooks of the event in their configured order; a failed hook stops the
+    /// execution, if it fails on errors, otherwise only a warning is logged.
+    pub fn run(&self, event: HookEvent, environment: &[(&str, String)]) -> Result<(), String> {
+        for hook in self.hooks.iter().filter(|hook| hook.event == event) {
+            match hook.execute(environment) {
+                Ok(()) => {}
+                Err(why) if hook.fail_on_error => return Err(why),
+                Err(why) => warn!("{}", why),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the post hooks after the pre hooks and the action with the `result`, then the
+    /// on-success or on-failure hooks; the first error is returned.
+    pub fn finish(
+        &self,
+        post_event: HookEvent,
+        result: Result<(), String>,
+        mut environment: Vec<(&'static str, String)>,
+    ) -> Result<(), String> {
+        let post_result = self.run(post_event, &environment);
+        let result = result
+            .and(post_result)
+            .and_then(|_| self.run(HookEvent::OnSuccess, &environment));
+
+        if let Err(why) = &result {
+            environment.push(("RUSTY_BACKUP_ERROR", why.clone()));
+            if let Err(hook_error) = self.run(HookEvent::OnFailure, &environment) {
+                error!("{}", hook_error);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    use crate::configuration::directory::Directory;
+
+    fn hooks(event: HookEvent, command: &str, timeout: Duration, fail_on_error: bool) -> Hooks {
+        Hooks {
+            hooks: vec![Hook {
+                event,
+                command: String::from(command),
+                timeout,
+                fail_on_error,
+            }],
+        }
+    }
+
+    #[test]
+    fn kills_the_process_group_of_a_hook_after_its_timeout() {
+        let marker = env::temp_dir().join(format!("rusty-backup-killed-{}", std::process::id()));
+        let hooks = hooks(
+            HookEvent::PreBackup,
+            &format!("(sleep 1; touch {}) & sleep 5", marker.display()),
+            Duration::from_millis(100),
+            true,
+        );
+        let started = Instant::now();
+        let err = hooks.run(HookEvent::PreBackup, &[]).unwrap_err();
+        assert!(err.contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // The background process of the hook was killed along with it.
+        thread::sleep(Duration::from_millis(1_500));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn fails_or_warns_on_a_failed_hook() {
+        let failing = hooks(HookEvent::PreBackup, "exit 3", Hook::DEFAULT_TIMEOUT, true);
+        let err = failing.run(HookEvent::PreBackup, &[]).unwrap_err();
+        assert!(err.contains("exit status: 3"), "{}", err);
+
+        let warning = hooks(HookEvent::PreBackup, "exit 3", Hook::DEFAULT_TIMEOUT, false);
+        assert!(warning.run(HookEvent::PreBackup, &[]).is_ok());
+        // Only the hooks of the event are executed.
+        assert!(failing.run(HookEvent::PostBackup, &[]).is_ok());
+    }
+
+    #[test]
+    fn exports_the_archive_to_the_hook() {
+        let output = env::temp_dir().join(format!("rusty-backup-hook-{}", std::process::id()));
+        let mut archive = Archive::new();
+        archive.destination.id = String::from("local");
+        for name in ["/var/www", "/etc"] {
+            let mut directory = Directory::new();
+            directory.name = String::from(name);
+            archive.directories.push(directory);
+        }
+        let hooks = hooks(
+            HookEvent::PostBackup,
+            &format!(
+                "printf '%s|%s|%s|%s' \"$RUSTY_BACKUP_EVENT\" \"$RUSTY_BACKUP_ARCHIVE\" \
+                 \"$RUSTY_BACKUP_DESTINATION\" \"$RUSTY_BACKUP_DIRECTORIES\" > {}",
+                output.display()
+            ),
+            Hook::DEFAULT_TIMEOUT,
+            true,
+        );
+
+        let environment = Hooks::build_environment(&archive, "2024-03-01_www");
+        hooks.run(HookEvent::PostBackup, &environment).unwrap();
+        let content = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+
+        assert_eq!(content, "post-backup|2024-03-01_www|local|/var/www\n/etc");
+    }
+}
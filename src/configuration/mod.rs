@@ -6,23 +6,31 @@ use std::{
 use rusoto_core::Region;
 use xml::reader::{EventReader, XmlEvent as XmlReaderEvent};
 
+use crate::helper::checksum::Checksum;
+
 pub mod archive;
+pub mod command;
 pub mod compression;
 pub mod credential;
 pub mod database;
 pub mod destination;
 pub mod directory;
 pub mod encryption;
+pub mod hook;
 pub mod program_parameter;
+pub mod rate_limit;
 
 use archive::Archive;
+use command::CommandSource;
 use compression::Compression;
 use credential::Credential;
 use database::{Database, Kind as DatabaseKind};
-use destination::{Destination, Kind as DestinationKind};
-use directory::Directory;
+use destination::{Destination, FtpTls, Kind as DestinationKind, LockRetention};
+use directory::{Directory, SnapshotKind, SpecialFiles};
 use encryption::Encryption;
+use hook::{Hook, HookEvent, Hooks};
 use program_parameter::ProgramParameter;
+use rate_limit::{RateLimit, RateSchedule};
 
 pub struct Configuration {
     pub archives: Vec<Archive>,
@@ -30,6 +38,10 @@ pub struct Configuration {
     pub credentials: Vec<Credential>,
     pub destinations: Vec<Destination>,
     pub encryptions: Vec<Encryption>,
+    /// The hooks of the whole backup or restore run.
+    pub hooks: Hooks,
+    /// The SHA-256 checksum of the loaded settings file.
+    pub hash: String,
     pub working_directory: String,
 }
 
@@ -43,10 +55,17 @@ impl Configuration {
             credentials: Vec::new(),
             destinations: Vec::new(),
             encryptions: Vec::new(),
+            hooks: Hooks::default(),
+            hash: String::new(),
             working_directory: String::new(),
         }
     }
 
+    /// Parses a boolean attribute value ("1", "true", "yes", "on" or "enabled").
+    fn parse_bool(value: &str) -> bool {
+        matches!(value, "1" | "true" | "yes" | "on" | "enabled")
+    }
+
     pub fn load(filename: &str) -> Result<Configuration, String> {
         let mut configuration = Configuration::new();
 
@@ -61,8 +80,9 @@ impl Configuration {
             None => {}
         }
 
-        match File::open(real_filename) {
+        match File::open(&real_filename) {
             Ok(mut file) => {
+                configuration.hash = Checksum::compute(&real_filename)?;
                 let mut archive = Archive::new();
                 let mut database = Database::new();
                 let mut destination = Destination::new();
@@ -108,10 +128,17 @@ impl Configuration {
                                                                 database.kind =
                                                                     DatabaseKind::PostgreSql;
                                                             }
+                                                            "sqlite" => {
+                                                                database.kind =
+                                                                    DatabaseKind::Sqlite;
+                                                            }
                                                             kind => {
                                                                 return Err(format!("invalid database kind value '{}'.", kind));
                                                             }
                                                         },
+                                                        "host" => {
+                                                            database.host = Some(attr.value);
+                                                        }
                                                         "id" => {
                                                             database.id = attr.value;
                                                         }
@@ -136,10 +163,44 @@ impl Configuration {
 
                                             for attr in attributes {
                                                 match attr.name.to_string().as_str() {
+                                                    "access-key" => {
+                                                        destination.s3_access_key =
+                                                            Some(attr.value);
+                                                    }
+                                                    "account" => {
+                                                        destination.account = attr.value;
+                                                    }
+                                                    "account-key" => {
+                                                        destination.account_key = attr.value;
+                                                    }
                                                     "bucket" => {
-                                                        destination.s3_bucket = attr.value;
+                                                        destination.bucket = attr.value;
+                                                    }
+                                                    "ca-file" => {
+                                                        destination.s3_ca_file = Some(attr.value);
+                                                    }
+                                                    "container" => {
+                                                        destination.container = attr.value;
+                                                    }
+                                                    "credentials-file" => {
+                                                        destination.credentials_file = attr.value;
+                                                    }
+                                                    "external-id" => {
+                                                        destination.s3_external_id =
+                                                            Some(attr.value);
+                                                    }
+                                                    "insecure-skip-verify" => {
+                                                        destination.s3_insecure_skip_verify =
+                                                            Configuration::parse_bool(&attr.value);
                                                     }
                                                     "kind" => match attr.value.as_str() {
+                                                        "azure" => {
+                                                            destination.kind =
+                                                                DestinationKind::Azure;
+                                                        }
+                                                        "gcs" => {
+                                                            destination.kind = DestinationKind::Gcs;
+                                                        }
                                                         "none" => {
                                                             destination.kind =
                                                                 DestinationKind::None;
@@ -148,12 +209,19 @@ impl Configuration {
                                                             destination.kind =
                                                                 DestinationKind::Directory;
                                                         }
+                                                        "ftp" => {
+                                                            destination.kind = DestinationKind::Ftp;
+                                                        }
                                                         "s3" => {
                                                             destination.kind = DestinationKind::S3;
                                                         }
                                                         "ssh" => {
                                                             destination.kind = DestinationKind::SSH;
                                                         }
+                                                        "webdav" => {
+                                                            destination.kind =
+                                                                DestinationKind::WebDav;
+                                                        }
                                                         kind => {
                                                             return Err(format!("invalid destination kind value '{}'.", kind));
                                                         }
@@ -169,27 +237,171 @@ impl Configuration {
                                                             Err(_) => {}
                                                         }
                                                     }
+                                                    "max-download-rate" => {
+                                                        destination.max_download_rate.rate =
+                                                            RateLimit::parse_rate(&attr.value)?;
+                                                    }
+                                                    "max-upload-rate" => {
+                                                        destination.max_upload_rate.rate =
+                                                            RateLimit::parse_rate(&attr.value)?;
+                                                    }
+                                                    "object-lock-mode" => {
+                                                        match attr.value.to_uppercase().as_str() {
+                                                            mode
+                                                            @ ("GOVERNANCE" | "COMPLIANCE") => {
+                                                                destination.s3_object_lock_mode =
+                                                                    Some(String::from(mode));
+                                                            }
+                                                            _ => {
+                                                                return Err(format!("invalid destination object-lock-mode value '{}'.", attr.value));
+                                                            }
+                                                        }
+                                                    }
+                                                    "object-lock-retention" => {
+                                                        destination.s3_object_lock_retention = Some(
+                                                            LockRetention::parse(&attr.value)?,
+                                                        );
+                                                    }
+                                                    "passive" => {
+                                                        destination.ftp_passive =
+                                                            Configuration::parse_bool(&attr.value);
+                                                    }
+                                                    "path-style" => {
+                                                        destination.s3_path_style =
+                                                            Configuration::parse_bool(&attr.value);
+                                                    }
                                                     "password" => {
                                                         destination.password = attr.value;
                                                     }
                                                     "path" => {
                                                         destination.path = attr.value;
                                                     }
+                                                    "port" => match attr.value.parse() {
+                                                        Ok(port) => {
+                                                            destination.port = Some(port);
+                                                        }
+                                                        Err(_) => {
+                                                            return Err(format!("invalid destination port value '{}'.", attr.value));
+                                                        }
+                                                    },
                                                     "id" => {
                                                         destination.id = attr.value;
                                                     }
                                                     "endpoint" => {
+                                                        destination.endpoint = attr.value.clone();
                                                         s3_endpoint = Some(attr.value);
                                                     }
+                                                    "profile" => {
+                                                        destination.s3_profile = Some(attr.value);
+                                                    }
                                                     "region" => {
                                                         s3_region_name = Some(attr.value);
                                                     }
+                                                    "restore-days" => match attr.value.parse() {
+                                                        Ok(days) if days > 0 => {
+                                                            destination.s3_restore_days = days;
+                                                        }
+                                                        _ => {
+                                                            return Err(format!("invalid destination restore-days value '{}'.", attr.value));
+                                                        }
+                                                    },
+                                                    "restore-timeout" => {
+                                                        match parse_duration0::parse(
+                                                            attr.value.as_str(),
+                                                        ) {
+                                                            Ok(duration) => {
+                                                                destination.s3_restore_timeout =
+                                                                    duration;
+                                                            }
+                                                            Err(_) => {
+                                                                return Err(format!("invalid destination restore-timeout value '{}'.", attr.value));
+                                                            }
+                                                        }
+                                                    }
+                                                    "restore-tier" => {
+                                                        match attr.value.to_lowercase().as_str() {
+                                                            "bulk" => {
+                                                                destination.s3_restore_tier =
+                                                                    String::from("Bulk");
+                                                            }
+                                                            "expedited" => {
+                                                                destination.s3_restore_tier =
+                                                                    String::from("Expedited");
+                                                            }
+                                                            "standard" => {
+                                                                destination.s3_restore_tier =
+                                                                    String::from("Standard");
+                                                            }
+                                                            _ => {
+                                                                return Err(format!("invalid destination restore-tier value '{}'.", attr.value));
+                                                            }
+                                                        }
+                                                    }
+                                                    "role-arn" => {
+                                                        destination.s3_role_arn = Some(attr.value);
+                                                    }
+                                                    "sas-token" => {
+                                                        destination.sas_token = attr.value;
+                                                    }
+                                                    "secret-key" => {
+                                                        destination.s3_secret_key =
+                                                            Some(attr.value);
+                                                    }
                                                     "server" => {
                                                         destination.server = attr.value;
                                                     }
+                                                    "sse-customer-key" => {
+                                                        destination.s3_sse_customer_key =
+                                                            Some(attr.value);
+                                                    }
+                                                    "sse-kms-key-id" => {
+                                                        destination.s3_sse_kms_key_id =
+                                                            Some(attr.value);
+                                                    }
+                                                    "storage-class" => {
+                                                        match attr.value.to_uppercase().as_str() {
+                                                            storage_class @ ("STANDARD"
+                                                            | "STANDARD_IA"
+                                                            | "ONEZONE_IA"
+                                                            | "INTELLIGENT_TIERING"
+                                                            | "GLACIER"
+                                                            | "GLACIER_IR"
+                                                            | "DEEP_ARCHIVE"
+                                                            | "REDUCED_REDUNDANCY") => {
+                                                                destination.s3_storage_class =
+                                                                    Some(String::from(
+                                                                        storage_class,
+                                                                    ));
+                                                            }
+                                                            _ => {
+                                                                return Err(format!("invalid destination storage-class value '{}'.", attr.value));
+                                                            }
+                                                        }
+                                                    }
+                                                    "tls" => match attr.value.as_str() {
+                                                        "none" => {
+                                                            destination.ftp_tls = FtpTls::None;
+                                                        }
+                                                        "explicit" => {
+                                                            destination.ftp_tls = FtpTls::Explicit;
+                                                        }
+                                                        "implicit" => {
+                                                            destination.ftp_tls = FtpTls::Implicit;
+                                                        }
+                                                        tls => {
+                                                            return Err(format!("invalid destination tls value '{}'.", tls));
+                                                        }
+                                                    },
+                                                    "url" => {
+                                                        destination.url = attr.value;
+                                                    }
                                                     "username" => {
                                                         destination.username = attr.value;
                                                     }
+                                                    "web-identity-token-file" => {
+                                                        destination.s3_web_identity_token_file =
+                                                            Some(attr.value);
+                                                    }
                                                     _ => {}
                                                 }
                                             }
@@ -240,6 +452,84 @@ impl Configuration {
                                                 };
                                             }
                                         }
+                                        "rate-limit" if depth == 4 => {
+                                            let mut from = None;
+                                            let mut to = None;
+                                            let mut upload_rate = None;
+                                            let mut download_rate = None;
+                                            for attr in attributes {
+                                                match attr.name.to_string().as_str() {
+                                                    "from" => {
+                                                        from = Some(RateLimit::parse_time(
+                                                            &attr.value,
+                                                        )?);
+                                                    }
+                                                    "to" => {
+                                                        to = Some(RateLimit::parse_time(
+                                                            &attr.value,
+                                                        )?);
+                                                    }
+                                                    "max-download-rate" => {
+                                                        download_rate = Some(
+                                                            RateLimit::parse_rate(&attr.value)?,
+                                                        );
+                                                    }
+                                                    "max-upload-rate" => {
+                                                        upload_rate = Some(RateLimit::parse_rate(
+                                                            &attr.value,
+                                                        )?);
+                                                    }
+                                                    _ => {}
+                                                }
+                                            }
+                                            let (from, to) = match (from, to) {
+                                                (Some(from), Some(to)) => (from, to),
+                                                _ => {
+                                                    return Err(format!(
+                                                        "the rate-limit-from and -to must be set for destination '{}'",
+                                                        destination.id
+                                                    ));
+                                                }
+                                            };
+                                            if let Some(rate) = download_rate {
+                                                destination
+                                                    .max_download_rate
+                                                    .schedules
+                                                    .push(RateSchedule { from, to, rate });
+                                            }
+                                            if let Some(rate) = upload_rate {
+                                                destination
+                                                    .max_upload_rate
+                                                    .schedules
+                                                    .push(RateSchedule { from, to, rate });
+                                            }
+                                        }
+                                        "metadata" | "tag" if depth == 4 => {
+                                            let mut tag_name = String::new();
+                                            let mut tag_value = String::new();
+                                            for attr in attributes {
+                                                match attr.name.to_string().as_str() {
+                                                    "name" => {
+                                                        tag_name = attr.value;
+                                                    }
+                                                    "value" => {
+                                                        tag_value = attr.value;
+                                                    }
+                                                    _ => {}
+                                                }
+                                            }
+                                            if tag_name.is_empty() {
+                                                return Err(format!(
+                                                    "the {}-name must be set for destination '{}'",
+                                                    name, destination.id
+                                                ));
+                                            }
+                                            if name.to_string() == "tag" {
+                                                destination.s3_tags.push((tag_name, tag_value));
+                                            } else {
+                                                destination.s3_metadata.insert(tag_name, tag_value);
+                                            }
+                                        }
                                         "encryptions" => {}
                                         "encryption" => {
                                             encryption = Encryption::new();
@@ -307,10 +597,34 @@ impl Configuration {
                                                     }
                                                 }
                                                 if destination.kind == DestinationKind::S3 {
-                                                    if destination.s3_bucket.len() == 0 {
+                                                    if destination.bucket.is_empty() {
                                                         return Err(format!("the destination-bucket must be set for kind: s3"));
                                                     }
                                                 }
+                                                if destination.kind == DestinationKind::S3 {
+                                                    destination.validate_s3_options()?;
+                                                }
+                                                if destination.kind == DestinationKind::Azure
+                                                    && (destination.account.is_empty()
+                                                        || destination.container.is_empty())
+                                                {
+                                                    return Err(String::from("the destination-account and -container must be set for kind: azure"));
+                                                }
+                                                if destination.kind == DestinationKind::Gcs
+                                                    && destination.bucket.is_empty()
+                                                {
+                                                    return Err(String::from("the destination-bucket must be set for kind: gcs"));
+                                                }
+                                                if destination.kind == DestinationKind::Ftp
+                                                    && destination.server.is_empty()
+                                                {
+                                                    return Err(String::from("the destination-server must be set for kind: ftp"));
+                                                }
+                                                if destination.kind == DestinationKind::WebDav
+                                                    && destination.url.is_empty()
+                                                {
+                                                    return Err(String::from("the destination-url must be set for kind: webdav"));
+                                                }
                                                 configuration
                                                     .destinations
                                                     .push(destination.clone());
@@ -342,6 +656,7 @@ impl Configuration {
                     Ok(_) => {
                         let mut global_db_id = String::new();
                         let mut depth = 0;
+                        let mut in_archive = false;
                         let parser = EventReader::new(BufReader::new(file));
                         for e in parser {
                             match e {
@@ -436,6 +751,7 @@ impl Configuration {
                                         "archives" => {}
                                         "archive" => {
                                             archive = Archive::new();
+                                            in_archive = true;
 
                                             for attr in attributes {
                                                 match attr.name.to_string().as_str() {
@@ -494,6 +810,93 @@ impl Configuration {
                                                 }
                                             }
                                         }
+                                        "hooks" => {}
+                                        "hook" => {
+                                            let mut hook: Option<Hook> = None;
+                                            let mut command = String::new();
+                                            let mut timeout = None;
+                                            let mut fail_on_error = None;
+                                            for attr in attributes {
+                                                match attr.name.to_string().as_str() {
+                                                    "event" => {
+                                                        hook = Some(Hook::new(HookEvent::parse(
+                                                            &attr.value,
+                                                        )?));
+                                                    }
+                                                    "command" => {
+                                                        command = attr.value;
+                                                    }
+                                                    "timeout" => {
+                                                        match parse_duration0::parse(
+                                                            attr.value.as_str(),
+                                                        ) {
+                                                            Ok(duration) => {
+                                                                timeout = Some(duration);
+                                                            }
+                                                            Err(_) => {
+                                                                return Err(format!("invalid hook timeout value '{}'.", attr.value));
+                                                            }
+                                                        }
+                                                    }
+                                                    "on-error" => {
+                                                        match attr.value.as_str() {
+                                                            "fail" => {
+                                                                fail_on_error = Some(true);
+                                                            }
+                                                            "warn" => {
+                                                                fail_on_error = Some(false);
+                                                            }
+                                                            on_error => {
+                                                                return Err(format!("invalid hook on-error value '{}'.", on_error));
+                                                            }
+                                                        }
+                                                    }
+                                                    _ => {}
+                                                }
+                                            }
+
+                                            let mut hook = match hook {
+                                                Some(hook) => hook,
+                                                None => {
+                                                    return Err(String::from(
+                                                        "the hook-event must be set",
+                                                    ));
+                                                }
+                                            };
+                                            if command.is_empty() {
+                                                return Err(String::from(
+                                                    "the hook-command must be set",
+                                                ));
+                                            }
+                                            hook.command = command;
+                                            hook.timeout = timeout.unwrap_or(hook.timeout);
+                                            hook.fail_on_error =
+                                                fail_on_error.unwrap_or(hook.fail_on_error);
+                                            match in_archive {
+                                                true => archive.hooks.hooks.push(hook),
+                                                false => configuration.hooks.hooks.push(hook),
+                                            }
+                                        }
+                                        "commands" => {}
+                                        "command" => {
+                                            let mut command = CommandSource::new();
+                                            for attr in attributes {
+                                                match attr.name.to_string().as_str() {
+                                                    "name" => {
+                                                        command.name = attr.value;
+                                                    }
+                                                    "exec" => {
+                                                        command.exec = attr.value;
+                                                    }
+                                                    "restore" => {
+                                                        command.restore = Some(attr.value);
+                                                    }
+                                                    _ => {}
+                                                }
+                                            }
+                                            command.validate()?;
+                                            archive.commands.push(command);
+                                        }
                                         "directories" => {}
                                         "directory" => {
                                             let mut dir = Directory::new();
@@ -509,6 +912,36 @@ impl Configuration {
                                                     "group" => {
                                                         dir.group = Some(attr.value);
                                                     }
+                                                    "special-files" => {
+                                                        dir.special_files = match attr
+                                                            .value
+                                                            .as_str()
+                                                        {
+                                                            "archive" => SpecialFiles::Archive,
+                                                            "skip" => SpecialFiles::Skip,
+                                                            "error" => SpecialFiles::Error,
+                                                            special_files => {
+                                                                return Err(format!("invalid special-files value '{}'.", special_files));
+                                                            }
+                                                        };
+                                                    }
+                                                    "snapshot" => {
+                                                        dir.snapshot = match attr.value.as_str() {
+                                                            "btrfs" => Some(SnapshotKind::Btrfs),
+                                                            "lvm" => Some(SnapshotKind::Lvm),
+                                                            "zfs" => Some(SnapshotKind::Zfs),
+                                                            "none" => None,
+                                                            snapshot => {
+                                                                return Err(format!(
+                                                                    "invalid snapshot value '{}'.",
+                                                                    snapshot
+                                                                ));
+                                                            }
+                                                        };
+                                                    }
+                                                    "snapshot-size" => {
+                                                        dir.snapshot_size = Some(attr.value);
+                                                    }
                                                     _ => {}
                                                 }
                                             }
@@ -524,6 +957,7 @@ impl Configuration {
                                     match name.to_string().as_str() {
                                         "archive" => {
                                             configuration.archives.push(archive.clone());
+                                            in_archive = false;
                                         }
                                         "databases" => {
                                             global_db_id = String::new();
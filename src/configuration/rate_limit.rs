@@ -0,0 +1,169 @@
+use chrono::{Local, NaiveTime};
+
+use crate::formatter::Formatter;
+
+/// A time window of a day, in which another rate applies; `to` may be before `from`, if the
+/// window spans midnight. A missing rate means unlimited.
+#[derive(Clone, Debug)]
+pub struct RateSchedule {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+    pub rate: Option<usize>,
+}
+
+impl RateSchedule {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+/// The maximum transfer rate in bytes per second, optionally overridden by time-of-day windows.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    pub rate: Option<usize>,
+    pub schedules: Vec<RateSchedule>,
+}
+
+impl RateLimit {
+    pub fn new() -> RateLimit {
+        RateLimit {
+            rate: None,
+            schedules: Vec::new(),
+        }
+    }
+
+    /// Whether any rate is configured, regardless of the current time.
+    pub fn is_limited(&self) -> bool {
+        self.rate.is_some()
+            || self
+                .schedules
+                .iter()
+                .any(|schedule| schedule.rate.is_some())
+    }
+
+    /// Gets the rate, which applies now; the first matching schedule wins.
+    pub fn current_rate(&self) -> Option<usize> {
+        let now = Local::now().time();
+        match self
+            .schedules
+            .iter()
+            .find(|schedule| schedule.contains(now))
+        {
+            Some(schedule) => schedule.rate,
+            None => self.rate,
+        }
+    }
+
+    /// Parses a rate like "512 KB", "2MB/s" or "unlimited".
+    pub fn parse_rate(value: &str) -> Result<Option<usize>, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("unlimited") {
+            return Ok(None);
+        }
+
+        match Formatter::parse_size(value.trim_end_matches("/s")) {
+            Some(rate) if rate > 0 => Ok(Some(rate)),
+            _ => Err(format!("invalid rate value '{}'.", value)),
+        }
+    }
+
+    /// Parses a time of day like "08:00".
+    pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(value.trim(), "%H:%M")
+            .map_err(|_| format!("invalid time value '{}'.", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(from: &str, to: &str, rate: Option<usize>) -> RateSchedule {
+        RateSchedule {
+            from: RateLimit::parse_time(from).unwrap(),
+            to: RateLimit::parse_time(to).unwrap(),
+            rate,
+        }
+    }
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!(RateLimit::parse_rate("512").unwrap(), Some(512));
+        assert_eq!(RateLimit::parse_rate("512 KB").unwrap(), Some(512 * 1024));
+        assert_eq!(
+            RateLimit::parse_rate(" 2MB/s ").unwrap(),
+            Some(2 * 1024 * 1024)
+        );
+        assert_eq!(RateLimit::parse_rate("1.5 K").unwrap(), Some(1536));
+        assert_eq!(RateLimit::parse_rate("Unlimited").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        assert!(RateLimit::parse_rate("").is_err());
+        assert!(RateLimit::parse_rate("0").is_err());
+        assert!(RateLimit::parse_rate("fast").is_err());
+        assert!(RateLimit::parse_rate("10 XB").is_err());
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(
+            RateLimit::parse_time("08:30").unwrap(),
+            NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+        );
+        assert!(RateLimit::parse_time("25:00").is_err());
+        assert!(RateLimit::parse_time("8").is_err());
+    }
+
+    #[test]
+    fn schedules_contain_their_window() {
+        let day = schedule("08:00", "18:00", Some(1));
+        assert!(day.contains(RateLimit::parse_time("08:00").unwrap()));
+        assert!(day.contains(RateLimit::parse_time("17:59").unwrap()));
+        assert!(!day.contains(RateLimit::parse_time("18:00").unwrap()));
+        assert!(!day.contains(RateLimit::parse_time("07:59").unwrap()));
+    }
+
+    #[test]
+    fn schedules_span_midnight() {
+        let night = schedule("22:00", "06:00", None);
+        assert!(night.contains(RateLimit::parse_time("23:00").unwrap()));
+        assert!(night.contains(RateLimit::parse_time("00:00").unwrap()));
+        assert!(!night.contains(RateLimit::parse_time("06:00").unwrap()));
+        assert!(!night.contains(RateLimit::parse_time("12:00").unwrap()));
+    }
+
+    #[test]
+    fn is_limited_by_any_rate() {
+        let mut rate_limit = RateLimit::new();
+        assert!(!rate_limit.is_limited());
+
+        rate_limit.schedules.push(schedule("22:00", "06:00", None));
+        assert!(!rate_limit.is_limited());
+
+        rate_limit
+            .schedules
+            .push(schedule("08:00", "18:00", Some(1024)));
+        assert!(rate_limit.is_limited());
+    }
+
+    #[test]
+    fn current_rate_prefers_the_first_matching_schedule() {
+        let mut rate_limit = RateLimit::new();
+        rate_limit.rate = Some(1024);
+        assert_eq!(rate_limit.current_rate(), Some(1024));
+
+        // Both windows together cover the whole day.
+        rate_limit.schedules.push(schedule("00:00", "12:00", None));
+        rate_limit.schedules.push(schedule("12:00", "00:00", None));
+        rate_limit
+            .schedules
+            .push(schedule("00:00", "23:59", Some(2048)));
+        assert_eq!(rate_limit.current_rate(), None);
+    }
+}
@@ -29,4 +29,24 @@ impl Formatter {
             unit = size_unit
         )
     }
+
+    /// Parses a size like "512", "1.5 MB" or "2GiB"; the units are powers of 1024, as printed by
+    /// `format_size`.
+    pub fn parse_size(value: &str) -> Option<usize> {
+        let value = value.trim();
+        let unit_start = value
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(value.len());
+        let number: f64 = value[..unit_start].parse().ok()?;
+        let factor = match value[unit_start..].trim().to_uppercase().as_str() {
+            "" | "B" => 1_u64,
+            "K" | "KB" | "KIB" => 1024,
+            "M" | "MB" | "MIB" => 1024 * 1024,
+            "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+            "T" | "TB" | "TIB" => 1024 * 1024 * 1024 * 1024,
+            _ => return None,
+        };
+
+        Some((number * factor as f64) as usize)
+    }
 }
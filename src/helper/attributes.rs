@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::io::{self, Read};
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use log::warn;
+use nix::unistd::{geteuid, Gid, Group, Uid, User};
+use tar::{Entry, EntryType, Header};
+
+use crate::restore::options::OwnerMapping;
+
+/// The owners, permissions and extended attributes (incl. POSIX ACLs and SELinux labels) of the
+/// archived files; the owner names are kept in the tar headers (or the PAX records "uname" and
+/// "gname", if they're too long), the extended attributes in PAX records "SCHILY.xattr.<name>".
+pub struct FileAttributes {
+    user_names: HashMap<u32, Option<String>>,
+    group_names: HashMap<u32, Option<String>>,
+}
+
+impl FileAttributes {
+    pub const XATTR_PREFIX: &'static str = "SCHILY.xattr.";
+
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            user_names: HashMap::new(),
+            group_names: HashMap::new(),
+        }
+    }
+
+    /// Sets the owner names of the file in the header, which already contains its metadata, and
+    /// gets the PAX records of the names, which don't fit in, and of the extended attributes.
+    pub fn read(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        header: &mut Header,
+    ) -> Vec<(String, Vec<u8>)> {
+        let mut pax_records = Vec::new();
+
+        let user_name = self.user_names.entry(metadata.uid()).or_insert_with(|| {
+            User::from_uid(Uid::from_raw(metadata.uid()))
+                .ok()
+                .flatten()
+                .map(|user| user.name)
+        });
+        if let Some(user_name) = user_name {
+            if header.set_username(user_name).is_err() {
+                pax_records.push((String::from("uname"), user_name.as_bytes().to_vec()));
+            }
+        }
+        let group_name = self.group_names.entry(metadata.gid()).or_insert_with(|| {
+            Group::from_gid(Gid::from_raw(metadata.gid()))
+                .ok()
+                .flatten()
+                .map(|group| group.name)
+        });
+        if let Some(group_name) = group_name {
+            if header.set_groupname(group_name).is_err() {
+                pax_records.push((String::from("gname"), group_name.as_bytes().to_vec()));
+            }
+        }
+
+        let names = match xattr::list_deref(path) {
+            Ok(names) => names,
+            Err(err) => {
+                warn!(
+                    "unable to list the extended attributes of {}: {}",
+                    path.display(),
+                    err
+                );
+                return pax_records;
+            }
+        };
+        for name in names {
+            match xattr::get_deref(path, &name) {
+                Ok(Some(value)) => pax_records.push((
+                    format!("{}{}", Self::XATTR_PREFIX, name.to_string_lossy()),
+                    value,
+                )),
+                Ok(None) => {}
+                Err(err) => warn!(
+                    "unable to read the extended attribute {:?} of {}: {}",
+                    name,
+                    path.display(),
+                    err
+                ),
+            }
+        }
+
+        pax_records
+    }
+
+    /// Reads the PAX records of the entry, which has to be done before it's unpacked.
+    pub fn read_pax_records<R: Read>(
+        entry: &mut Entry<R>,
+    ) -> Result<Vec<(String, Vec<u8>)>, String> {
+        let extensions = match entry.pax_extensions().map_err(Self::map_error)? {
+            Some(extensions) => extensions,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut pax_records = Vec::new();
+        for extension in extensions {
+            let extension = extension.map_err(Self::map_error)?;
+            pax_records.push((
+                String::from_utf8_lossy(extension.key_bytes()).to_string(),
+                extension.value_bytes().to_vec(),
+            ));
+        }
+        Ok(pax_records)
+    }
+
+    fn get_pax_record<'a>(pax_records: &'a [(String, Vec<u8>)], key: &str) -> Option<&'a [u8]> {
+        pax_records
+            .iter()
+            .find(|(record_key, _)| record_key == key)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Gets the owner of the entry as mapped by `owners`; with `OwnerMapping::Named`, the owner
+    /// and group names are looked up on this system, falling back to the archived ids.
+    fn map_owner(
+        header: &Header,
+        pax_records: &[(String, Vec<u8>)],
+        owners: OwnerMapping,
+    ) -> Result<(Option<u32>, Option<u32>), String> {
+        let uid = header.uid().map_err(Self::map_error)? as u32;
+        let gid = header.gid().map_err(Self::map_error)? as u32;
+        match owners {
+            OwnerMapping::None => Ok((None, None)),
+            OwnerMapping::Numeric => Ok((Some(uid), Some(gid))),
+            OwnerMapping::Named => {
+                let user_name = Self::get_pax_record(pax_records, "uname")
+                    .or(header.username_bytes())
+                    .map(String::from_utf8_lossy);
+                let group_name = Self::get_pax_record(pax_records, "gname")
+                    .or(header.groupname_bytes())
+                    .map(String::from_utf8_lossy);
+                let mapped_uid = user_name
+                    .and_then(|name| User::from_name(&name).ok().flatten())
+                    .map(|user| user.uid.as_raw());
+                let mapped_gid = group_name
+                    .and_then(|name| Group::from_name(&name).ok().flatten())
+                    .map(|group| group.gid.as_raw());
+                Ok((
+                    Some(mapped_uid.unwrap_or(uid)),
+                    Some(mapped_gid.unwrap_or(gid)),
+                ))
+            }
+        }
+    }
+
+    /// Restores the owner, the special permission bits and the extended attributes of the
+    /// unpacked entry at `dst_path`. The archived owner is only restored by root; the owner and
+    /// group of the directory's configuration override it.
+    pub fn restore(
+        dst_path: &Path,
+        header: &Header,
+        pax_records: &[(String, Vec<u8>)],
+        owners: OwnerMapping,
+        uid_override: Option<u32>,
+        gid_override: Option<u32>,
+    ) -> Result<(), String> {
+        let (mut uid, mut gid) = match geteuid().is_root() {
+            true => Self::map_owner(header, pax_records, owners)?,
+            false => (None, None),
+        };
+        uid = uid_override.or(uid);
+        gid = gid_override.or(gid);
+
+        let is_symlink = header.entry_type() == EntryType::Symlink;
+        if uid.is_some() || gid.is_some() {
+            lchown(dst_path, uid, gid).map_err(Self::map_error)?;
+
+            // Changing the owner clears the setuid and setgid bits, so they're set again.
+            let mode = header.mode().map_err(Self::map_error)? & 0o7777;
+            if !is_symlink && mode & 0o7000 != 0 {
+                fs::set_permissions(dst_path, fs::Permissions::from_mode(mode))
+                    .map_err(Self::map_error)?;
+            }
+        }
+
+        // The ACLs are set after the permissions, as changing the mode would alter their mask.
+        for (key, value) in pax_records {
+            let name = match key.strip_prefix(Self::XATTR_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            if let Err(err) = xattr::set(dst_path, name, value) {
+                warn!(
+                    "unable to restore the extended attribute {} of {}: {}",
+                    name,
+                    dst_path.display(),
+                    err
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+
+    use tar::Builder;
+
+    fn header(user_name: &str, group_name: &str) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_uid(12345);
+        header.set_gid(23456);
+        header.set_username(user_name).unwrap();
+        header.set_groupname(group_name).unwrap();
+        header
+    }
+
+    #[test]
+    fn round_trips_extended_attributes_through_pax_records() {
+        let root = env::temp_dir().join(format!("rusty-backup-xattr-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let source = root.join("source");
+        let restored = root.join("restored");
+        File::create(&source).unwrap();
+        File::create(&restored).unwrap();
+        xattr::set(&source, "user.rusty-backup", b"value").unwrap();
+
+        let metadata = fs::metadata(&source).unwrap();
+        let mut header = Header::new_gnu();
+        header.set_metadata(&metadata);
+        let pax_records = FileAttributes::new().read(&source, &metadata, &mut header);
+        let mut tar = Builder::new(Vec::new());
+        tar.append_pax_extensions(
+            pax_records
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_slice())),
+        )
+        .unwrap();
+        tar.append_data(&mut header, "source", io::empty()).unwrap();
+        let content = tar.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(content.as_slice());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        let read_records = FileAttributes::read_pax_records(&mut entry).unwrap();
+        FileAttributes::restore(
+            &restored,
+            entry.header(),
+            &read_records,
+            OwnerMapping::None,
+            None,
+            None,
+        )
+        .unwrap();
+        let value = xattr::get(&restored, "user.rusty-backup").unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(read_records.contains(&(
+            String::from("SCHILY.xattr.user.rusty-backup"),
+            b"value".to_vec()
+        )));
+        assert_eq!(value.as_deref(), Some(&b"value"[..]));
+    }
+
+    #[test]
+    fn maps_the_owner_by_name() {
+        let header = header("root", "root");
+        assert_eq!(
+            FileAttributes::map_owner(&header, &[], OwnerMapping::Named).unwrap(),
+            (Some(0), Some(0))
+        );
+
+        // The PAX records hold the names, which don't fit into the header.
+        let header = self::header("rusty-backup-nobody", "rusty-backup-nobody");
+        let pax_records = [
+            (String::from("uname"), b"root".to_vec()),
+            (String::from("gname"), b"root".to_vec()),
+        ];
+        assert_eq!(
+            FileAttributes::map_owner(&header, &pax_records, OwnerMapping::Named).unwrap(),
+            (Some(0), Some(0))
+        );
+    }
+
+    #[test]
+    fn maps_unknown_names_to_the_archived_ids() {
+        let header = header("rusty-backup-nobody", "rusty-backup-nobody");
+        assert_eq!(
+            FileAttributes::map_owner(&header, &[], OwnerMapping::Named).unwrap(),
+            (Some(12345), Some(23456))
+        );
+    }
+
+    #[test]
+    fn maps_the_owner_by_id_or_not_at_all() {
+        let header = header("root", "root");
+        assert_eq!(
+            FileAttributes::map_owner(&header, &[], OwnerMapping::Numeric).unwrap(),
+            (Some(12345), Some(23456))
+        );
+        assert_eq!(
+            FileAttributes::map_owner(&header, &[], OwnerMapping::None).unwrap(),
+            (None, None)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+/// The SHA-256 checksum of an archive file, which is stored in a sidecar file next to it, in the
+/// format of `sha256sum`, e.g. "<hex>  2024-03-01_www.tar.bz2".
+pub struct Checksum {}
+
+impl Checksum {
+    pub const EXTENSION: &'static str = ".sha256";
+
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    pub fn get_sidecar_name(file_name: &str) -> String {
+        format!("{}{}", file_name, Self::EXTENSION)
+    }
+
+    fn encode(hasher: Sha256) -> String {
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Computes the hex encoded SHA-256 checksum of the given file.
+    pub fn compute(file_name: &str) -> Result<String, String> {
+        let mut file = File::open(file_name).map_err(Self::map_error)?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(Self::map_error)?;
+
+        Ok(Self::encode(hasher))
+    }
+
+    /// Writes the sidecar file of the given file and returns its name.
+    pub fn write_sidecar(file_name: &str) -> Result<String, String> {
+        let sidecar_name = Self::get_sidecar_name(file_name);
+        fs::write(
+            &sidecar_name,
+            format!("{}  {}\n", Self::compute(file_name)?, file_name),
+        )
+        .map_err(Self::map_error)?;
+
+        Ok(sidecar_name)
+    }
+
+    /// Reads the checksum of the given file from its sidecar file.
+    pub fn read_sidecar(file_name: &str) -> Result<String, String> {
+        let sidecar_name = Self::get_sidecar_name(file_name);
+        let content = fs::read_to_string(&sidecar_name).map_err(Self::map_error)?;
+        Self::parse_sidecar(&content, file_name)
+            .ok_or_else(|| format!("no checksum of '{}' in '{}'.", file_name, sidecar_name))
+    }
+
+    /// Checks the given file against the checksum of its sidecar file.
+    pub fn verify(file_name: &str) -> Result<(), String> {
+        let expected_checksum = Self::read_sidecar(file_name)?;
+        let checksum = Self::compute(file_name)?;
+        if checksum != expected_checksum {
+            return Err(format!(
+                "checksum mismatch: expected {}, got {}",
+                expected_checksum, checksum
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Gets the checksum of the given file from the content of a sidecar file.
+    pub fn parse_sidecar(content: &str, file_name: &str) -> Option<String> {
+        content
+            .lines()
+            .filter_map(|line| line.split_once(char::is_whitespace))
+            .find(|(_, name)| name.trim_start_matches([' ', '*']) == file_name)
+            .map(|(checksum, _)| checksum.to_lowercase())
+    }
+}
+
+/// A reader, which computes the SHA-256 checksum of everything read through it, so a file can be
+/// hashed while it's archived.
+pub struct ChecksumReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Adds `len` zero bytes to the checksum, e.g. for a hole of a sparse file, which isn't read.
+    pub fn update_zeros(&mut self, mut len: u64) {
+        let zeros = [0; 8192];
+        self.size += len;
+        while len > 0 {
+            let chunk = len.min(zeros.len() as u64) as usize;
+            self.hasher.update(&zeros[..chunk]);
+            len -= chunk as u64;
+        }
+    }
+
+    /// Gets the number of read bytes and their hex encoded checksum.
+    pub fn finish(self) -> (u64, String) {
+        (self.size, Checksum::encode(self.hasher))
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_bytes = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read_bytes]);
+        self.size += read_bytes as u64;
+        Ok(read_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// The SHA-256 checksum of "content".
+    const CONTENT_SHA256: &str = "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73";
+
+    fn write_temp_file(name: &str, content: &str) -> String {
+        let file_name = env::temp_dir()
+            .join(format!(
+                "rusty-backup-checksum-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&file_name, content).unwrap();
+        file_name
+    }
+
+    #[test]
+    fn writes_the_sidecar_in_the_format_of_sha256sum() {
+        let file_name = write_temp_file("sidecar.tar", "content");
+        let sidecar_name = Checksum::write_sidecar(&file_name).unwrap();
+        let sidecar = fs::read_to_string(&sidecar_name).unwrap();
+        let checksum = Checksum::read_sidecar(&file_name);
+        let verified = Checksum::verify(&file_name);
+        fs::remove_file(&file_name).unwrap();
+        fs::remove_file(&sidecar_name).unwrap();
+
+        assert_eq!(sidecar_name, format!("{}.sha256", file_name));
+        assert_eq!(sidecar, format!("{}  {}\n", CONTENT_SHA256, file_name));
+        assert_eq!(checksum.unwrap(), CONTENT_SHA256);
+        assert!(verified.is_ok());
+    }
+
+    #[test]
+    fn fails_to_verify_a_corrupted_file() {
+        let file_name = write_temp_file("corrupted.tar", "content");
+        let sidecar_name = Checksum::write_sidecar(&file_name).unwrap();
+        fs::write(&file_name, "c0ntent").unwrap();
+        let verified = Checksum::verify(&file_name);
+        fs::remove_file(&file_name).unwrap();
+        fs::remove_file(&sidecar_name).unwrap();
+
+        let err = verified.unwrap_err();
+        assert!(err.starts_with("checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn fails_to_verify_without_a_sidecar() {
+        let file_name = write_temp_file("unchecked.tar", "content");
+        let verified = Checksum::verify(&file_name);
+        fs::remove_file(&file_name).unwrap();
+
+        assert!(verified.is_err());
+    }
+
+    #[test]
+    fn parses_sidecars_of_sha256sum() {
+        let content = format!(
+            "{}  www.tar\n{}  *db.tar\n",
+            CONTENT_SHA256,
+            CONTENT_SHA256.to_uppercase()
+        );
+        assert_eq!(
+            Checksum::parse_sidecar(&content, "www.tar").as_deref(),
+            Some(CONTENT_SHA256)
+        );
+        // Binary mode is marked by an asterisk; the checksum is compared in lowercase.
+        assert_eq!(
+            Checksum::parse_sidecar(&content, "db.tar").as_deref(),
+            Some(CONTENT_SHA256)
+        );
+        assert_eq!(Checksum::parse_sidecar(&content, "other.tar"), None);
+        assert_eq!(Checksum::parse_sidecar("", "www.tar"), None);
+    }
+}
@@ -1,10 +1,21 @@
 use std::collections::VecDeque;
+use std::io::Write;
 use std::ops::{Add, Sub};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
+use crate::formatter::Formatter;
+
+pub mod attributes;
+pub mod checksum;
+pub mod sparse;
+
 pub struct ProgressStats {
     pub total_length: Option<usize>,
     pub progressed_size: usize,
+    /// The maximum rate in bytes per second, the transfer is throttled to.
+    pub rate_limit: Option<usize>,
     finished: bool,
     start_time: Instant,
     time_series: VecDeque<(Instant, usize)>,
@@ -15,6 +26,7 @@ impl ProgressStats {
         ProgressStats {
             total_length: None,
             progressed_size: 0,
+            rate_limit: None,
             finished: false,
             start_time: std::time::Instant::now(),
             time_series: VecDeque::new(),
@@ -198,4 +210,82 @@ impl ProgressStats {
     pub fn set_finished(&mut self) {
         self.finished = true;
     }
+
+    /// Spawns a thread, which prints the progress every 250ms until the stats are finished.
+    pub fn spawn_output_thread(stats: Arc<Mutex<ProgressStats>>, label: &str) -> JoinHandle<()> {
+        let label = label.to_string();
+        thread::spawn(move || {
+            let cloned_arc_download_stats = Arc::clone(&stats);
+            loop {
+                let mut output_string = format!("{} ", label);
+                match cloned_arc_download_stats.lock() {
+                    Ok(download_stats) => {
+                        output_string.push_str(
+                            Formatter::format_size(download_stats.progressed_size, 2).as_str(),
+                        );
+                        if let Some(content_length) = download_stats.total_length {
+                            output_string.push_str(
+                                format!(
+                                    "/{} ({number:.2}%)",
+                                    Formatter::format_size(content_length, 2),
+                                    number = download_stats.get_progress_in_percentage().unwrap()
+                                )
+                                .as_str(),
+                            );
+                        }
+                        output_string.push_str(
+                            format!("; runtime: {}", download_stats.get_formatted_runtime())
+                                .as_str(),
+                        );
+                        if let Some(formatted_ete) = download_stats.get_formatted_ete() {
+                            output_string.push_str(format!("; ete: {}", formatted_ete).as_str());
+                        }
+                        output_string.push_str(
+                            format!(
+                                "; speed: {}/s",
+                                Formatter::format_size(download_stats.get_average_speed(), 2)
+                            )
+                            .as_str(),
+                        );
+                        output_string.push_str(
+                            format!(
+                                "; speed (<=1s): {}/s",
+                                Formatter::format_size(
+                                    download_stats.get_average_speed_for_last_second(),
+                                    2
+                                )
+                            )
+                            .as_str(),
+                        );
+                        output_string.push_str(
+                            format!(
+                                "; speed (<=10s): {}/s",
+                                Formatter::format_size(
+                                    download_stats.get_average_speed_for_last_10_seconds(),
+                                    2
+                                )
+                            )
+                            .as_str(),
+                        );
+                        if let Some(rate_limit) = download_stats.rate_limit {
+                            output_string.push_str(
+                                format!("; limit: {}/s", Formatter::format_size(rate_limit, 2))
+                                    .as_str(),
+                            );
+                        }
+
+                        print!("{}\r{}", termion::clear::CurrentLine, output_string);
+                        std::io::stdout().flush().unwrap_or_default();
+
+                        if download_stats.is_finished() {
+                            break;
+                        }
+                    }
+                    Err(_) => continue,
+                }
+
+                thread::sleep(Duration::from_millis(250));
+            }
+        })
+    }
 }
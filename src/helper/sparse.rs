@@ -0,0 +1,399 @@
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+
+use nix::errno::Errno;
+use nix::unistd::{lseek, Whence};
+use tar::{EntryType, GnuSparseHeader, Header};
+
+use crate::helper::checksum::ChecksumReader;
+
+const BLOCK_SIZE: u64 = 512;
+
+/// The segments of a sparse file, which contain data, as (offset, length); the rest of the file
+/// are holes. It's archived as GNU sparse entry, which contains the data segments only.
+pub struct SparseMap {
+    pub segments: Vec<(u64, u64)>,
+    pub size: u64,
+}
+
+impl SparseMap {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Finds the data segments of the file by SEEK_DATA and SEEK_HOLE, if it occupies less space
+    /// than its size; the segments are aligned to 512 bytes, as required by the tar format.
+    pub fn detect(file: &File, metadata: &Metadata) -> Result<Option<SparseMap>, String> {
+        let size = metadata.size();
+        if metadata.blocks() * BLOCK_SIZE >= size {
+            return Ok(None);
+        }
+
+        let mut segments: Vec<(u64, u64)> = Vec::new();
+        let mut position = 0;
+        while position < size {
+            let start = match lseek(file, position as i64, Whence::SeekData) {
+                Ok(start) => start as u64,
+                // There's no more data behind the position.
+                Err(Errno::ENXIO) => break,
+                // The filesystem doesn't support finding holes.
+                Err(Errno::EINVAL) => return Ok(None),
+                Err(errno) => return Err(format!("error: {:?}", errno)),
+            };
+            let end = lseek(file, start as i64, Whence::SeekHole)
+                .map_err(|errno| format!("error: {:?}", errno))? as u64;
+
+            let start = start / BLOCK_SIZE * BLOCK_SIZE;
+            let end = end
+                .div_ceil(BLOCK_SIZE)
+                .saturating_mul(BLOCK_SIZE)
+                .min(size);
+            match segments.last_mut() {
+                Some((last_start, last_length)) if *last_start + *last_length >= start => {
+                    *last_length = end - *last_start;
+                }
+                _ => segments.push((start, end - start)),
+            }
+            position = end;
+        }
+
+        // A hole at the end is marked by an empty segment, so the size of the file is restored.
+        if segments
+            .last()
+            .is_none_or(|(start, length)| start + length < size)
+        {
+            segments.push((size, 0));
+        }
+
+        Ok(Some(SparseMap { segments, size }))
+    }
+
+    pub fn data_size(&self) -> u64 {
+        self.segments.iter().map(|(_, length)| length).sum()
+    }
+
+    fn build_sparse_header(segment: &(u64, u64)) -> GnuSparseHeader {
+        let mut sparse_header = GnuSparseHeader {
+            offset: [0; 12],
+            numbytes: [0; 12],
+        };
+        sparse_header.set_offset(segment.0);
+        sparse_header.set_length(segment.1);
+        sparse_header
+    }
+
+    /// Turns the GNU header, which contains the file's metadata, into a sparse header with the
+    /// first segments, and gets the extension blocks with the remaining segments, which precede
+    /// the data in the archive.
+    pub fn prepare_header(&self, header: &mut Header) -> Vec<u8> {
+        header.set_entry_type(EntryType::GNUSparse);
+        header.set_size(self.data_size());
+
+        let mut extension = Vec::new();
+        if let Some(gnu_header) = header.as_gnu_mut() {
+            gnu_header.set_real_size(self.size);
+            let header_count = gnu_header.sparse.len();
+            for (sparse_header, segment) in gnu_header.sparse.iter_mut().zip(&self.segments) {
+                *sparse_header = Self::build_sparse_header(segment);
+            }
+            gnu_header.set_is_extended(self.segments.len() > header_count);
+
+            // An extension block holds 21 segments and a flag, whether another block follows.
+            let chunks: Vec<&[(u64, u64)]> = self.segments[header_count.min(self.segments.len())..]
+                .chunks(21)
+                .collect();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let mut block = [0; BLOCK_SIZE as usize];
+                for (position, segment) in chunk.iter().enumerate() {
+                    let sparse_header = Self::build_sparse_header(segment);
+                    block[position * 24..position * 24 + 12].copy_from_slice(&sparse_header.offset);
+                    block[position * 24 + 12..position * 24 + 24]
+                        .copy_from_slice(&sparse_header.numbytes);
+                }
+                block[21 * 24] = (index + 1 < chunks.len()) as u8;
+                extension.extend_from_slice(&block);
+            }
+        }
+
+        extension
+    }
+
+    /// Whether the last of the sparse header blocks is followed by an extension block.
+    pub fn is_extended(blocks: &[u8]) -> bool {
+        match blocks.len() / BLOCK_SIZE as usize {
+            0 => false,
+            1 => Header::from_byte_slice(&blocks[..BLOCK_SIZE as usize])
+                .as_gnu()
+                .is_some_and(|gnu_header| gnu_header.is_extended()),
+            count => blocks[(count - 1) * BLOCK_SIZE as usize + 21 * 24] == 1,
+        }
+    }
+
+    /// Parses the segments of a GNU sparse entry from its header block and the following
+    /// extension blocks.
+    pub fn from_header_blocks(blocks: &[u8]) -> Result<SparseMap, String> {
+        if blocks.len() < BLOCK_SIZE as usize {
+            return Err(String::from("the sparse header is incomplete."));
+        }
+        let header = Header::from_byte_slice(&blocks[..BLOCK_SIZE as usize]);
+        let gnu_header = header
+            .as_gnu()
+            .ok_or_else(|| String::from("the sparse entry has no GNU header."))?;
+
+        // The extension blocks hold 21 segments of 24 bytes each.
+        let fields = gnu_header
+            .sparse
+            .iter()
+            .map(|sparse_header| (&sparse_header.offset[..], &sparse_header.numbytes[..]))
+            .chain(
+                blocks[BLOCK_SIZE as usize..]
+                    .chunks(BLOCK_SIZE as usize)
+                    .flat_map(|block| block.chunks_exact(24).take(21))
+                    .map(|field| (&field[..12], &field[12..])),
+            );
+
+        let mut segments = Vec::new();
+        for (offset, numbytes) in fields {
+            let mut sparse_header = Self::build_sparse_header(&(0, 0));
+            sparse_header.offset.copy_from_slice(offset);
+            sparse_header.numbytes.copy_from_slice(numbytes);
+            if sparse_header.is_empty() {
+                continue;
+            }
+            segments.push((
+                sparse_header.offset().map_err(Self::map_error)?,
+                sparse_header.length().map_err(Self::map_error)?,
+            ));
+        }
+        Ok(SparseMap {
+            segments,
+            size: gnu_header.real_size().map_err(Self::map_error)?,
+        })
+    }
+
+    /// Locates the offset of the file: gets the position of its data within the entry's data
+    /// (`None` within a hole), and the end of the data segment or hole, which contains it.
+    pub fn locate(&self, offset: u64) -> (Option<u64>, u64) {
+        let mut data_position = 0;
+        for (start, length) in &self.segments {
+            if offset < *start {
+                return (None, *start);
+            }
+            if offset < start + length {
+                return (Some(data_position + offset - start), start + length);
+            }
+            data_position += length;
+        }
+        (None, self.size)
+    }
+}
+
+/// Reads the data segments of a sparse file, and computes the checksum of its whole content,
+/// including the holes.
+pub struct SparseReader<'a> {
+    inner: ChecksumReader<File>,
+    map: &'a SparseMap,
+    segment_index: usize,
+    position: u64,
+    remaining: u64,
+}
+
+impl<'a> SparseReader<'a> {
+    pub fn new(file: File, map: &'a SparseMap) -> Self {
+        Self {
+            inner: ChecksumReader::new(file),
+            map,
+            segment_index: 0,
+            position: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Gets the size of the file and the hex encoded checksum of its content.
+    pub fn finish(mut self) -> (u64, String) {
+        self.inner.update_zeros(self.map.size - self.position);
+        self.inner.finish()
+    }
+}
+
+impl Read for SparseReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let (start, length) = match self.map.segments.get(self.segment_index) {
+                Some(segment) => *segment,
+                None => return Ok(0),
+            };
+            self.segment_index += 1;
+            self.inner.update_zeros(start - self.position);
+            self.inner.get_mut().seek(SeekFrom::Start(start))?;
+            self.position = start;
+            self.remaining = length;
+        }
+
+        let max_length = buf.len().min(self.remaining as usize);
+        let read_bytes = self.inner.read(&mut buf[..max_length])?;
+        if read_bytes == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the sparse file was truncated while reading",
+            ));
+        }
+        self.position += read_bytes as u64;
+        self.remaining -= read_bytes as u64;
+        Ok(read_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::FileExt;
+
+    use sha2::{Digest, Sha256};
+
+    /// Creates a file of 1 MiB with two blocks of data and holes in between and at the end.
+    fn create_sparse_file(name: &str) -> String {
+        let file_name = env::temp_dir()
+            .join(format!(
+                "rusty-backup-sparse-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string();
+        let file = File::create(&file_name).unwrap();
+        file.set_len(1 << 20).unwrap();
+        file.write_all_at(&[1; 4096], 64 << 10).unwrap();
+        file.write_all_at(&[2; 4096], 256 << 10).unwrap();
+        file_name
+    }
+
+    fn detect(file_name: &str) -> Option<SparseMap> {
+        let file = File::open(file_name).unwrap();
+        SparseMap::detect(&file, &file.metadata().unwrap()).unwrap()
+    }
+
+    fn build_map(count: u64) -> SparseMap {
+        SparseMap {
+            segments: (0..count).map(|index| (index * 2048, 512)).collect(),
+            size: count * 2048,
+        }
+    }
+
+    #[test]
+    fn detects_the_data_segments_and_the_hole_at_the_end() {
+        let file_name = create_sparse_file("detect");
+        let map = detect(&file_name);
+        fs::remove_file(&file_name).unwrap();
+
+        let map = map.expect("the file has holes");
+        assert_eq!(
+            map.segments,
+            [(64 << 10, 4096), (256 << 10, 4096), (1 << 20, 0)]
+        );
+        assert_eq!(map.size, 1 << 20);
+        assert_eq!(map.data_size(), 8192);
+    }
+
+    #[test]
+    fn detects_no_segments_of_a_file_without_holes() {
+        let file_name = env::temp_dir()
+            .join(format!("rusty-backup-sparse-{}-dense", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        File::create(&file_name)
+            .unwrap()
+            .write_all(&[1; 8192])
+            .unwrap();
+        let map = detect(&file_name);
+        fs::remove_file(&file_name).unwrap();
+
+        assert!(map.is_none());
+    }
+
+    #[test]
+    fn fits_few_segments_into_the_header() {
+        let map = build_map(3);
+        let mut header = Header::new_gnu();
+        let extension = map.prepare_header(&mut header);
+
+        assert!(extension.is_empty());
+        assert_eq!(header.entry_type(), EntryType::GNUSparse);
+        assert_eq!(header.entry_size().unwrap(), 3 * 512);
+        assert!(!SparseMap::is_extended(header.as_bytes()));
+
+        let parsed = SparseMap::from_header_blocks(header.as_bytes()).unwrap();
+        assert_eq!(parsed.segments, map.segments);
+        assert_eq!(parsed.size, map.size);
+    }
+
+    #[test]
+    fn chains_the_extension_blocks_of_many_segments() {
+        // 4 segments in the header, 21 in the first extension block and 5 in the second one.
+        let map = build_map(30);
+        let mut header = Header::new_gnu();
+        let extension = map.prepare_header(&mut header);
+
+        assert_eq!(extension.len(), 2 * BLOCK_SIZE as usize);
+        assert_eq!(extension[21 * 24], 1);
+        assert_eq!(extension[BLOCK_SIZE as usize + 21 * 24], 0);
+
+        let mut blocks = header.as_bytes().to_vec();
+        assert!(SparseMap::is_extended(&blocks));
+        blocks.extend_from_slice(&extension[..BLOCK_SIZE as usize]);
+        assert!(SparseMap::is_extended(&blocks));
+        blocks.extend_from_slice(&extension[BLOCK_SIZE as usize..]);
+        assert!(!SparseMap::is_extended(&blocks));
+
+        let parsed = SparseMap::from_header_blocks(&blocks).unwrap();
+        assert_eq!(parsed.segments, map.segments);
+        assert_eq!(parsed.size, map.size);
+    }
+
+    #[test]
+    fn keeps_the_empty_segment_of_a_hole_at_the_end() {
+        let map = SparseMap {
+            segments: vec![(0, 512), (4096, 0)],
+            size: 4096,
+        };
+        let mut header = Header::new_gnu();
+        map.prepare_header(&mut header);
+
+        let parsed = SparseMap::from_header_blocks(header.as_bytes()).unwrap();
+        assert_eq!(parsed.segments, [(0, 512), (4096, 0)]);
+        assert_eq!(parsed.size, 4096);
+    }
+
+    #[test]
+    fn fails_on_an_incomplete_header() {
+        assert!(SparseMap::from_header_blocks(&[0; 100]).is_err());
+        assert!(!SparseMap::is_extended(&[]));
+    }
+
+    #[test]
+    fn reads_the_data_and_checksums_the_whole_content() {
+        let file_name = create_sparse_file("reader");
+        let content = fs::read(&file_name).unwrap();
+        let map = detect(&file_name).expect("the file has holes");
+
+        let mut reader = SparseReader::new(File::open(&file_name).unwrap(), &map);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        let (size, sha256) = reader.finish();
+        fs::remove_file(&file_name).unwrap();
+
+        let mut expected = vec![1; 4096];
+        expected.extend_from_slice(&[2; 4096]);
+        assert_eq!(data, expected);
+        assert_eq!(size, 1 << 20);
+        let expected_sha256: String = Sha256::digest(&content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        assert_eq!(sha256, expected_sha256);
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::{Serialize, Serializer};
+
+use crate::configuration::{compression::Compression, Configuration};
+use crate::formatter::Formatter;
+
+#[derive(Serialize)]
+struct ListedArchive {
+    name: String,
+    destination: String,
+    compression: String,
+    encryption: Option<String>,
+    files: Vec<ListedFile>,
+}
+
+#[derive(Serialize)]
+struct ListedFile {
+    name: String,
+    size: u64,
+    /// The modification time in UTC (RFC 3339), if the destination provides it.
+    #[serde(serialize_with = "serialize_utc")]
+    modified: Option<NaiveDateTime>,
+}
+
+fn serialize_utc<S: Serializer>(
+    modified: &Option<NaiveDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match modified {
+        Some(modified) => {
+            serializer.serialize_str(&modified.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+pub struct List {}
+
+impl List {
+    fn get_compression_name(compression: &Compression) -> String {
+        match compression {
+            Compression::None => String::from("none"),
+            compression => compression
+                .to_extension_string()
+                .trim_start_matches('.')
+                .to_string(),
+        }
+    }
+
+    fn print_text(listed_archives: &[ListedArchive]) {
+        for listed_archive in listed_archives {
+            println!(
+                "{} (destination: {}, compression: {}, encryption: {})",
+                listed_archive.name,
+                listed_archive.destination,
+                listed_archive.compression,
+                listed_archive.encryption.as_deref().unwrap_or("none")
+            );
+            if listed_archive.files.is_empty() {
+                println!("  no archive files found");
+            }
+            for listed_file in &listed_archive.files {
+                let modified = match listed_file.modified {
+                    Some(modified) => Local
+                        .from_utc_datetime(&modified)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string(),
+                    None => String::from("-"),
+                };
+                println!(
+                    "  {:19}  {:>10}  {}",
+                    modified,
+                    Formatter::format_size(listed_file.size as usize, 2),
+                    listed_file.name
+                );
+            }
+        }
+    }
+
+    /// Prints the files of every archive on its destination, oldest first, as text (with local
+    /// times) or as JSON (with UTC times).
+    pub async fn start(configuration: Configuration, format: &str) -> Result<(), String> {
+        if format != "text" && format != "json" {
+            return Err(format!("invalid list format: {}", format));
+        }
+
+        let mut listed_archives = Vec::new();
+        for archive in &configuration.archives {
+            let mut archive_files = archive.destination.list_archive_files(archive).await?;
+            archive_files.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.name.cmp(&b.name)));
+
+            listed_archives.push(ListedArchive {
+                name: archive.name.clone(),
+                destination: archive.destination.id.clone(),
+                compression: Self::get_compression_name(&archive.compression),
+                encryption: archive
+                    .encryption
+                    .as_ref()
+                    .map(|encryption| encryption.cipher.clone()),
+                files: archive_files
+                    .into_iter()
+                    .map(|archive_file| ListedFile {
+                        name: archive_file.name,
+                        size: archive_file.size,
+                        modified: archive_file.modified,
+                    })
+                    .collect(),
+            });
+        }
+
+        match format {
+            "json" => println!(
+                "{}",
+                serde_json::to_string_pretty(&listed_archives)
+                    .map_err(|err| format!("error: {:?}", err))?
+            ),
+            _ => Self::print_text(&listed_archives),
+        }
+
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
 use std::process;
 
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use log::{error, info, LevelFilter};
 use log4rs::{
     append::console::{ConsoleAppender, Target},
@@ -17,14 +17,28 @@ use log4rs::{
 };
 
 mod backup;
+mod browse;
 mod configuration;
 mod formatter;
 mod helper;
+mod list;
+mod mount;
 mod restore;
+mod storage;
+mod sync;
+mod verify;
 
 struct Arguments {
+    all_versions: bool,
+    archive_selection: restore::selection::ArchiveSelection,
     backup_settings_file: String,
+    list_format: String,
     mode: String,
+    mountpoint: Option<String>,
+    restore_options: restore::options::RestoreOptions,
+    sync_from: Option<String>,
+    sync_to: Option<String>,
+    test_import: bool,
 }
 
 async fn start_main() {
@@ -47,13 +61,84 @@ async fn start_main() {
                 return;
             }
         },
-        "restore" => match restore::Restore::start(backup_configuration).await {
-            Ok(_) => {}
-            Err(why) => {
+        "browse" => {
+            if let Err(why) = browse::Browse::start(
+                backup_configuration,
+                arguments.archive_selection,
+                arguments.restore_options,
+                arguments.all_versions,
+            )
+            .await
+            {
                 error!("{}", why);
-                return;
             }
-        },
+        }
+        "list" => {
+            if let Err(why) = list::List::start(backup_configuration, &arguments.list_format).await
+            {
+                error!("{}", why);
+            }
+        }
+        "mount" => {
+            let mountpoint = match arguments.mountpoint {
+                Some(mountpoint) => mountpoint,
+                None => {
+                    error!("the mount mode requires a mountpoint (--mountpoint).");
+                    return;
+                }
+            };
+            if let Err(why) = mount::Mount::start(
+                backup_configuration,
+                arguments.archive_selection,
+                arguments.all_versions,
+                &mountpoint,
+            )
+            .await
+            {
+                error!("{}", why);
+            }
+        }
+        "restore" => {
+            match restore::Restore::start(
+                backup_configuration,
+                arguments.archive_selection,
+                arguments.restore_options,
+            )
+            .await
+            {
+                Ok(_) => {}
+                Err(why) => {
+                    error!("{}", why);
+                }
+            }
+        }
+        "sync" => {
+            let sync_to = match arguments.sync_to {
+                Some(sync_to) => sync_to,
+                None => {
+                    error!("the sync mode requires a target destination (--to).");
+                    return;
+                }
+            };
+            if let Err(why) =
+                sync::Synchronization::start(backup_configuration, arguments.sync_from, sync_to)
+                    .await
+            {
+                error!("{}", why);
+            }
+        }
+        "verify" => {
+            if let Err(why) = verify::Verify::start(
+                backup_configuration,
+                arguments.archive_selection,
+                arguments.all_versions,
+                arguments.test_import,
+            )
+            .await
+            {
+                error!("{}", why);
+            }
+        }
         mode => {
             error!("invalid mode: {}", mode);
             return;
@@ -105,7 +190,130 @@ fn get_arguments() -> Arguments {
                 .short('m')
                 .long("mode")
                 .value_name("MODE")
-                .help("backup (default) oder restore"),
+                .help("backup (default), restore, sync, list, browse, mount or verify"),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_name("NAME")
+                .help("restore/browse/mount/verify: the name of the archive file to restore (default: the newest)"),
+        )
+        .arg(
+            Arg::new("at")
+                .long("at")
+                .value_name("DATETIME")
+                .help("restore/browse/mount/verify: the newest archive file at the given time, e.g. 2024-03-01 or \"2024-03-01 18:30\""),
+        )
+        .arg(
+            Arg::new("before")
+                .long("before")
+                .value_name("DATETIME")
+                .help("restore/browse/mount/verify: only archive files, which were created before the given time"),
+        )
+        .arg(
+            Arg::new("after")
+                .long("after")
+                .value_name("DATETIME")
+                .help("restore/browse/mount/verify: only archive files, which were created after the given time"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("restore/browse: only the entries matching the glob, e.g. \"/var/www/**/*.php\""),
+        )
+        .arg(
+            Arg::new("path")
+                .long("path")
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("restore/browse: only the given file or subtree, e.g. /var/www/html"),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .value_name("DIR")
+                .help("restore: the directory to restore the files into (default: their original location)"),
+        )
+        .arg(
+            Arg::new("databases-only")
+                .long("databases-only")
+                .action(ArgAction::SetTrue)
+                .help("restore: only the databases"),
+        )
+        .arg(
+            Arg::new("database-name")
+                .long("database-name")
+                .value_name("NAME")
+                .help("restore: the new database to restore into, instead of dropping the original one; {name} is replaced by its name"),
+        )
+        .arg(
+            Arg::new("database-host")
+                .long("database-host")
+                .value_name("HOST")
+                .help("restore: the server to restore the databases to, e.g. a staging server"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("restore: print the changes against the live files and databases, without restoring anything"),
+        )
+        .arg(
+            Arg::new("files-only")
+                .long("files-only")
+                .action(ArgAction::SetTrue)
+                .help("restore: only the files, without the databases"),
+        )
+        .arg(
+            Arg::new("owners")
+                .long("owners")
+                .value_name("MAPPING")
+                .value_parser(["named", "numeric", "none"])
+                .help("restore: map the file owners by their names (default), by their numeric ids, or not at all"),
+        )
+        .arg(
+            Arg::new("swap-databases")
+                .long("swap-databases")
+                .action(ArgAction::SetTrue)
+                .help("restore: import into a new database and replace the original one only after a successful import"),
+        )
+        .arg(
+            Arg::new("all-versions")
+                .long("all-versions")
+                .action(ArgAction::SetTrue)
+                .help("browse/mount/verify: all archive files instead of only the newest (or selected) one"),
+        )
+        .arg(
+            Arg::new("mountpoint")
+                .long("mountpoint")
+                .value_name("DIR")
+                .help("mount: the directory to mount the archives at"),
+        )
+        .arg(
+            Arg::new("test-import")
+                .long("test-import")
+                .action(ArgAction::SetTrue)
+                .help("verify: import the database dumps into scratch databases, which are dropped afterwards"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("list: text (default) or json"),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .value_name("DESTINATION-ID")
+                .help("sync: the source destination (default: the destination of each archive)"),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .value_name("DESTINATION-ID")
+                .help("sync: the target destination, which receives the missing archives"),
         )
         .get_matches();
 
@@ -186,8 +394,89 @@ fn get_arguments() -> Arguments {
         .to_string();
     mode = matches.get_one("mode").unwrap_or(&mode).to_string();
 
+    let archive_selection = restore::selection::ArchiveSelection {
+        archive_name: matches.get_one::<String>("archive").cloned(),
+        at: parse_datetime_argument(&matches, "at", true),
+        before: parse_datetime_argument(&matches, "before", false),
+        after: parse_datetime_argument(&matches, "after", true),
+    };
+
+    let includes = matches
+        .get_many::<String>("include")
+        .unwrap_or_default()
+        .map(
+            |include| match restore::options::RestoreOptions::parse_include(include) {
+                Ok(pattern) => pattern,
+                Err(message) => {
+                    error!("--include: {}", message);
+                    process::exit(1);
+                }
+            },
+        )
+        .collect();
+    let owners = matches
+        .get_one::<String>("owners")
+        .map(
+            |owners| match restore::options::OwnerMapping::parse(owners) {
+                Ok(owners) => owners,
+                Err(message) => {
+                    error!("--owners: {}", message);
+                    process::exit(1);
+                }
+            },
+        )
+        .unwrap_or_default();
+    // The working directory is changed on restore, so a relative target is resolved beforehand.
+    let target = matches.get_one::<String>("target").map(|target| {
+        std::env::current_dir()
+            .map(|current_dir| current_dir.join(target).to_string_lossy().to_string())
+            .unwrap_or_else(|_| target.clone())
+    });
+    let restore_options = restore::options::RestoreOptions {
+        includes,
+        paths: matches
+            .get_many::<String>("path")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        target,
+        databases_only: matches.get_flag("databases-only"),
+        database_name: matches.get_one::<String>("database-name").cloned(),
+        database_host: matches.get_one::<String>("database-host").cloned(),
+        dry_run: matches.get_flag("dry-run"),
+        files_only: matches.get_flag("files-only"),
+        owners,
+        swap_databases: matches.get_flag("swap-databases"),
+    };
+
     Arguments {
+        all_versions: matches.get_flag("all-versions"),
+        archive_selection,
         backup_settings_file,
+        list_format: matches
+            .get_one::<String>("format")
+            .cloned()
+            .unwrap_or_else(|| String::from("text")),
         mode,
+        mountpoint: matches.get_one::<String>("mountpoint").cloned(),
+        restore_options,
+        sync_from: matches.get_one::<String>("from").cloned(),
+        sync_to: matches.get_one::<String>("to").cloned(),
+        test_import: matches.get_flag("test-import"),
+    }
+}
+
+fn parse_datetime_argument(
+    matches: &clap::ArgMatches,
+    name: &str,
+    end_of_day: bool,
+) -> Option<chrono::NaiveDateTime> {
+    let value = matches.get_one::<String>(name)?;
+    match restore::selection::ArchiveSelection::parse_datetime(value, end_of_day) {
+        Ok(datetime) => Some(datetime),
+        Err(message) => {
+            error!("--{}: {}", name, message);
+            process::exit(1);
+        }
     }
 }
@@ -0,0 +1,837 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fuser::{
+    Errno, FileAttr, FileHandle, FileType, Filesystem, FopenFlags, INodeNo, LockOwner, OpenAccMode,
+    OpenFlags, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyXattr,
+    Request,
+};
+use log::{error, warn};
+use tar::{EntryType, Header};
+
+use crate::configuration::archive::Archive;
+use crate::configuration::compression::Compression;
+use crate::helper::sparse::SparseMap;
+
+/// The archives never change while mounted, so the kernel may cache everything.
+const TTL: Duration = Duration::from_secs(3600);
+
+/// The location of a file's content in the (decompressed) tar-stream of an archive file.
+#[derive(Clone, Copy)]
+struct DataLocation {
+    archive_file: usize,
+    position: u64,
+    /// The position of the header of a sparse file, whose data segments are listed in it.
+    sparse_header_position: Option<u64>,
+}
+
+enum Content {
+    Directory(BTreeMap<OsString, INodeNo>),
+    File(DataLocation),
+    Symlink(PathBuf),
+}
+
+struct Node {
+    attr: FileAttr,
+    parent: INodeNo,
+    content: Content,
+}
+
+struct MountedFile {
+    archive: Archive,
+    file_name: String,
+}
+
+/// The tar-stream of an archive file, which is read forward only.
+struct TarStream {
+    reader: Box<dyn Read + Send>,
+    child: Option<Child>,
+    position: u64,
+}
+
+impl Drop for TarStream {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            child.kill().unwrap_or_default();
+            child.wait().ok();
+        }
+    }
+}
+
+struct OpenFile {
+    location: DataLocation,
+    stream: Option<TarStream>,
+    /// The segments of a sparse file and the position of its data, behind the sparse headers.
+    sparse_map: Option<(SparseMap, u64)>,
+}
+
+/// A read-only filesystem with a directory per mounted archive file, whose entries are indexed
+/// from the tar headers; the content is decrypted and decompressed lazily on reading.
+pub struct ArchiveFilesystem {
+    nodes: Vec<Node>,
+    mounted_files: Vec<MountedFile>,
+    open_files: Mutex<HashMap<u64, OpenFile>>,
+    next_file_handle: AtomicU64,
+}
+
+impl ArchiveFilesystem {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                attr: Self::build_attr(
+                    INodeNo::ROOT,
+                    FileType::Directory,
+                    0o555,
+                    SystemTime::now(),
+                ),
+                parent: INodeNo::ROOT,
+                content: Content::Directory(BTreeMap::new()),
+            }],
+            mounted_files: Vec::new(),
+            open_files: Mutex::new(HashMap::new()),
+            next_file_handle: AtomicU64::new(1),
+        }
+    }
+
+    fn build_attr(ino: INodeNo, kind: FileType, perm: u16, mtime: SystemTime) -> FileAttr {
+        FileAttr {
+            ino,
+            size: 0,
+            blocks: 0,
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: nix::unistd::getuid().as_raw(),
+            gid: nix::unistd::getgid().as_raw(),
+            rdev: 0,
+            flags: 0,
+            blksize: 512,
+        }
+    }
+
+    fn get_node(&self, ino: INodeNo) -> Option<&Node> {
+        self.nodes.get((ino.0 as usize).checked_sub(1)?)
+    }
+
+    fn add_node(
+        &mut self,
+        parent: INodeNo,
+        name: OsString,
+        kind: FileType,
+        content: Content,
+    ) -> INodeNo {
+        let ino = INodeNo(self.nodes.len() as u64 + 1);
+        let mtime = self.nodes[parent.0 as usize - 1].attr.mtime;
+        self.nodes.push(Node {
+            attr: Self::build_attr(ino, kind, 0o755, mtime),
+            parent,
+            content,
+        });
+        if let Content::Directory(children) = &mut self.nodes[parent.0 as usize - 1].content {
+            children.insert(name, ino);
+        }
+        ino
+    }
+
+    fn find_child(&self, parent: INodeNo, name: &OsStr) -> Option<INodeNo> {
+        match &self.get_node(parent)?.content {
+            Content::Directory(children) => children.get(name).copied(),
+            _ => None,
+        }
+    }
+
+    /// Gets the directory of the given path below `root`, which is created with its parents if
+    /// missing; returns `None` if a part of the path isn't a directory.
+    fn get_or_create_directory(&mut self, root: INodeNo, path: &Path) -> Option<INodeNo> {
+        let mut ino = root;
+        for name in Self::normalize_path(path)? {
+            ino = match self.find_child(ino, &name) {
+                Some(child) => match self.get_node(child)?.content {
+                    Content::Directory(_) => child,
+                    _ => return None,
+                },
+                None => self.add_node(
+                    ino,
+                    name,
+                    FileType::Directory,
+                    Content::Directory(BTreeMap::new()),
+                ),
+            };
+        }
+        Some(ino)
+    }
+
+    /// Gets the names of the path's components; returns `None` for paths leaving their root.
+    fn normalize_path(path: &Path) -> Option<Vec<OsString>> {
+        let mut names = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name.to_os_string()),
+                Component::CurDir | Component::RootDir => {}
+                _ => return None,
+            }
+        }
+        Some(names)
+    }
+
+    fn apply_header(attr: &mut FileAttr, header: &Header) {
+        if let Ok(mode) = header.mode() {
+            attr.perm = (mode & 0o7777) as u16;
+        }
+        if let Ok(uid) = header.uid() {
+            attr.uid = uid as u32;
+        }
+        if let Ok(gid) = header.gid() {
+            attr.gid = gid as u32;
+        }
+        if let Ok(mtime) = header.mtime() {
+            let mtime = UNIX_EPOCH + Duration::from_secs(mtime);
+            attr.atime = mtime;
+            attr.mtime = mtime;
+            attr.ctime = mtime;
+            attr.crtime = mtime;
+        }
+    }
+
+    /// Adds a directory with the given name for the downloaded archive file, and indexes the
+    /// entries of its tar-stream below it.
+    pub fn add_archive_file(
+        &mut self,
+        archive: &Archive,
+        file_name: &str,
+        name: &str,
+        modified: SystemTime,
+    ) -> Result<(), String> {
+        if self.find_child(INodeNo::ROOT, OsStr::new(name)).is_some() {
+            return Err(format!("the directory '{}' is mounted twice.", name));
+        }
+        let archive_file = self.mounted_files.len();
+        self.mounted_files.push(MountedFile {
+            archive: archive.clone(),
+            file_name: file_name.to_string(),
+        });
+        let root = self.add_node(
+            INodeNo::ROOT,
+            OsString::from(name),
+            FileType::Directory,
+            Content::Directory(BTreeMap::new()),
+        );
+        self.nodes[root.0 as usize - 1].attr =
+            Self::build_attr(root, FileType::Directory, 0o555, modified);
+
+        let (reader, mut child) = archive.open_tar_stream(file_name)?;
+        let mut tar = tar::Archive::new(reader);
+        for entry in tar.entries().map_err(Self::map_error)? {
+            let entry = entry.map_err(Self::map_error)?;
+            let entry_path = entry.path().map_err(Self::map_error)?.to_path_buf();
+            let header = entry.header();
+
+            let (kind, content, size) = match header.entry_type() {
+                EntryType::Directory => {
+                    match self.get_or_create_directory(root, &entry_path) {
+                        Some(ino) if ino != root => {
+                            Self::apply_header(&mut self.nodes[ino.0 as usize - 1].attr, header)
+                        }
+                        Some(_) => {}
+                        None => warn!("skipping the entry '{}'.", entry_path.display()),
+                    }
+                    continue;
+                }
+                EntryType::Regular | EntryType::Continuous | EntryType::GNUSparse => (
+                    FileType::RegularFile,
+                    Content::File(DataLocation {
+                        archive_file,
+                        position: entry.raw_file_position(),
+                        sparse_header_position: header
+                            .entry_type()
+                            .is_gnu_sparse()
+                            .then(|| entry.raw_header_position()),
+                    }),
+                    entry.size(),
+                ),
+                EntryType::Symlink => match entry.link_name().map_err(Self::map_error)? {
+                    Some(link_name) => (
+                        FileType::Symlink,
+                        Content::Symlink(link_name.to_path_buf()),
+                        0,
+                    ),
+                    None => continue,
+                },
+                EntryType::Link => {
+                    // A hardlink shares the content of its target, which precedes it in the archive.
+                    let target = entry
+                        .link_name()
+                        .map_err(Self::map_error)?
+                        .and_then(|link_name| self.resolve(root, &link_name));
+                    match target.map(|target| &self.nodes[target.0 as usize - 1]) {
+                        Some(Node {
+                            content: Content::File(location),
+                            attr,
+                            ..
+                        }) => (FileType::RegularFile, Content::File(*location), attr.size),
+                        _ => {
+                            warn!("skipping the hardlink '{}'.", entry_path.display());
+                            continue;
+                        }
+                    }
+                }
+                // Device files and FIFOs have no content to copy out of the mount.
+                _ => continue,
+            };
+
+            let parent = entry_path
+                .parent()
+                .and_then(|parent| self.get_or_create_directory(root, parent));
+            let name = entry_path.file_name().map(OsStr::to_os_string);
+            let (parent, name) = match (parent, name) {
+                (Some(parent), Some(name)) if self.find_child(parent, &name).is_none() => {
+                    (parent, name)
+                }
+                _ => {
+                    warn!("skipping the entry '{}'.", entry_path.display());
+                    continue;
+                }
+            };
+            let ino = self.add_node(parent, name, kind, content);
+            let attr = &mut self.nodes[ino.0 as usize - 1].attr;
+            Self::apply_header(attr, header);
+            if kind == FileType::RegularFile {
+                attr.size = size;
+                attr.blocks = size.div_ceil(512);
+            }
+        }
+
+        // The rest of the stream is drained, so the decryption-program doesn't fail on writing.
+        io::copy(&mut tar.into_inner(), &mut io::sink()).map_err(Self::map_error)?;
+        if let Some(child) = &mut child {
+            let status = child.wait().map_err(Self::map_error)?;
+            if !status.success() {
+                return Err(format!(
+                    "decryption of '{}' failed with: {}",
+                    file_name, status
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, root: INodeNo, path: &Path) -> Option<INodeNo> {
+        let mut ino = root;
+        for name in Self::normalize_path(path)? {
+            ino = self.find_child(ino, &name)?;
+        }
+        Some(ino)
+    }
+
+    /// Reads up to `size` bytes at `offset` of a file's content; the holes of a sparse file are
+    /// filled with zeros, its data segments are read from the entry's data.
+    fn read_content(
+        &self,
+        open_file: &mut OpenFile,
+        offset: u64,
+        size: u64,
+    ) -> Result<Vec<u8>, String> {
+        let location = open_file.location;
+        let sparse_header_position = match location.sparse_header_position {
+            Some(sparse_header_position) => sparse_header_position,
+            None => return self.read_raw(open_file, location.position + offset, size),
+        };
+
+        let (sparse_map, data_start) = match open_file.sparse_map.take() {
+            Some(sparse_map) => sparse_map,
+            None => {
+                // The header may be followed by extension blocks with further segments.
+                let mut blocks = self.read_raw(open_file, sparse_header_position, 512)?;
+                while SparseMap::is_extended(&blocks) {
+                    let position = sparse_header_position + blocks.len() as u64;
+                    let block = self.read_raw(open_file, position, 512)?;
+                    if block.len() != 512 {
+                        return Err(String::from("the sparse header is truncated."));
+                    }
+                    blocks.extend_from_slice(&block);
+                }
+                (
+                    SparseMap::from_header_blocks(&blocks)?,
+                    sparse_header_position + blocks.len() as u64,
+                )
+            }
+        };
+        let mut data = Vec::with_capacity(size as usize);
+        let mut offset = offset;
+        let end = (offset + size).min(sparse_map.size);
+        let mut result = Ok(());
+        while offset < end {
+            let (data_position, segment_end) = sparse_map.locate(offset);
+            let length = segment_end.min(end) - offset;
+            match data_position {
+                Some(data_position) => {
+                    match self.read_raw(open_file, data_start + data_position, length) {
+                        Ok(segment_data) if segment_data.len() as u64 == length => {
+                            data.extend_from_slice(&segment_data)
+                        }
+                        Ok(_) => {
+                            result = Err(String::from("the sparse file's data is truncated."));
+                            break;
+                        }
+                        Err(why) => {
+                            result = Err(why);
+                            break;
+                        }
+                    }
+                }
+                None => data.resize(data.len() + length as usize, 0),
+            }
+            offset += length;
+        }
+        open_file.sparse_map = Some((sparse_map, data_start));
+
+        result.map(|_| data)
+    }
+
+    /// Reads up to `size` bytes at the position of the (decompressed) tar-stream. Plain tar files
+    /// are read at the position directly; otherwise the open stream is read forward (or
+    /// reopened, if the position lies behind it), so copying a file decompresses the archive
+    /// only once.
+    fn read_raw(
+        &self,
+        open_file: &mut OpenFile,
+        position: u64,
+        size: u64,
+    ) -> Result<Vec<u8>, String> {
+        let mounted_file = &self.mounted_files[open_file.location.archive_file];
+        let mut data = Vec::with_capacity(size as usize);
+
+        if mounted_file.archive.encryption.is_none()
+            && mounted_file.archive.compression == Compression::Tar
+        {
+            let mut file = File::open(&mounted_file.file_name).map_err(Self::map_error)?;
+            file.seek(SeekFrom::Start(position))
+                .map_err(Self::map_error)?;
+            file.take(size)
+                .read_to_end(&mut data)
+                .map_err(Self::map_error)?;
+            return Ok(data);
+        }
+
+        if open_file
+            .stream
+            .as_ref()
+            .is_none_or(|stream| stream.position > position)
+        {
+            let (reader, child) = mounted_file
+                .archive
+                .open_tar_stream(&mounted_file.file_name)?;
+            open_file.stream = Some(TarStream {
+                reader,
+                child,
+                position: 0,
+            });
+        }
+        let stream = open_file.stream.as_mut().unwrap();
+        let skipped = io::copy(
+            &mut (&mut stream.reader).take(position - stream.position),
+            &mut io::sink(),
+        )
+        .map_err(Self::map_error)?;
+        stream.position += skipped;
+        let read = (&mut stream.reader)
+            .take(size)
+            .read_to_end(&mut data)
+            .map_err(Self::map_error)?;
+        stream.position += read as u64;
+
+        Ok(data)
+    }
+}
+
+impl Filesystem for ArchiveFilesystem {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        match self
+            .find_child(parent, name)
+            .and_then(|ino| self.get_node(ino))
+        {
+            Some(node) => reply.entry(&TTL, &node.attr, fuser::Generation(0)),
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        match self.get_node(ino) {
+            Some(node) => reply.attr(&TTL, &node.attr),
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn readlink(&self, _req: &Request, ino: INodeNo, reply: ReplyData) {
+        match self.get_node(ino).map(|node| &node.content) {
+            Some(Content::Symlink(target)) => reply.data(target.as_os_str().as_encoded_bytes()),
+            Some(_) => reply.error(Errno::EINVAL),
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn open(&self, _req: &Request, ino: INodeNo, flags: OpenFlags, reply: ReplyOpen) {
+        if flags.acc_mode() != OpenAccMode::O_RDONLY {
+            reply.error(Errno::EROFS);
+            return;
+        }
+        match self.get_node(ino).map(|node| &node.content) {
+            Some(Content::File(location)) => {
+                let file_handle = self.next_file_handle.fetch_add(1, Ordering::SeqCst);
+                self.open_files.lock().unwrap().insert(
+                    file_handle,
+                    OpenFile {
+                        location: *location,
+                        stream: None,
+                        sparse_map: None,
+                    },
+                );
+                reply.opened(FileHandle(file_handle), FopenFlags::FOPEN_KEEP_CACHE);
+            }
+            Some(Content::Directory(_)) => reply.error(Errno::EISDIR),
+            Some(_) => reply.error(Errno::EINVAL),
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        let file_size = match self.get_node(ino) {
+            Some(node) => node.attr.size,
+            None => return reply.error(Errno::ENOENT),
+        };
+        let mut open_files = self.open_files.lock().unwrap();
+        let open_file = match open_files.get_mut(&fh.0) {
+            Some(open_file) => open_file,
+            None => return reply.error(Errno::EINVAL),
+        };
+
+        let size = (size as u64).min(file_size.saturating_sub(offset));
+        match self.read_content(open_file, offset, size) {
+            Ok(data) => reply.data(&data),
+            Err(why) => {
+                error!("{}", why);
+                reply.error(Errno::EIO);
+            }
+        }
+    }
+
+    fn flush(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        _fh: FileHandle,
+        _lock_owner: LockOwner,
+        reply: ReplyEmpty,
+    ) {
+        reply.ok();
+    }
+
+    fn release(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        fh: FileHandle,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.open_files.lock().unwrap().remove(&fh.0);
+        reply.ok();
+    }
+
+    // Extended attributes aren't indexed, so every file has none.
+    fn getxattr(
+        &self,
+        _req: &Request,
+        _ino: INodeNo,
+        _name: &OsStr,
+        _size: u32,
+        reply: ReplyXattr,
+    ) {
+        reply.error(Errno::NO_XATTR);
+    }
+
+    fn listxattr(&self, _req: &Request, _ino: INodeNo, size: u32, reply: ReplyXattr) {
+        match size {
+            0 => reply.size(0),
+            _ => reply.data(&[]),
+        }
+    }
+
+    fn readdir(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        mut reply: ReplyDirectory,
+    ) {
+        let node = match self.get_node(ino) {
+            Some(node) => node,
+            None => return reply.error(Errno::ENOENT),
+        };
+        let children = match &node.content {
+            Content::Directory(children) => children,
+            _ => return reply.error(Errno::ENOTDIR),
+        };
+
+        let entries = [
+            (ino, FileType::Directory, OsStr::new(".")),
+            (node.parent, FileType::Directory, OsStr::new("..")),
+        ]
+        .into_iter()
+        .chain(children.iter().map(|(name, child)| {
+            (
+                *child,
+                self.nodes[child.0 as usize - 1].attr.kind,
+                name.as_os_str(),
+            )
+        }));
+        for (index, (child, kind, name)) in entries.enumerate().skip(offset as usize) {
+            // The offset of an entry is the one of the next entry.
+            if reply.add(child, index as u64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use bzip2::write::BzEncoder;
+
+    /// Builds a tar-stream with a directory, a file, a symlink, a hardlink and an entry, which
+    /// leaves its root.
+    fn build_tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o750);
+        header.set_mtime(1_700_000_000);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "var/www/", io::empty())
+            .unwrap();
+
+        let content = b"<h1>hello</h1>";
+        let mut header = Header::new_gnu();
+        header.set_mode(0o640);
+        header.set_uid(33);
+        header.set_size(content.len() as u64);
+        builder
+            .append_data(&mut header, "var/www/index.html", &content[..])
+            .unwrap();
+
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "var/www/link.html", "index.html")
+            .unwrap();
+
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Link);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "var/www/copy.html", "var/www/index.html")
+            .unwrap();
+
+        let mut header = Header::new_gnu();
+        header.set_size(4);
+        header.as_old_mut().name[..7].copy_from_slice(b"../evil");
+        header.set_cksum();
+        builder.append(&header, &b"evil"[..]).unwrap();
+
+        builder.into_inner().unwrap()
+    }
+
+    fn write_archive_file(name: &str, compression: Compression) -> (Archive, String) {
+        let file_name = env::temp_dir()
+            .join(format!(
+                "rusty-backup-mount-{}-{}",
+                std::process::id(),
+                name
+            ))
+            .to_string_lossy()
+            .to_string();
+        let tar = build_tar();
+        match compression {
+            Compression::TarBZ2 => {
+                let mut encoder = BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+                encoder.write_all(&tar).unwrap();
+                fs::write(&file_name, encoder.finish().unwrap()).unwrap();
+            }
+            _ => fs::write(&file_name, tar).unwrap(),
+        }
+
+        let mut archive = Archive::new();
+        archive.compression = compression;
+        (archive, file_name)
+    }
+
+    fn open_file(filesystem: &ArchiveFilesystem, ino: INodeNo) -> OpenFile {
+        match filesystem.get_node(ino).unwrap().content {
+            Content::File(location) => OpenFile {
+                location,
+                stream: None,
+                sparse_map: None,
+            },
+            _ => panic!("{:?} isn't a file", ino),
+        }
+    }
+
+    fn index(name: &str, compression: Compression) -> (ArchiveFilesystem, INodeNo, String) {
+        let (archive, file_name) = write_archive_file(name, compression);
+        let mut filesystem = ArchiveFilesystem::new();
+        filesystem
+            .add_archive_file(&archive, &file_name, "www_01", UNIX_EPOCH)
+            .unwrap();
+        let root = filesystem
+            .find_child(INodeNo::ROOT, OsStr::new("www_01"))
+            .unwrap();
+        (filesystem, root, file_name)
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            ArchiveFilesystem::normalize_path(Path::new("/var/./www")).unwrap(),
+            [OsString::from("var"), OsString::from("www")]
+        );
+        assert!(ArchiveFilesystem::normalize_path(Path::new("var/../etc")).is_none());
+    }
+
+    #[test]
+    fn indexes_the_entries_of_an_archive_file() {
+        let (filesystem, root, file_name) = index("index.tar", Compression::Tar);
+
+        let directory = filesystem.resolve(root, Path::new("var/www")).unwrap();
+        let attr = filesystem.get_node(directory).unwrap().attr;
+        assert_eq!(attr.kind, FileType::Directory);
+        assert_eq!(attr.perm, 0o750);
+        assert_eq!(attr.mtime, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        let file = filesystem
+            .resolve(root, Path::new("var/www/index.html"))
+            .unwrap();
+        let attr = filesystem.get_node(file).unwrap().attr;
+        assert_eq!(attr.kind, FileType::RegularFile);
+        assert_eq!((attr.size, attr.perm, attr.uid), (14, 0o640, 33));
+
+        let link = filesystem
+            .resolve(root, Path::new("var/www/link.html"))
+            .unwrap();
+        match &filesystem.get_node(link).unwrap().content {
+            Content::Symlink(target) => assert_eq!(target, Path::new("index.html")),
+            _ => panic!("the symlink isn't indexed"),
+        }
+
+        assert!(filesystem.resolve(root, Path::new("evil")).is_none());
+        assert!(filesystem
+            .find_child(INodeNo::ROOT, OsStr::new("evil"))
+            .is_none());
+
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn rejects_archive_files_mounted_twice() {
+        let (mut filesystem, _, file_name) = index("twice.tar", Compression::Tar);
+        let mut archive = Archive::new();
+        archive.compression = Compression::Tar;
+        assert!(filesystem
+            .add_archive_file(&archive, &file_name, "www_01", UNIX_EPOCH)
+            .is_err());
+
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn reads_files_of_plain_tar_files() {
+        let (filesystem, root, file_name) = index("read.tar", Compression::Tar);
+        let file = filesystem
+            .resolve(root, Path::new("var/www/index.html"))
+            .unwrap();
+        let mut open_file = open_file(&filesystem, file);
+        assert_eq!(
+            filesystem.read_content(&mut open_file, 0, 14).unwrap(),
+            b"<h1>hello</h1>"
+        );
+        assert_eq!(
+            filesystem.read_content(&mut open_file, 4, 5).unwrap(),
+            b"hello"
+        );
+
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn reads_hardlinks_from_their_target() {
+        let (filesystem, root, file_name) = index("hardlink.tar", Compression::Tar);
+        let copy = filesystem
+            .resolve(root, Path::new("var/www/copy.html"))
+            .unwrap();
+        assert_eq!(filesystem.get_node(copy).unwrap().attr.size, 14);
+        let mut open_file = open_file(&filesystem, copy);
+        assert_eq!(
+            filesystem.read_content(&mut open_file, 0, 14).unwrap(),
+            b"<h1>hello</h1>"
+        );
+
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+
+    #[test]
+    fn reads_files_of_compressed_archive_files() {
+        let (filesystem, root, file_name) = index("read.tar.bz2", Compression::TarBZ2);
+        let file = filesystem
+            .resolve(root, Path::new("var/www/index.html"))
+            .unwrap();
+        let mut open_file = open_file(&filesystem, file);
+        assert_eq!(
+            filesystem.read_content(&mut open_file, 4, 5).unwrap(),
+            b"hello"
+        );
+        // Reading behind the stream's position reopens it.
+        assert_eq!(
+            filesystem.read_content(&mut open_file, 0, 4).unwrap(),
+            b"<h1>"
+        );
+
+        fs::remove_file(file_name).unwrap_or_default();
+    }
+}
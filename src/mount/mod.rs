@@ -0,0 +1,153 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use fuser::{Config, MountOption};
+use log::{info, warn};
+
+use crate::configuration::destination::{Destination, Kind as DestinationKind};
+use crate::configuration::Configuration;
+use crate::restore::selection::ArchiveSelection;
+
+mod filesystem;
+
+use filesystem::ArchiveFilesystem;
+
+pub struct Mount {}
+
+impl Mount {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Downloads the selected archive file (or all matching archive files with `all_versions`)
+    /// of every archive and indexes their entries.
+    async fn build_filesystem(
+        configuration: &Configuration,
+        archive_selection: &ArchiveSelection,
+        all_versions: bool,
+        downloaded_files: &mut Vec<String>,
+    ) -> Result<ArchiveFilesystem, String> {
+        let mut filesystem = ArchiveFilesystem::new();
+
+        for archive in &configuration.archives {
+            if archive.destination.kind == DestinationKind::None {
+                warn!(
+                    "the archive '{}' has no destination to mount.",
+                    archive.name
+                );
+                continue;
+            }
+
+            let storage = archive.destination.storage().await?;
+            let archive_files =
+                Destination::list_storage_archive_files(storage.as_ref(), archive).await?;
+            let mut archive_files = archive_selection.filter(archive, archive_files);
+            if !all_versions && archive_files.len() > 1 {
+                archive_files.drain(..archive_files.len() - 1);
+            }
+            if archive_files.is_empty() {
+                warn!(
+                    "no archive file found on destination '{}'.",
+                    archive.destination.id
+                );
+            }
+
+            for archive_file in archive_files {
+                storage.get_file(&archive_file.name).await?;
+                downloaded_files.push(archive_file.name.clone());
+
+                let modified = archive_file
+                    .modified
+                    .map(|modified| {
+                        SystemTime::from(DateTime::<Utc>::from_naive_utc_and_offset(modified, Utc))
+                    })
+                    .unwrap_or_else(SystemTime::now);
+                filesystem.add_archive_file(
+                    archive,
+                    &archive_file.name,
+                    &archive.strip_file_extension(&archive_file.name),
+                    modified,
+                )?;
+                info!("indexed archive file {}", archive_file.name);
+            }
+        }
+
+        Ok(filesystem)
+    }
+
+    /// Mounts the selected archive files as a read-only filesystem at the mountpoint, with a
+    /// directory per archive file, until it's unmounted (e.g. by `umount`) or interrupted.
+    pub async fn start(
+        configuration: Configuration,
+        archive_selection: ArchiveSelection,
+        all_versions: bool,
+        mountpoint: &str,
+    ) -> Result<(), String> {
+        if !Path::new(mountpoint).is_dir() {
+            return Err(format!("the mountpoint '{}' is no directory.", mountpoint));
+        }
+        fs::create_dir_all(&configuration.working_directory).map_err(Self::map_error)?;
+        env::set_current_dir(&configuration.working_directory).map_err(Self::map_error)?;
+
+        let mut downloaded_files = Vec::new();
+        let result = Self::mount(
+            &configuration,
+            &archive_selection,
+            all_versions,
+            mountpoint,
+            &mut downloaded_files,
+        )
+        .await;
+        for downloaded_file in downloaded_files {
+            fs::remove_file(downloaded_file).unwrap_or_default();
+        }
+
+        result
+    }
+
+    async fn mount(
+        configuration: &Configuration,
+        archive_selection: &ArchiveSelection,
+        all_versions: bool,
+        mountpoint: &str,
+        downloaded_files: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let filesystem = Self::build_filesystem(
+            configuration,
+            archive_selection,
+            all_versions,
+            downloaded_files,
+        )
+        .await?;
+
+        let mut config = Config::default();
+        config.mount_options.extend([
+            MountOption::RO,
+            MountOption::FSName(String::from("rusty-backup")),
+            MountOption::Subtype(String::from("rusty-backup")),
+        ]);
+        let session =
+            fuser::spawn_mount(filesystem, mountpoint, &config).map_err(Self::map_error)?;
+        info!(
+            "mounted the archives at {}; unmount it or press Ctrl-C to finish.",
+            mountpoint
+        );
+
+        // The session ends on its own, if the filesystem is unmounted externally.
+        loop {
+            if session.guard.is_finished() {
+                return session.join().map_err(Self::map_error);
+            }
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            }
+        }
+        info!("unmounting {}", mountpoint);
+        session.umount_and_join().map_err(Self::map_error)
+    }
+}
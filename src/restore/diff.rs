@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tar::{Entry, EntryType};
+use walkdir::WalkDir;
+
+use crate::helper::checksum::{Checksum, ChecksumReader};
+use crate::restore::options::RestoreOptions;
+
+/// The changes, a restore would make to the live filesystem and databases.
+#[derive(Default)]
+pub struct RestoreDiff {
+    pub new: Vec<String>,
+    pub modified: Vec<String>,
+    /// The files with other permissions, with the current and the archived mode.
+    pub permissions: Vec<(String, u32, u32)>,
+    /// The files, which exist on the filesystem, but not in the archive.
+    pub deleted: Vec<String>,
+    /// The databases with a description, how they would be restored.
+    pub databases: Vec<String>,
+    /// The outputs of commands with a description, how they would be restored.
+    pub commands: Vec<String>,
+    pub unchanged: usize,
+    compared_paths: HashSet<PathBuf>,
+}
+
+impl RestoreDiff {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Compares the archived entry with the file at the path, it would be restored to.
+    pub fn compare_entry<R: Read>(
+        &mut self,
+        entry: &mut Entry<R>,
+        dst_path: &Path,
+    ) -> Result<(), String> {
+        self.compared_paths.insert(dst_path.to_path_buf());
+        let dst = dst_path.to_string_lossy().to_string();
+        let metadata = match fs::symlink_metadata(dst_path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.new.push(dst);
+                return Ok(());
+            }
+            Err(err) => return Err(Self::map_error(err)),
+        };
+
+        let header = entry.header();
+        let archived_mode = header.mode().map_err(Self::map_error)? & 0o7777;
+        let current_mode = metadata.permissions().mode() & 0o7777;
+        let entry_type = header.entry_type();
+        let modified = match entry_type {
+            EntryType::Directory => !metadata.is_dir(),
+            EntryType::Symlink => {
+                !metadata.file_type().is_symlink()
+                    || fs::read_link(dst_path).ok().as_deref()
+                        != entry.link_name().map_err(Self::map_error)?.as_deref()
+            }
+            _ if entry_type.is_file() || entry_type.is_gnu_sparse() => {
+                !metadata.is_file()
+                    || metadata.size() != entry.size()
+                    || Self::compute_entry_checksum(entry)? != Checksum::compute(&dst)?
+            }
+            // A hardlink is restored as another link to its target, a regular file.
+            EntryType::Link => !metadata.is_file(),
+            EntryType::Fifo => !metadata.file_type().is_fifo(),
+            EntryType::Char => !metadata.file_type().is_char_device(),
+            EntryType::Block => !metadata.file_type().is_block_device(),
+            _ => false,
+        };
+
+        if modified {
+            self.modified.push(dst);
+        } else if archived_mode != current_mode && entry_type != EntryType::Symlink {
+            self.permissions.push((dst, current_mode, archived_mode));
+        } else {
+            self.unchanged += 1;
+        }
+
+        Ok(())
+    }
+
+    fn compute_entry_checksum<R: Read>(entry: &mut Entry<R>) -> Result<String, String> {
+        let mut reader = ChecksumReader::new(entry);
+        io::copy(&mut reader, &mut io::sink()).map_err(Self::map_error)?;
+        Ok(reader.finish().1)
+    }
+
+    /// Lists the files below the restored directory `dst_root`, which aren't in the archive; its
+    /// files are named `entry_name` in the archive and originally located at `original_root`.
+    pub fn find_deleted(
+        &mut self,
+        dst_root: &Path,
+        entry_name: &str,
+        original_root: &Path,
+        options: &RestoreOptions,
+    ) {
+        for dir_entry in WalkDir::new(dst_root).min_depth(1).into_iter().flatten() {
+            if self.compared_paths.contains(dir_entry.path()) {
+                continue;
+            }
+            let relative_path = match dir_entry.path().strip_prefix(dst_root) {
+                Ok(relative_path) => relative_path,
+                Err(_) => continue,
+            };
+            let entry_path = Path::new(entry_name).join(relative_path);
+            let original_path = original_root.join(relative_path);
+            if options.matches(
+                &entry_path.to_string_lossy(),
+                Some(&original_path.to_string_lossy()),
+            ) {
+                self.deleted
+                    .push(dir_entry.path().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    pub fn print(&self, archive_file_name: &str) {
+        println!("dry-run of {}:", archive_file_name);
+        for path in &self.new {
+            println!("  new:         {}", path);
+        }
+        for path in &self.modified {
+            println!("  modified:    {}", path);
+        }
+        for (path, current_mode, archived_mode) in &self.permissions {
+            println!(
+                "  permissions: {} ({:04o} => {:04o})",
+                path, current_mode, archived_mode
+            );
+        }
+        for path in &self.deleted {
+            println!("  deleted:     {} (not in the archive)", path);
+        }
+        for database in &self.databases {
+            println!("  database:    {}", database);
+        }
+        for command in &self.commands {
+            println!("  command:     {}", command);
+        }
+        println!(
+            "  {} new, {} modified, {} permission changes, {} deleted, {} unchanged, {} databases, {} commands",
+            self.new.len(),
+            self.modified.len(),
+            self.permissions.len(),
+            self.deleted.len(),
+            self.unchanged,
+            self.databases.len(),
+            self.commands.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::symlink;
+
+    use nix::sys::stat::Mode;
+    use nix::unistd::mkfifo;
+    use tar::{Builder, Header};
+
+    fn append_file(tar: &mut Builder<Vec<u8>>, path: &str, content: &str) {
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, path, content.as_bytes())
+            .unwrap();
+    }
+
+    fn append_special(tar: &mut Builder<Vec<u8>>, path: &str, entry_type: EntryType, link: &str) {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(0);
+        header.set_mode(0o644);
+        if link.is_empty() {
+            tar.append_data(&mut header, path, io::empty()).unwrap();
+        } else {
+            tar.append_link(&mut header, path, link).unwrap();
+        }
+    }
+
+    /// Compares the entries of a small archive with a directory, which was changed since.
+    fn compare(name: &str, change: impl Fn(&Path)) -> (RestoreDiff, PathBuf) {
+        let root =
+            env::temp_dir().join(format!("rusty-backup-diff-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("index.html"), "content").unwrap();
+        fs::write(root.join("style.css"), "body {}").unwrap();
+        symlink("index.html", root.join("home.html")).unwrap();
+        fs::hard_link(root.join("index.html"), root.join("default.html")).unwrap();
+        mkfifo(&root.join("pipe"), Mode::from_bits_truncate(0o644)).unwrap();
+        for path in ["index.html", "style.css"] {
+            fs::set_permissions(root.join(path), fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        change(&root);
+
+        let mut tar = Builder::new(Vec::new());
+        append_file(&mut tar, "www/index.html", "content");
+        append_file(&mut tar, "www/style.css", "body {}");
+        append_file(&mut tar, "www/about.html", "about");
+        append_special(&mut tar, "www/home.html", EntryType::Symlink, "index.html");
+        append_special(
+            &mut tar,
+            "www/default.html",
+            EntryType::Link,
+            "www/index.html",
+        );
+        append_special(&mut tar, "www/pipe", EntryType::Fifo, "");
+        let content = tar.into_inner().unwrap();
+
+        let mut diff = RestoreDiff::default();
+        let mut archive = tar::Archive::new(content.as_slice());
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let entry_path = entry.path().unwrap().to_string_lossy().to_string();
+            let dst_path = root.join(entry_path.trim_start_matches("www/"));
+            diff.compare_entry(&mut entry, &dst_path).unwrap();
+        }
+        diff.find_deleted(
+            &root,
+            "www",
+            Path::new("/var/www"),
+            &RestoreOptions::default(),
+        );
+        (diff, root)
+    }
+
+    fn relative(root: &Path, paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_no_changes_of_the_unchanged_files() {
+        let (diff, root) = compare("unchanged", |_| {});
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(relative(&root, &diff.new), ["about.html"]);
+        assert!(diff.modified.is_empty());
+        assert!(diff.permissions.is_empty());
+        assert!(diff.deleted.is_empty());
+        assert_eq!(diff.unchanged, 5);
+    }
+
+    #[test]
+    fn finds_modified_files_by_size_and_content() {
+        let (diff, root) = compare("modified", |root| {
+            fs::write(root.join("index.html"), "changed").unwrap();
+            fs::write(root.join("style.css"), "body { margin: 0 }").unwrap();
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        // The hardlink shares the content of index.html, but is still a regular file.
+        assert_eq!(relative(&root, &diff.modified), ["index.html", "style.css"]);
+        assert_eq!(diff.unchanged, 3);
+    }
+
+    #[test]
+    fn finds_a_symlink_with_another_target() {
+        let (diff, root) = compare("symlink", |root| {
+            fs::remove_file(root.join("home.html")).unwrap();
+            symlink("style.css", root.join("home.html")).unwrap();
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(relative(&root, &diff.modified), ["home.html"]);
+    }
+
+    #[test]
+    fn finds_hardlinks_and_fifos_of_another_type() {
+        let (diff, root) = compare("types", |root| {
+            fs::remove_file(root.join("default.html")).unwrap();
+            fs::create_dir(root.join("default.html")).unwrap();
+            fs::remove_file(root.join("pipe")).unwrap();
+            fs::write(root.join("pipe"), "").unwrap();
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(relative(&root, &diff.modified), ["default.html", "pipe"]);
+    }
+
+    #[test]
+    fn finds_new_deleted_files_and_permission_changes() {
+        let (diff, root) = compare("deleted", |root| {
+            fs::remove_file(root.join("style.css")).unwrap();
+            fs::write(root.join("old.html"), "old").unwrap();
+            fs::set_permissions(root.join("index.html"), fs::Permissions::from_mode(0o600))
+                .unwrap();
+        });
+        fs::remove_dir_all(&root).unwrap();
+
+        let mut new = relative(&root, &diff.new);
+        new.sort();
+        assert_eq!(new, ["about.html", "style.css"]);
+        assert_eq!(relative(&root, &diff.deleted), ["old.html"]);
+        assert_eq!(diff.permissions.len(), 2);
+        assert_eq!(diff.permissions[0].1, 0o600);
+        assert_eq!(diff.permissions[0].2, 0o644);
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+
+/// How the owners of the archived files are restored.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OwnerMapping {
+    /// By the owner and group names, falling back to the archived ids, if they don't exist.
+    #[default]
+    Named,
+    /// By the archived user and group ids.
+    Numeric,
+    /// The restored files are owned by the restoring user.
+    None,
+}
+
+impl OwnerMapping {
+    pub fn parse(value: &str) -> Result<OwnerMapping, String> {
+        match value {
+            "named" => Ok(OwnerMapping::Named),
+            "numeric" => Ok(OwnerMapping::Numeric),
+            "none" => Ok(OwnerMapping::None),
+            owners => Err(format!("invalid owners value '{}'.", owners)),
+        }
+    }
+}
+
+/// Limits, which entries of an archive are restored and where the files are written to.
+#[derive(Clone, Debug, Default)]
+pub struct RestoreOptions {
+    /// Only entries matching one of these globs are restored, e.g. "/var/www/**/*.php".
+    pub includes: Vec<Pattern>,
+    /// Only these files or subtrees are restored, e.g. "/var/www/html".
+    pub paths: Vec<String>,
+    /// The directory, the files are restored into, instead of their original location.
+    pub target: Option<String>,
+    pub databases_only: bool,
+    /// The database, a dump is restored into, instead of dropping and re-importing the original
+    /// one; "{name}" is replaced by the original name, e.g. "{name}_restore_20261016".
+    pub database_name: Option<String>,
+    /// The server, the databases are restored to, e.g. a staging server.
+    pub database_host: Option<String>,
+    /// Only the changes are printed, which the restore would make, without restoring anything.
+    pub dry_run: bool,
+    pub files_only: bool,
+    pub owners: OwnerMapping,
+    /// The databases are replaced by the restored ones, only after a successful import.
+    pub swap_databases: bool,
+}
+
+impl RestoreOptions {
+    pub fn parse_include(value: &str) -> Result<Pattern, String> {
+        Pattern::new(value).map_err(|err| format!("invalid include pattern '{}': {}", value, err))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.databases_only && self.files_only {
+            return Err(String::from(
+                "--databases-only and --files-only can't be combined.",
+            ));
+        }
+
+        if let Some(database_name) = &self.database_name {
+            if self.swap_databases && database_name == "{name}" {
+                return Err(String::from(
+                    "--swap-databases needs a --database-name other than the original one.",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the databases are restored into new databases, instead of being dropped and
+    /// re-imported in place.
+    pub fn is_safe_database_restore(&self) -> bool {
+        self.database_name.is_some() || self.database_host.is_some() || self.swap_databases
+    }
+
+    /// Gets the name of the database, the dump of the database `name` is restored into; without
+    /// one, a new name is generated on swapping, otherwise the original name is kept.
+    pub fn build_database_name(&self, name: &str) -> Option<String> {
+        match &self.database_name {
+            Some(database_name) => Some(database_name.replace("{name}", name)),
+            None if self.swap_databases => None,
+            None => Some(name.to_string()),
+        }
+    }
+
+    /// Describes, what a restore would do with the database `name`, for the dry-run.
+    pub fn describe_database_restore(&self, name: &str) -> String {
+        if !self.is_safe_database_restore() {
+            return format!("{} (would be dropped and imported)", name);
+        }
+
+        let mut description = match self.build_database_name(name) {
+            Some(database_name) => format!("{} (would be imported into '{}'", name, database_name),
+            None => format!("{} (would be imported into a new database", name),
+        };
+        if let Some(database_host) = &self.database_host {
+            description.push_str(&format!(" on {}", database_host));
+        }
+        if self.swap_databases {
+            description.push_str(" and swapped in");
+        }
+        description.push(')');
+        description
+    }
+
+    /// Whether the given entry is restored. The globs and paths are matched against the original
+    /// path of a file (e.g. "/var/www/index.html") and its path in the archive
+    /// ("www/index.html"); for a database, against the name of its dump ("shop.sql").
+    pub fn matches(&self, entry_path: &str, original_path: Option<&str>) -> bool {
+        let candidates: Vec<&str> = [Some(entry_path), original_path]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let match_options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        if !self.includes.is_empty()
+            && !self.includes.iter().any(|include| {
+                candidates
+                    .iter()
+                    .any(|candidate| include.matches_with(candidate, match_options))
+            })
+        {
+            return false;
+        }
+
+        if !self.paths.is_empty()
+            && !self.paths.iter().any(|path| {
+                let path = Path::new(path);
+                candidates
+                    .iter()
+                    .any(|candidate| Path::new(candidate).starts_with(path))
+            })
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(includes: &[&str], paths: &[&str]) -> RestoreOptions {
+        RestoreOptions {
+            includes: includes
+                .iter()
+                .map(|include| RestoreOptions::parse_include(include).unwrap())
+                .collect(),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_everything_without_includes_and_paths() {
+        let options = options(&[], &[]);
+        assert!(options.matches("www/index.html", Some("/var/www/index.html")));
+        assert!(options.matches("shop.sql", None));
+    }
+
+    #[test]
+    fn matches_absolute_includes_against_the_original_path() {
+        let options = options(&["/var/www/**/*.php"], &[]);
+        assert!(options.matches("www/html/index.php", Some("/var/www/html/index.php")));
+        assert!(!options.matches("www/html/index.html", Some("/var/www/html/index.html")));
+        // Anchored at the root, the glob doesn't match the path in the archive.
+        assert!(!options.matches("www/html/index.php", None));
+    }
+
+    #[test]
+    fn matches_relative_includes_against_the_entry_path() {
+        let options = options(&["www/*.php"], &[]);
+        assert!(options.matches("www/index.php", Some("/var/www/index.php")));
+        // `*` doesn't cross directories and the glob isn't searched within the path.
+        assert!(!options.matches("www/html/index.php", Some("/var/www/html/index.php")));
+        assert!(!options.matches("backup/www/index.php", None));
+    }
+
+    #[test]
+    fn matches_any_of_the_includes() {
+        let options = options(&["*.sql", "www/**"], &[]);
+        assert!(options.matches("shop.sql", None));
+        assert!(options.matches("www/html/index.html", None));
+        assert!(!options.matches("etc/hosts", Some("/etc/hosts")));
+    }
+
+    #[test]
+    fn rejects_invalid_include_patterns() {
+        let why = RestoreOptions::parse_include("/var/www/[abc").unwrap_err();
+        assert!(why.starts_with("invalid include pattern '/var/www/[abc'"));
+        assert!(RestoreOptions::parse_include("/var/***/x").is_err());
+    }
+
+    #[test]
+    fn matches_paths_as_subtrees() {
+        let options = options(&[], &["/var/www/html"]);
+        assert!(options.matches("www/html", Some("/var/www/html")));
+        assert!(options.matches("www/html/index.html", Some("/var/www/html/index.html")));
+        // Only whole components match, not the prefix of a name.
+        assert!(!options.matches("www/html2/index.html", Some("/var/www/html2/index.html")));
+    }
+
+    #[test]
+    fn matches_includes_and_paths_together() {
+        let options = options(&["**/*.php"], &["www/html"]);
+        assert!(options.matches("www/html/index.php", None));
+        assert!(!options.matches("www/html/index.html", None));
+        assert!(!options.matches("www/admin/index.php", None));
+    }
+}
@@ -0,0 +1,240 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::configuration::archive::Archive;
+use crate::storage::ArchiveFile;
+
+/// Selects the archive file to restore; without any option, the newest file is selected.
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveSelection {
+    /// The name of the archive file, with or without its extension.
+    pub archive_name: Option<String>,
+    /// The newest file, which was modified at or before this time (UTC), is selected.
+    pub at: Option<NaiveDateTime>,
+    /// Only files, which were modified before this time (UTC), are selected.
+    pub before: Option<NaiveDateTime>,
+    /// Only files, which were modified after this time (UTC), are selected.
+    pub after: Option<NaiveDateTime>,
+}
+
+impl ArchiveSelection {
+    /// Parses a local date or date-time like "2024-03-01", "2024-03-01 18:30" or an RFC 3339
+    /// timestamp and returns it in UTC. A date without time means the end of that day, if
+    /// `end_of_day` is set, and its start otherwise.
+    pub fn parse_datetime(value: &str, end_of_day: bool) -> Result<NaiveDateTime, String> {
+        let value = value.trim();
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+            return Ok(datetime.naive_utc());
+        }
+
+        let local_datetime = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .or_else(|| {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                match end_of_day {
+                    true => date.and_hms_opt(23, 59, 59),
+                    false => date.and_hms_opt(0, 0, 0),
+                }
+            })
+            .ok_or_else(|| format!("invalid date-time value '{}'.", value))?;
+
+        match Local.from_local_datetime(&local_datetime).earliest() {
+            Some(datetime) => Ok(datetime.naive_utc()),
+            None => Err(format!("invalid local date-time value '{}'.", value)),
+        }
+    }
+
+    fn matches(&self, archive: &Archive, archive_file: &ArchiveFile) -> bool {
+        if let Some(archive_name) = &self.archive_name {
+            if archive_file.name != *archive_name
+                && archive.strip_file_extension(&archive_file.name) != *archive_name
+            {
+                return false;
+            }
+        }
+
+        // Files without a modification time can't be compared to a time bound.
+        let modified = match archive_file.modified {
+            Some(modified) => modified,
+            None => return self.is_unbounded(),
+        };
+        if self.at.is_some_and(|at| modified > at) {
+            return false;
+        }
+        if self.before.is_some_and(|before| modified >= before) {
+            return false;
+        }
+        if self.after.is_some_and(|after| modified <= after) {
+            return false;
+        }
+
+        true
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.at.is_none() && self.before.is_none() && self.after.is_none()
+    }
+
+    /// Gets the matching files of the given archive files, the most recently modified last;
+    /// files without a modification time come first, so they're selected last.
+    pub fn filter(&self, archive: &Archive, archive_files: Vec<ArchiveFile>) -> Vec<ArchiveFile> {
+        let mut archive_files: Vec<ArchiveFile> = archive_files
+            .into_iter()
+            .filter(|archive_file| self.matches(archive, archive_file))
+            .collect();
+        archive_files.sort_by(|a, b| match (a.modified, b.modified) {
+            (Some(a_modified), Some(b_modified)) => a_modified.cmp(&b_modified),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => a.name.cmp(&b.name),
+        });
+        archive_files
+    }
+
+    /// Gets the most recently modified file of the given archive files, which matches.
+    pub fn select(
+        &self,
+        archive: &Archive,
+        archive_files: Vec<ArchiveFile>,
+    ) -> Option<ArchiveFile> {
+        self.filter(archive, archive_files).pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::compression::Compression;
+
+    fn datetime(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn archive_file(name: &str, modified: Option<&str>) -> ArchiveFile {
+        ArchiveFile {
+            name: String::from(name),
+            size: 0,
+            modified: modified.map(datetime),
+        }
+    }
+
+    fn archive_files() -> Vec<ArchiveFile> {
+        vec![
+            archive_file("www_02.tar", Some("2024-03-02 12:00:00")),
+            archive_file("www_unknown.tar", None),
+            archive_file("www_01.tar", Some("2024-03-01 12:00:00")),
+            archive_file("www_03.tar", Some("2024-03-03 12:00:00")),
+        ]
+    }
+
+    fn archive() -> Archive {
+        let mut archive = Archive::new();
+        archive.compression = Compression::Tar;
+        archive
+    }
+
+    fn names(archive_files: Vec<ArchiveFile>) -> Vec<String> {
+        archive_files
+            .into_iter()
+            .map(|archive_file| archive_file.name)
+            .collect()
+    }
+
+    #[test]
+    fn selects_the_newest_file_by_default() {
+        let selection = ArchiveSelection::default();
+        let selected = selection.select(&archive(), archive_files()).unwrap();
+        assert_eq!(selected.name, "www_03.tar");
+    }
+
+    #[test]
+    fn keeps_files_without_time_when_unbounded() {
+        let selection = ArchiveSelection::default();
+        assert_eq!(
+            names(selection.filter(&archive(), archive_files())),
+            ["www_unknown.tar", "www_01.tar", "www_02.tar", "www_03.tar"]
+        );
+
+        let files = vec![archive_file("www_unknown.tar", None)];
+        let selected = selection.select(&archive(), files).unwrap();
+        assert_eq!(selected.name, "www_unknown.tar");
+    }
+
+    #[test]
+    fn selects_files_without_time_by_name() {
+        let selection = ArchiveSelection {
+            archive_name: Some(String::from("www_unknown")),
+            ..Default::default()
+        };
+        let selected = selection.select(&archive(), archive_files()).unwrap();
+        assert_eq!(selected.name, "www_unknown.tar");
+    }
+
+    #[test]
+    fn at_includes_its_time() {
+        let selection = ArchiveSelection {
+            at: Some(datetime("2024-03-02 12:00:00")),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(selection.filter(&archive(), archive_files())),
+            ["www_01.tar", "www_02.tar"]
+        );
+
+        let selection = ArchiveSelection {
+            at: Some(datetime("2024-03-02 11:59:59")),
+            ..Default::default()
+        };
+        let selected = selection.select(&archive(), archive_files()).unwrap();
+        assert_eq!(selected.name, "www_01.tar");
+    }
+
+    #[test]
+    fn before_excludes_its_time() {
+        let selection = ArchiveSelection {
+            before: Some(datetime("2024-03-02 12:00:00")),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(selection.filter(&archive(), archive_files())),
+            ["www_01.tar"]
+        );
+    }
+
+    #[test]
+    fn after_excludes_its_time() {
+        let selection = ArchiveSelection {
+            after: Some(datetime("2024-03-02 12:00:00")),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(selection.filter(&archive(), archive_files())),
+            ["www_03.tar"]
+        );
+    }
+
+    #[test]
+    fn after_and_before_select_a_range() {
+        let selection = ArchiveSelection {
+            after: Some(datetime("2024-03-01 12:00:00")),
+            before: Some(datetime("2024-03-03 12:00:00")),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(selection.filter(&archive(), archive_files())),
+            ["www_02.tar"]
+        );
+    }
+
+    #[test]
+    fn bounds_exclude_files_without_time() {
+        let selection = ArchiveSelection {
+            archive_name: Some(String::from("www_unknown")),
+            before: Some(datetime("2024-03-04 00:00:00")),
+            ..Default::default()
+        };
+        assert!(selection.select(&archive(), archive_files()).is_none());
+    }
+}
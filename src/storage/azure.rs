@@ -0,0 +1,524 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use sha2::Sha256;
+use xml::reader::{EventReader, XmlEvent as XmlReaderEvent};
+
+use crate::configuration::destination::Destination;
+use crate::storage::{self, ArchiveFile, ByteStream, Storage};
+
+/// Characters, which are kept as they are in a blob name of an url.
+const BLOB_NAME: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+const API_VERSION: &str = "2021-08-06";
+
+/// The size of the blocks, which are staged before the block list is committed.
+const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+pub struct AzureStorage {
+    client: Client,
+    account: String,
+    account_key: Option<Vec<u8>>,
+    sas_token: Option<String>,
+    container_url: String,
+    prefix: String,
+}
+
+impl AzureStorage {
+    pub fn new(destination: &Destination) -> Result<AzureStorage, String> {
+        if destination.account.is_empty() || destination.container.is_empty() {
+            return Err(String::from(
+                "the destination-account and -container must be set for kind: azure",
+            ));
+        }
+
+        let account_key = match destination.account_key.is_empty() {
+            true => None,
+            false => Some(
+                BASE64
+                    .decode(&destination.account_key)
+                    .map_err(Self::map_error)?,
+            ),
+        };
+        let sas_token = match destination.sas_token.is_empty() {
+            true => None,
+            false => Some(destination.sas_token.trim_start_matches('?').to_string()),
+        };
+        let endpoint = match destination.endpoint.is_empty() {
+            true => format!("https://{}.blob.core.windows.net", destination.account),
+            false => destination.endpoint.trim_end_matches('/').to_string(),
+        };
+        let mut prefix = destination.path.trim_matches('/').to_string();
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+
+        Ok(AzureStorage {
+            client: Client::builder().build().map_err(Self::map_error)?,
+            account: destination.account.clone(),
+            account_key,
+            sas_token,
+            container_url: format!("{}/{}", endpoint, destination.container),
+            prefix,
+        })
+    }
+
+    fn build_blob_url(&self, file_name: &str, query: &str) -> Result<Url, String> {
+        let blob_name = format!("{}{}", self.prefix, file_name);
+        let mut url = format!(
+            "{}/{}",
+            self.container_url,
+            utf8_percent_encode(&blob_name, BLOB_NAME)
+        );
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(query);
+        }
+
+        self.append_sas_token(url)
+    }
+
+    /// Builds the url of a "List Blobs" request for the page starting at the marker.
+    fn build_list_url(&self, prefix: &str, marker: &str) -> Result<Url, String> {
+        let mut url = format!("{}?restype=container&comp=list", self.container_url);
+        if !prefix.is_empty() {
+            url.push_str("&prefix=");
+            url.push_str(&utf8_percent_encode(prefix, NON_ALPHANUMERIC).to_string());
+        }
+        if !marker.is_empty() {
+            url.push_str("&marker=");
+            url.push_str(&utf8_percent_encode(marker, NON_ALPHANUMERIC).to_string());
+        }
+
+        self.append_sas_token(url)
+    }
+
+    fn append_sas_token(&self, mut url: String) -> Result<Url, String> {
+        if let Some(sas_token) = &self.sas_token {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str(sas_token);
+        }
+
+        Url::parse(&url).map_err(Self::map_error)
+    }
+
+    /// Builds the "SharedKey" authorization header value for a request.
+    fn sign(
+        &self,
+        account_key: &[u8],
+        method: &Method,
+        url: &Url,
+        date: &str,
+        content_length: usize,
+    ) -> Result<String, String> {
+        let content_length = match content_length {
+            0 => String::new(),
+            length => length.to_string(),
+        };
+        let mut string_to_sign = format!(
+            "{}\n\n\n{}\n\n\n\n\n\n\n\n\nx-ms-date:{}\nx-ms-version:{}\n/{}{}",
+            method,
+            content_length,
+            date,
+            API_VERSION,
+            self.account,
+            url.path()
+        );
+
+        let mut query_pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (name.to_lowercase(), value.to_string()))
+            .collect();
+        query_pairs.sort();
+        for (name, value) in query_pairs {
+            string_to_sign.push_str(format!("\n{}:{}", name, value).as_str());
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(account_key).map_err(Self::map_error)?;
+        mac.update(string_to_sign.as_bytes());
+        let signature = BASE64.encode(mac.finalize().into_bytes());
+
+        Ok(format!("SharedKey {}:{}", self.account, signature))
+    }
+
+    async fn send(&self, method: Method, url: Url, body: Vec<u8>) -> Result<Response, String> {
+        let response = self.send_unchecked(method, url, body).await?;
+        Self::check_response(response).await
+    }
+
+    async fn send_unchecked(
+        &self,
+        method: Method,
+        url: Url,
+        body: Vec<u8>,
+    ) -> Result<Response, String> {
+        let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let mut request = self
+            .client
+            .request(method.clone(), url.clone())
+            .header("x-ms-date", &date)
+            .header("x-ms-version", API_VERSION);
+        if let Some(account_key) = &self.account_key {
+            let authorization = self.sign(account_key, &method, &url, &date, body.len())?;
+            request = request.header("Authorization", authorization);
+        }
+        if method == Method::PUT {
+            request = request.header("Content-Length", body.len()).body(body);
+        }
+
+        request.send().await.map_err(Self::map_error)
+    }
+
+    async fn check_response(response: Response) -> Result<Response, String> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let url = response.url().clone();
+        let message = response.text().await.unwrap_or_default();
+        Err(format!(
+            "request to {} failed with status: {} {}",
+            url.path(),
+            status,
+            message
+        ))
+    }
+
+    /// Parses a "List Blobs" response and returns the marker of the next page.
+    fn parse_blob_list(
+        &self,
+        xml: &str,
+        archive_files: &mut Vec<ArchiveFile>,
+    ) -> Result<String, String> {
+        let mut current_element = String::new();
+        let mut next_marker = String::new();
+        let mut name = String::new();
+        let mut size = 0;
+        let mut modified = None;
+
+        for e in EventReader::new(xml.as_bytes()) {
+            match e {
+                Ok(XmlReaderEvent::StartElement { name: element, .. }) => {
+                    if element.local_name == "Blob" {
+                        name = String::new();
+                        size = 0;
+                        modified = None;
+                    }
+                    current_element = element.local_name;
+                }
+                Ok(XmlReaderEvent::Characters(text)) => match current_element.as_str() {
+                    "Name" => {
+                        name.push_str(&text);
+                    }
+                    "Content-Length" => {
+                        size = text.trim().parse().unwrap_or_default();
+                    }
+                    "Last-Modified" => {
+                        modified = DateTime::parse_from_rfc2822(text.trim())
+                            .ok()
+                            .map(|date| date.naive_utc());
+                    }
+                    "NextMarker" => {
+                        next_marker.push_str(&text);
+                    }
+                    _ => {}
+                },
+                Ok(XmlReaderEvent::EndElement { name: element }) => {
+                    current_element = String::new();
+                    if element.local_name != "Blob" {
+                        continue;
+                    }
+                    let file_name = name.strip_prefix(&self.prefix).unwrap_or(&name);
+                    if file_name.is_empty() || file_name.contains('/') {
+                        continue;
+                    }
+                    archive_files.push(ArchiveFile {
+                        name: file_name.to_string(),
+                        size,
+                        modified,
+                    });
+                }
+                Err(err) => {
+                    return Err(format!("XML-Error: {:?}", err));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(next_marker)
+    }
+
+    fn map_error<E: std::fmt::Debug>(err: E) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for AzureStorage {
+    /// Uploads the stream as block blob: every block is staged first, then the block list is
+    /// committed, so the blob only becomes visible after the upload is complete.
+    async fn put(&self, file_name: &str, mut body: ByteStream, _length: u64) -> Result<(), String> {
+        let mut buffer = Vec::new();
+        let mut block_ids = Vec::new();
+        loop {
+            let block = storage::read_chunk(&mut body, &mut buffer, BLOCK_SIZE).await?;
+            if block.is_empty() {
+                break;
+            }
+
+            let block_id = BASE64.encode(format!("{:08}", block_ids.len()));
+            let query = format!(
+                "comp=block&blockid={}",
+                utf8_percent_encode(&block_id, NON_ALPHANUMERIC)
+            );
+            let is_last_block = block.len() < BLOCK_SIZE;
+            self.send(Method::PUT, self.build_blob_url(file_name, &query)?, block)
+                .await?;
+            block_ids.push(block_id);
+            if is_last_block {
+                break;
+            }
+        }
+
+        let mut block_list = String::from(r#"<?xml version="1.0" encoding="utf-8"?><BlockList>"#);
+        for block_id in block_ids {
+            block_list.push_str(format!("<Latest>{}</Latest>", block_id).as_str());
+        }
+        block_list.push_str("</BlockList>");
+        self.send(
+            Method::PUT,
+            self.build_blob_url(file_name, "comp=blocklist")?,
+            block_list.into_bytes(),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let mut archive_files = Vec::new();
+        let mut marker = String::new();
+        let prefix = format!("{}{}", self.prefix, prefix.unwrap_or_default());
+        loop {
+            let response = self
+                .send(
+                    Method::GET,
+                    self.build_list_url(&prefix, &marker)?,
+                    Vec::new(),
+                )
+                .await?;
+            let xml = response.text().await.map_err(Self::map_error)?;
+            marker = self.parse_blob_list(&xml, &mut archive_files)?;
+            if marker.is_empty() {
+                break;
+            }
+        }
+
+        Ok(archive_files)
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let response = self
+            .send(Method::GET, self.build_blob_url(file_name, "")?, Vec::new())
+            .await?;
+
+        let length = response.content_length();
+        Ok((
+            Box::pin(response.bytes_stream().map_err(Self::map_error)),
+            length,
+        ))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        let response = self
+            .send_unchecked(
+                Method::DELETE,
+                self.build_blob_url(file_name, "")?,
+                Vec::new(),
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check_response(response).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATE: &str = "Fri, 01 Mar 2024 10:00:00 GMT";
+
+    fn storage(path: &str, account_key: &str, sas_token: &str) -> AzureStorage {
+        let mut destination = Destination::new();
+        destination.account = String::from("account");
+        destination.account_key = String::from(account_key);
+        destination.sas_token = String::from(sas_token);
+        destination.container = String::from("container");
+        destination.path = String::from(path);
+        AzureStorage::new(&destination).unwrap()
+    }
+
+    #[test]
+    fn requires_an_account_and_a_container() {
+        let mut destination = Destination::new();
+        destination.account = String::from("account");
+        assert!(AzureStorage::new(&destination).is_err());
+    }
+
+    #[test]
+    fn builds_blob_urls() {
+        let storage = storage("/backups/", "", "");
+        assert_eq!(
+            storage.build_blob_url("www data.tar", "").unwrap().as_str(),
+            "https://account.blob.core.windows.net/container/backups/www%20data.tar"
+        );
+        assert_eq!(
+            storage
+                .build_blob_url("www.tar", "comp=blocklist")
+                .unwrap()
+                .as_str(),
+            "https://account.blob.core.windows.net/container/backups/www.tar?comp=blocklist"
+        );
+    }
+
+    #[test]
+    fn builds_urls_with_a_custom_endpoint() {
+        let mut destination = Destination::new();
+        destination.account = String::from("devstoreaccount1");
+        destination.container = String::from("container");
+        destination.endpoint = String::from("http://127.0.0.1:10000/devstoreaccount1/");
+        let storage = AzureStorage::new(&destination).unwrap();
+        assert_eq!(
+            storage.build_blob_url("www.tar", "").unwrap().as_str(),
+            "http://127.0.0.1:10000/devstoreaccount1/container/www.tar"
+        );
+    }
+
+    #[test]
+    fn appends_the_sas_token() {
+        let storage = storage("", "", "?sv=2021-08-06&sig=abc");
+        assert_eq!(
+            storage.build_blob_url("www.tar", "").unwrap().as_str(),
+            "https://account.blob.core.windows.net/container/www.tar?sv=2021-08-06&sig=abc"
+        );
+        assert_eq!(
+            storage
+                .build_blob_url("www.tar", "comp=blocklist")
+                .unwrap()
+                .as_str(),
+            "https://account.blob.core.windows.net/container/www.tar?comp=blocklist&sv=2021-08-06&sig=abc"
+        );
+    }
+
+    #[test]
+    fn builds_list_urls() {
+        let storage = storage("backups", "", "");
+        assert_eq!(
+            storage.build_list_url("backups/www_", "").unwrap().as_str(),
+            "https://account.blob.core.windows.net/container?restype=container&comp=list&prefix=backups%2Fwww%5F"
+        );
+        assert_eq!(
+            storage.build_list_url("", "2!8!MDAw").unwrap().as_str(),
+            "https://account.blob.core.windows.net/container?restype=container&comp=list&marker=2%218%21MDAw"
+        );
+    }
+
+    #[test]
+    fn signs_requests_with_the_shared_key() {
+        // The signatures were computed with the string-to-sign of the Azure documentation.
+        let storage = storage("backups", "c2VjcmV0LWtleQ==", "");
+        let account_key = storage.account_key.clone().unwrap();
+
+        let url = storage
+            .build_blob_url("www data.tar", "comp=block&blockid=AAAA")
+            .unwrap();
+        assert_eq!(
+            storage
+                .sign(&account_key, &Method::PUT, &url, DATE, 5)
+                .unwrap(),
+            "SharedKey account:AaxvJAdyP2XYXJ0MrOPjZBBfolwrK2ouLiYM+Ivks9Y="
+        );
+
+        let url = storage.build_list_url("", "").unwrap();
+        assert_eq!(
+            storage
+                .sign(&account_key, &Method::GET, &url, DATE, 0)
+                .unwrap(),
+            "SharedKey account:tW18krUTFEG0ddW0xk76ZmBinaNVDDphhVxwS5fwE0k="
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_account_keys() {
+        let mut destination = Destination::new();
+        destination.account = String::from("account");
+        destination.container = String::from("container");
+        destination.account_key = String::from("no base64!");
+        assert!(AzureStorage::new(&destination).is_err());
+    }
+
+    #[test]
+    fn parses_blob_lists() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<EnumerationResults ServiceEndpoint="https://account.blob.core.windows.net/" ContainerName="container">
+	<Blobs>
+		<Blob>
+			<Name>backups/www_01.tar.bz2</Name>
+			<Properties>
+				<Last-Modified>Fri, 01 Mar 2024 18:30:00 GMT</Last-Modified>
+				<Content-Length>1024</Content-Length>
+			</Properties>
+		</Blob>
+		<Blob>
+			<Name>backups/old/www_00.tar.bz2</Name>
+			<Properties><Content-Length>512</Content-Length></Properties>
+		</Blob>
+		<Blob>
+			<Name>backups/www_02.tar.bz2</Name>
+			<Properties><Content-Length>2048</Content-Length></Properties>
+		</Blob>
+	</Blobs>
+	<NextMarker>2!8!MDAw</NextMarker>
+</EnumerationResults>"#;
+
+        let mut archive_files = Vec::new();
+        let next_marker = storage("backups", "", "")
+            .parse_blob_list(xml, &mut archive_files)
+            .unwrap();
+        assert_eq!(next_marker, "2!8!MDAw");
+        assert_eq!(archive_files.len(), 2);
+        assert_eq!(archive_files[0].name, "www_01.tar.bz2");
+        assert_eq!(archive_files[0].size, 1024);
+        assert_eq!(
+            archive_files[0].modified.unwrap().to_string(),
+            "2024-03-01 18:30:00"
+        );
+        assert_eq!(archive_files[1].name, "www_02.tar.bz2");
+        assert!(archive_files[1].modified.is_none());
+    }
+
+    #[test]
+    fn parses_the_last_page_of_a_blob_list() {
+        let xml = r#"<EnumerationResults><Blobs/><NextMarker/></EnumerationResults>"#;
+        let mut archive_files = Vec::new();
+        let next_marker = storage("", "", "")
+            .parse_blob_list(xml, &mut archive_files)
+            .unwrap();
+        assert!(next_marker.is_empty());
+        assert!(archive_files.is_empty());
+    }
+}
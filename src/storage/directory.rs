@@ -0,0 +1,158 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use log::info;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+use crate::configuration::{destination::Destination, Configuration};
+use crate::storage::throttle::UploadThrottle;
+use crate::storage::{self, ArchiveFile, ByteStream, Storage};
+
+pub struct DirectoryStorage {
+    path: String,
+}
+
+impl DirectoryStorage {
+    pub fn new(destination: &Destination) -> DirectoryStorage {
+        DirectoryStorage {
+            path: destination.path.clone(),
+        }
+    }
+
+    fn build_file_path(&self, file_name: &str) -> String {
+        format!("{}/{}", self.path, file_name)
+    }
+
+    fn build_archive_file(file_name: String, metadata: fs::Metadata) -> ArchiveFile {
+        let modified = metadata
+            .modified()
+            .ok()
+            .map(|modified| DateTime::<Utc>::from(modified).naive_utc());
+
+        ArchiveFile {
+            name: file_name,
+            size: metadata.len(),
+            modified,
+        }
+    }
+
+    fn create_directory(&self) -> Result<(), String> {
+        if fs::create_dir_all(&self.path).is_err() {
+            return Err(format!("unable to create archive_path: '{}/'", self.path));
+        }
+
+        Ok(())
+    }
+
+    fn map_error(err: std::io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for DirectoryStorage {
+    async fn put(&self, file_name: &str, mut body: ByteStream, _length: u64) -> Result<(), String> {
+        self.create_directory()?;
+
+        let mut file = tokio::fs::File::create(self.build_file_path(file_name))
+            .await
+            .map_err(Self::map_error)?;
+        while let Some(chunk) = body.next().await {
+            file.write_all(&chunk?).await.map_err(Self::map_error)?;
+        }
+        file.flush().await.map_err(Self::map_error)
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let mut archive_files = Vec::new();
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(archive_files),
+            Err(err) => return Err(Self::map_error(err)),
+        };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(prefix) = prefix {
+                if !file_name.starts_with(prefix) {
+                    continue;
+                }
+            }
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_file() => {
+                    archive_files.push(Self::build_archive_file(file_name, metadata));
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(archive_files)
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let file = tokio::fs::File::open(self.build_file_path(file_name))
+            .await
+            .map_err(Self::map_error)?;
+        let length = file.metadata().await.map_err(Self::map_error)?.len();
+        let stream =
+            ReaderStream::with_capacity(file, Configuration::BUFFER_SIZE).map_err(Self::map_error);
+
+        Ok((Box::pin(stream), Some(length)))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        match fs::remove_file(self.build_file_path(file_name)) {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Self::map_error(err)),
+        }
+    }
+
+    async fn stat(&self, file_name: &str) -> Result<Option<ArchiveFile>, String> {
+        match fs::metadata(self.build_file_path(file_name)) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(Self::build_archive_file(
+                file_name.to_string(),
+                metadata,
+            ))),
+            Ok(_) => Ok(None),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Self::map_error(err)),
+        }
+    }
+
+    /// Renames the file into the directory and falls back to copying it, if the directory is
+    /// located on another file system; only the copy is throttled.
+    async fn put_file_throttled(
+        &self,
+        file_name: &str,
+        throttle: Option<&UploadThrottle>,
+    ) -> Result<(), String> {
+        self.create_directory()?;
+
+        let new_archive_name = self.build_file_path(file_name);
+        match (fs::rename(file_name, &new_archive_name), throttle) {
+            (Ok(_), _) => {}
+            (Err(_rename_err), Some(throttle)) => {
+                let (body, length) = storage::open_file_stream(file_name).await?;
+                self.put(file_name, throttle.apply(body), length).await?;
+                fs::remove_file(file_name).unwrap_or_default();
+            }
+            (Err(_rename_err), None) => match fs::copy(file_name, &new_archive_name) {
+                Ok(_) => fs::remove_file(file_name).unwrap_or_default(),
+                Err(copy_err) => {
+                    return Err(format!(
+                        "unable to rename and copy '{}' to '{}'! error: {:?}",
+                        file_name, new_archive_name, copy_err
+                    ));
+                }
+            },
+        }
+        info!("file moved: {} => {}", file_name, new_archive_name);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    io::{Read, Write},
+    net::ToSocketAddrs,
+    sync::{Mutex, MutexGuard},
+    task::Poll,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use log::warn;
+use suppaftp::{
+    list::{File as ListFile, ListParser},
+    native_tls::TlsConnector,
+    types::FileType,
+    FtpError, Mode, NativeTlsConnector, NativeTlsFtpStream, Status,
+};
+
+use crate::configuration::destination::{Destination, FtpTls};
+use crate::configuration::Configuration;
+use crate::storage::{ArchiveFile, ByteStream, Storage};
+
+pub struct FtpStorage {
+    stream: Mutex<NativeTlsFtpStream>,
+}
+
+impl FtpStorage {
+    /// Connects and logs in to the FTP server of the destination and changes into its path.
+    pub fn connect(destination: &Destination) -> Result<FtpStorage, String> {
+        let port = match destination.port {
+            Some(port) => port,
+            None if destination.ftp_tls == FtpTls::Implicit => 990,
+            None => 21,
+        };
+        let addr = format!("{}:{}", destination.server, port);
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(Self::map_error)?
+            .next()
+            .ok_or_else(|| format!("unable to resolve ftp server: {}", addr))?;
+
+        let mut stream = match destination.ftp_tls {
+            FtpTls::None => NativeTlsFtpStream::connect(addr).map_err(Self::map_error)?,
+            FtpTls::Explicit => NativeTlsFtpStream::connect(addr)
+                .map_err(Self::map_error)?
+                .into_secure(Self::build_tls_connector()?, &destination.server)
+                .map_err(Self::map_error)?,
+            FtpTls::Implicit => NativeTlsFtpStream::connect_secure_implicit(
+                addr,
+                Self::build_tls_connector()?,
+                &destination.server,
+            )
+            .map_err(Self::map_error)?,
+        };
+        if destination.ftp_passive {
+            stream.set_mode(Mode::Passive);
+        } else {
+            stream = stream.active_mode(Duration::from_secs(60));
+        }
+
+        stream
+            .login(destination.username.as_str(), destination.password.as_str())
+            .map_err(Self::map_error)?;
+        stream
+            .transfer_type(FileType::Binary)
+            .map_err(Self::map_error)?;
+
+        Self::change_directory(&mut stream, &destination.path)?;
+
+        Ok(FtpStorage {
+            stream: Mutex::new(stream),
+        })
+    }
+
+    fn build_tls_connector() -> Result<NativeTlsConnector, String> {
+        let tls_connector = TlsConnector::new().map_err(Self::map_error)?;
+        Ok(NativeTlsConnector::from(tls_connector))
+    }
+
+    /// Changes into the given path and creates the missing directories on the way.
+    fn change_directory(stream: &mut NativeTlsFtpStream, path: &str) -> Result<(), String> {
+        if path.starts_with('/') {
+            stream.cwd("/").map_err(Self::map_error)?;
+        }
+
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if stream.cwd(segment).is_ok() {
+                continue;
+            }
+            stream.mkdir(segment).map_err(Self::map_error)?;
+            stream.cwd(segment).map_err(Self::map_error)?;
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, NativeTlsFtpStream>, String> {
+        self.stream
+            .lock()
+            .map_err(|_| String::from("the ftp connection is poisoned."))
+    }
+
+    fn map_error<E: std::fmt::Debug>(err: E) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for FtpStorage {
+    async fn put(&self, file_name: &str, mut body: ByteStream, _length: u64) -> Result<(), String> {
+        let mut transfer_stream = self
+            .lock()?
+            .put_with_stream(file_name)
+            .map_err(Self::map_error)?;
+        while let Some(chunk) = body.next().await {
+            transfer_stream
+                .write_all(&chunk?)
+                .map_err(Self::map_error)?;
+        }
+
+        transfer_stream.finish().map_err(Self::map_error)
+    }
+
+    /// Lists the files in the current directory, using MLSD with a fallback to LIST.
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let mut stream = self.lock()?;
+        let files: Vec<ListFile> = match stream.mlsd(None) {
+            Ok(lines) => lines
+                .iter()
+                .filter_map(|line| ListParser::parse_mlsd(line).ok())
+                .collect(),
+            Err(err) => {
+                warn!("MLSD failed ({}), falling back to LIST.", err);
+                stream
+                    .list(None)
+                    .map_err(Self::map_error)?
+                    .iter()
+                    .filter_map(|line| {
+                        ListParser::parse_posix(line)
+                            .or_else(|_| ListParser::parse_dos(line))
+                            .ok()
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(files
+            .into_iter()
+            .filter(|file| file.is_file())
+            .filter(|file| match prefix {
+                Some(prefix) => file.name().starts_with(prefix),
+                None => true,
+            })
+            .map(|file| ArchiveFile {
+                name: file.name().to_string(),
+                size: file.size() as u64,
+                modified: Some(DateTime::<Utc>::from(file.modified()).naive_utc()),
+            })
+            .collect())
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let mut stream = self.lock()?;
+        let length = stream.size(file_name).ok().map(|size| size as u64);
+        let mut transfer_stream = Some(stream.retr_as_stream(file_name).map_err(Self::map_error)?);
+
+        let body = futures::stream::poll_fn(move |_| {
+            let mut current_transfer_stream = match transfer_stream.take() {
+                Some(current_transfer_stream) => current_transfer_stream,
+                None => return Poll::Ready(None),
+            };
+            let mut buf = vec![0; Configuration::BUFFER_SIZE];
+            match current_transfer_stream.read(&mut buf) {
+                Ok(0) => match current_transfer_stream.finish() {
+                    Ok(_) => Poll::Ready(None),
+                    Err(err) => Poll::Ready(Some(Err(Self::map_error(err)))),
+                },
+                Ok(read_bytes) => {
+                    buf.truncate(read_bytes);
+                    transfer_stream = Some(current_transfer_stream);
+                    Poll::Ready(Some(Ok(Bytes::from(buf))))
+                }
+                Err(err) => Poll::Ready(Some(Err(Self::map_error(err)))),
+            }
+        });
+
+        Ok((Box::pin(body), length))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        match self.lock()?.rm(file_name) {
+            Ok(_) => Ok(()),
+            Err(FtpError::UnexpectedResponse(response))
+                if response.status == Status::FileUnavailable =>
+            {
+                Ok(())
+            }
+            Err(err) => Err(Self::map_error(err)),
+        }
+    }
+}
+
+impl Drop for FtpStorage {
+    fn drop(&mut self) {
+        if let Ok(stream) = self.stream.get_mut() {
+            stream.quit().unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,443 @@
+use std::{env, fs};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::configuration::destination::Destination;
+use crate::storage::{self, ArchiveFile, ByteStream, Storage};
+
+/// Characters, which are kept as they are in an object name of an url.
+const OBJECT_NAME: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_write";
+
+/// The size of the chunks of a resumable upload; must be a multiple of 256 KiB.
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectList {
+    #[serde(default)]
+    items: Vec<Object>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Object {
+    name: String,
+    size: Option<String>,
+    updated: Option<String>,
+}
+
+pub struct GcsStorage {
+    client: Client,
+    access_token: Option<String>,
+    bucket: String,
+    endpoint: String,
+    prefix: String,
+}
+
+impl GcsStorage {
+    /// Creates a client, which is authorized with the service account key of the destination
+    /// (`credentials-file`, default: `GOOGLE_APPLICATION_CREDENTIALS`). Without any key, the
+    /// requests are sent unauthorized, e.g. to a local fake-gcs-server.
+    pub async fn new(destination: &Destination) -> Result<GcsStorage, String> {
+        if destination.bucket.is_empty() {
+            return Err(String::from(
+                "the destination-bucket must be set for kind: gcs",
+            ));
+        }
+
+        let client = Client::builder().build().map_err(Self::map_error)?;
+        let credentials_file = match destination.credentials_file.is_empty() {
+            true => env::var("GOOGLE_APPLICATION_CREDENTIALS").ok(),
+            false => Some(destination.credentials_file.clone()),
+        };
+        let access_token = match credentials_file {
+            Some(credentials_file) => {
+                Some(Self::fetch_access_token(&client, &credentials_file).await?)
+            }
+            None => None,
+        };
+        let endpoint = match destination.endpoint.is_empty() {
+            true => String::from("https://storage.googleapis.com"),
+            false => destination.endpoint.trim_end_matches('/').to_string(),
+        };
+        let mut prefix = destination.path.trim_matches('/').to_string();
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+
+        Ok(GcsStorage {
+            client,
+            access_token,
+            bucket: destination.bucket.clone(),
+            endpoint,
+            prefix,
+        })
+    }
+
+    /// Exchanges a self-signed JWT of the service account for an OAuth2 access token.
+    async fn fetch_access_token(client: &Client, credentials_file: &str) -> Result<String, String> {
+        let credentials = fs::read_to_string(credentials_file).map_err(Self::map_error)?;
+        let key: ServiceAccountKey = serde_json::from_str(&credentials).map_err(Self::map_error)?;
+
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            iss: &key.client_email,
+            scope: SCOPE,
+            aud: &key.token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let encoding_key =
+            EncodingKey::from_rsa_pem(key.private_key.as_bytes()).map_err(Self::map_error)?;
+        let assertion =
+            jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &encoding_key)
+                .map_err(Self::map_error)?;
+
+        let body = format!(
+            "grant_type={}&assertion={}",
+            utf8_percent_encode(
+                "urn:ietf:params:oauth:grant-type:jwt-bearer",
+                NON_ALPHANUMERIC
+            ),
+            assertion
+        );
+        let response = client
+            .post(&key.token_uri)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        let response = Self::check_response(response).await?;
+        let token: TokenResponse =
+            serde_json::from_str(&response.text().await.map_err(Self::map_error)?)
+                .map_err(Self::map_error)?;
+
+        Ok(token.access_token)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.access_token {
+            Some(access_token) => request.bearer_auth(access_token),
+            None => request,
+        }
+    }
+
+    fn build_object_url(&self, file_name: &str) -> String {
+        format!(
+            "{}/storage/v1/b/{}/o/{}",
+            self.endpoint,
+            utf8_percent_encode(&self.bucket, OBJECT_NAME),
+            utf8_percent_encode(&format!("{}{}", self.prefix, file_name), OBJECT_NAME)
+        )
+    }
+
+    /// Builds the url of an objects list request for the page of the token.
+    fn build_list_url(&self, prefix: &str, page_token: Option<&str>) -> String {
+        let mut url = format!(
+            "{}/storage/v1/b/{}/o?prefix={}",
+            self.endpoint,
+            utf8_percent_encode(&self.bucket, OBJECT_NAME),
+            utf8_percent_encode(prefix, OBJECT_NAME)
+        );
+        if let Some(page_token) = page_token {
+            url.push_str("&pageToken=");
+            url.push_str(&utf8_percent_encode(page_token, OBJECT_NAME).to_string());
+        }
+
+        url
+    }
+
+    /// Parses an objects list response and returns the token of the next page.
+    fn parse_object_list(
+        &self,
+        json: &str,
+        archive_files: &mut Vec<ArchiveFile>,
+    ) -> Result<Option<String>, String> {
+        let object_list: ObjectList = serde_json::from_str(json).map_err(Self::map_error)?;
+        for object in object_list.items {
+            let file_name = object
+                .name
+                .strip_prefix(&self.prefix)
+                .unwrap_or(&object.name);
+            if file_name.is_empty() || file_name.contains('/') {
+                continue;
+            }
+            archive_files.push(ArchiveFile {
+                name: file_name.to_string(),
+                size: object
+                    .size
+                    .and_then(|size| size.parse().ok())
+                    .unwrap_or_default(),
+                modified: object
+                    .updated
+                    .and_then(|updated| DateTime::parse_from_rfc3339(&updated).ok())
+                    .map(|updated| updated.naive_utc()),
+            });
+        }
+
+        Ok(object_list.next_page_token)
+    }
+
+    async fn check_response(response: Response) -> Result<Response, String> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let url = response.url().clone();
+        let message = response.text().await.unwrap_or_default();
+        Err(format!(
+            "request to {} failed with status: {} {}",
+            url.path(),
+            status,
+            message
+        ))
+    }
+
+    fn map_error<E: std::fmt::Debug>(err: E) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for GcsStorage {
+    /// Uploads the stream with a resumable upload session in chunks of `CHUNK_SIZE`.
+    async fn put(&self, file_name: &str, mut body: ByteStream, length: u64) -> Result<(), String> {
+        let url = format!(
+            "{}/upload/storage/v1/b/{}/o?uploadType=resumable&name={}",
+            self.endpoint,
+            utf8_percent_encode(&self.bucket, OBJECT_NAME),
+            utf8_percent_encode(&format!("{}{}", self.prefix, file_name), OBJECT_NAME)
+        );
+        let response = self
+            .authorize(self.client.post(url))
+            .header("X-Upload-Content-Length", length)
+            .header("Content-Length", 0)
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        let response = Self::check_response(response).await?;
+        let session_url = match response.headers().get("Location") {
+            Some(location) => location.to_str().map_err(Self::map_error)?.to_string(),
+            None => return Err(String::from("no upload session url returned.")),
+        };
+
+        let mut buffer = Vec::new();
+        let mut offset = 0_u64;
+        loop {
+            let chunk = storage::read_chunk(&mut body, &mut buffer, CHUNK_SIZE).await?;
+            let content_range = match chunk.len() {
+                0 => format!("bytes */{}", length),
+                chunk_length => format!(
+                    "bytes {}-{}/{}",
+                    offset,
+                    offset + chunk_length as u64 - 1,
+                    length
+                ),
+            };
+            offset += chunk.len() as u64;
+
+            let response = self
+                .authorize(self.client.put(&session_url))
+                .header("Content-Range", content_range)
+                .header("Content-Length", chunk.len())
+                .body(chunk)
+                .send()
+                .await
+                .map_err(Self::map_error)?;
+            // 308 (Permanent Redirect) is returned, as long as the upload is incomplete.
+            if response.status() == StatusCode::PERMANENT_REDIRECT && offset < length {
+                continue;
+            }
+            Self::check_response(response).await?;
+            break;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let mut archive_files = Vec::new();
+        let mut page_token: Option<String> = None;
+        let prefix = format!("{}{}", self.prefix, prefix.unwrap_or_default());
+        loop {
+            let url = self.build_list_url(&prefix, page_token.as_deref());
+            let response = self
+                .authorize(self.client.get(url))
+                .send()
+                .await
+                .map_err(Self::map_error)?;
+            let response = Self::check_response(response).await?;
+            let json = response.text().await.map_err(Self::map_error)?;
+            page_token = self.parse_object_list(&json, &mut archive_files)?;
+            if page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(archive_files)
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let url = format!("{}?alt=media", self.build_object_url(file_name));
+        let response = self
+            .authorize(self.client.get(url))
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        let response = Self::check_response(response).await?;
+
+        let length = response.content_length();
+        Ok((
+            Box::pin(response.bytes_stream().map_err(Self::map_error)),
+            length,
+        ))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        let response = self
+            .authorize(self.client.delete(self.build_object_url(file_name)))
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check_response(response).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage(path: &str) -> GcsStorage {
+        let mut prefix = String::from(path);
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        GcsStorage {
+            client: Client::new(),
+            access_token: None,
+            bucket: String::from("my-bucket"),
+            endpoint: String::from("https://storage.googleapis.com"),
+            prefix,
+        }
+    }
+
+    #[tokio::test]
+    async fn requires_a_bucket() {
+        assert!(GcsStorage::new(&Destination::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fails_on_a_missing_credentials_file() {
+        let mut destination = Destination::new();
+        destination.bucket = String::from("my-bucket");
+        destination.credentials_file = String::from("/nonexistent/gcs.json");
+        assert!(GcsStorage::new(&destination).await.is_err());
+    }
+
+    #[test]
+    fn builds_object_urls() {
+        assert_eq!(
+            storage("backups/daily").build_object_url("www data.tar"),
+            "https://storage.googleapis.com/storage/v1/b/my-bucket/o/backups%2Fdaily%2Fwww%20data.tar"
+        );
+        assert_eq!(
+            storage("").build_object_url("www.tar"),
+            "https://storage.googleapis.com/storage/v1/b/my-bucket/o/www.tar"
+        );
+    }
+
+    #[test]
+    fn builds_list_urls() {
+        let storage = storage("backups");
+        assert_eq!(
+            storage.build_list_url("backups/www_", None),
+            "https://storage.googleapis.com/storage/v1/b/my-bucket/o?prefix=backups%2Fwww_"
+        );
+        assert_eq!(
+            storage.build_list_url("backups/", Some("Cg4/d3d3+")),
+            "https://storage.googleapis.com/storage/v1/b/my-bucket/o?prefix=backups%2F&pageToken=Cg4%2Fd3d3%2B"
+        );
+    }
+
+    #[test]
+    fn parses_object_lists() {
+        let json = r#"{
+            "kind": "storage#objects",
+            "items": [
+                {"name": "backups/www_01.tar.bz2", "size": "1024", "updated": "2024-03-01T18:30:00.000Z"},
+                {"name": "backups/old/www_00.tar.bz2", "size": "512"},
+                {"name": "backups/www_02.tar.bz2"}
+            ],
+            "nextPageToken": "Cg4"
+        }"#;
+
+        let mut archive_files = Vec::new();
+        let page_token = storage("backups")
+            .parse_object_list(json, &mut archive_files)
+            .unwrap();
+        assert_eq!(page_token.as_deref(), Some("Cg4"));
+        assert_eq!(archive_files.len(), 2);
+        assert_eq!(archive_files[0].name, "www_01.tar.bz2");
+        assert_eq!(archive_files[0].size, 1024);
+        assert_eq!(
+            archive_files[0].modified.unwrap().to_string(),
+            "2024-03-01 18:30:00"
+        );
+        assert_eq!(archive_files[1].name, "www_02.tar.bz2");
+        assert_eq!(archive_files[1].size, 0);
+        assert!(archive_files[1].modified.is_none());
+    }
+
+    #[test]
+    fn parses_empty_object_lists() {
+        let mut archive_files = Vec::new();
+        let page_token = storage("")
+            .parse_object_list(r#"{"kind": "storage#objects"}"#, &mut archive_files)
+            .unwrap();
+        assert!(page_token.is_none());
+        assert!(archive_files.is_empty());
+        assert!(storage("")
+            .parse_object_list("<html>", &mut archive_files)
+            .is_err());
+    }
+}
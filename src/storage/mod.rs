@@ -0,0 +1,170 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+use futures::{Stream, StreamExt, TryStreamExt};
+use log::info;
+use tokio_util::io::ReaderStream;
+
+use crate::configuration::Configuration;
+use crate::helper::ProgressStats;
+use throttle::UploadThrottle;
+
+pub mod azure;
+pub mod directory;
+pub mod ftp;
+pub mod gcs;
+pub mod s3;
+pub mod ssh;
+pub mod throttle;
+pub mod webdav;
+
+/// A stream of bytes, which is uploaded to or downloaded from a storage.
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// An archive file, which is stored on a destination.
+#[derive(Clone, Debug)]
+pub struct ArchiveFile {
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<NaiveDateTime>,
+}
+
+/// The operations, every kind of destination has to provide. Backup, restore, pruning and
+/// synchronization are built on top of these, so a new kind only has to implement this trait.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Stores the stream of the given length as file with the given name.
+    async fn put(&self, file_name: &str, body: ByteStream, length: u64) -> Result<(), String>;
+
+    /// Lists all files, optionally limited to the names starting with the given prefix.
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String>;
+
+    /// Opens the file with the given name and returns its content with its length, if known.
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String>;
+
+    /// Deletes the file with the given name; a missing file is not an error.
+    async fn delete(&self, file_name: &str) -> Result<(), String>;
+
+    /// Gets the metadata of the file with the given name, if it exists.
+    async fn stat(&self, file_name: &str) -> Result<Option<ArchiveFile>, String> {
+        Ok(self
+            .list(Some(file_name))
+            .await?
+            .into_iter()
+            .find(|archive_file| archive_file.name == file_name))
+    }
+
+    /// Moves the file with the given name from the working directory to this storage.
+    async fn put_file(&self, file_name: &str) -> Result<(), String> {
+        self.put_file_throttled(file_name, None).await
+    }
+
+    /// Moves the file with the given name from the working directory to this storage, while the
+    /// upload is throttled, if a throttle is given.
+    async fn put_file_throttled(
+        &self,
+        file_name: &str,
+        throttle: Option<&UploadThrottle>,
+    ) -> Result<(), String> {
+        let (body, length) = open_file_stream(file_name).await?;
+        let body = match throttle {
+            Some(throttle) => throttle.apply(body),
+            None => body,
+        };
+        info!("uploading file: {}", file_name);
+        self.put(file_name, body, length).await?;
+        info!("file upload ok: {}", file_name);
+
+        fs::remove_file(file_name).map_err(map_error)
+    }
+
+    /// Downloads the file with the given name into the working directory.
+    async fn get_file(&self, file_name: &str) -> Result<(), String> {
+        let (body, length) = self.get(file_name).await?;
+        write_stream_to_file(body, length, file_name, None).await
+    }
+}
+
+/// Opens the given local file as stream and returns it with its length.
+pub async fn open_file_stream(file_name: &str) -> Result<(ByteStream, u64), String> {
+    let file = tokio::fs::File::open(file_name).await.map_err(map_error)?;
+    let length = file.metadata().await.map_err(map_error)?.len();
+    let stream = ReaderStream::with_capacity(file, Configuration::BUFFER_SIZE).map_err(map_error);
+
+    Ok((Box::pin(stream), length))
+}
+
+/// Adds the size of every chunk of the stream to the progress.
+pub fn track_progress(body: ByteStream, stats: Arc<Mutex<ProgressStats>>) -> ByteStream {
+    Box::pin(body.inspect_ok(move |chunk| {
+        if let Ok(mut stats) = stats.lock() {
+            stats.add_progressed_size(chunk.len());
+        }
+    }))
+}
+
+/// Writes the given stream into a file, while printing the download progress and the rate
+/// limit, which the stream is throttled to.
+pub async fn write_stream_to_file(
+    mut body: ByteStream,
+    length: Option<u64>,
+    file_name: &str,
+    rate_limit: Option<usize>,
+) -> Result<(), String> {
+    let mut download_stats = ProgressStats::new();
+    download_stats.total_length = length.map(|length| length as usize);
+    download_stats.rate_limit = rate_limit;
+    let arc_download_stats = Arc::new(Mutex::new(download_stats));
+    let cloned_arc_download_stats = Arc::clone(&arc_download_stats);
+    let thread = ProgressStats::spawn_output_thread(arc_download_stats, "downloading...");
+
+    let mut f = File::create(file_name).map_err(map_error)?;
+    let result = loop {
+        let chunk = match body.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(err)) => break Err(err),
+            None => break Ok(()),
+        };
+        if let Err(err) = f.write_all(&chunk) {
+            break Err(map_error(err));
+        }
+        if let Ok(mut download_stats) = cloned_arc_download_stats.lock() {
+            download_stats.add_progressed_size(chunk.len());
+        }
+    };
+
+    if let Ok(mut download_stats) = cloned_arc_download_stats.lock() {
+        download_stats.set_finished();
+    }
+    thread.join().unwrap_or_default();
+    println!();
+
+    result
+}
+
+/// Reads the next chunk of the given size from the stream; only the last chunk is smaller.
+/// The bytes, which were received beyond the chunk, are kept in the buffer for the next call.
+pub async fn read_chunk(
+    body: &mut ByteStream,
+    buffer: &mut Vec<u8>,
+    size: usize,
+) -> Result<Vec<u8>, String> {
+    while buffer.len() < size {
+        match body.next().await {
+            Some(chunk) => buffer.extend_from_slice(&chunk?),
+            None => break,
+        }
+    }
+
+    let rest = buffer.split_off(size.min(buffer.len()));
+    Ok(std::mem::replace(buffer, rest))
+}
+
+fn map_error<E: std::fmt::Debug>(err: E) -> String {
+    format!("error: {:?}", err)
+}
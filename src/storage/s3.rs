@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::{fs, io};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{NaiveDateTime, SecondsFormat, Utc};
+use futures::TryStreamExt;
+use hyper::client::HttpConnector;
+use hyper_tls::HttpsConnector;
+use log::{info, warn};
+use md5::{Digest, Md5};
+use native_tls::{Certificate, TlsConnector};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rusoto_core::request::TlsError;
+use rusoto_core::{Client, HttpClient, RusotoError};
+use rusoto_credential::{
+    AutoRefreshingProvider, ChainProvider, CredentialsError, ProfileProvider, StaticProvider,
+    Variable,
+};
+use rusoto_s3::{
+    DeleteObjectError, DeleteObjectRequest, GetObjectError, GetObjectRequest, GlacierJobParameters,
+    HeadObjectError, HeadObjectOutput, HeadObjectRequest, ListObjectsV2Error, ListObjectsV2Request,
+    ObjectLockRetention, PutObjectError, PutObjectRequest, PutObjectRetentionError,
+    PutObjectRetentionRequest, RestoreObjectError, RestoreObjectRequest, RestoreRequest, S3Client,
+    StreamingBody, S3,
+};
+
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient, WebIdentityProvider};
+
+use crate::configuration::destination::{Destination, LockRetention};
+use crate::storage::{ArchiveFile, ByteStream, Storage};
+
+/// Characters, which are kept as they are in the url-encoded tag set.
+const TAG_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// The interval, in which the state of a restore from Glacier is checked.
+const RESTORE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The name of the session, which is created on assuming a role.
+const ROLE_SESSION_NAME: &str = "rusty-backup";
+
+/// A customer provided key for SSE-C; it has to be sent with every request of an object.
+struct SseCustomerKey {
+    key: String,
+    key_md5: String,
+}
+
+pub struct S3Storage {
+    client: S3Client,
+    bucket: String,
+    metadata: HashMap<String, String>,
+    object_lock_mode: Option<String>,
+    object_lock_retention: Option<LockRetention>,
+    restore_days: i64,
+    restore_timeout: Duration,
+    restore_tier: String,
+    sse_customer_key: Option<SseCustomerKey>,
+    sse_kms_key_id: Option<String>,
+    storage_class: Option<String>,
+    tagging: Option<String>,
+}
+
+impl S3Storage {
+    pub fn new(destination: &Destination) -> Result<S3Storage, String> {
+        let sse_customer_key = match &destination.s3_sse_customer_key {
+            Some(key) => {
+                let raw_key = BASE64.decode(key).map_err(Self::map_base64_error)?;
+                Some(SseCustomerKey {
+                    key: key.clone(),
+                    key_md5: BASE64.encode(Md5::digest(raw_key)),
+                })
+            }
+            None => None,
+        };
+        let tagging = match destination.s3_tags.is_empty() {
+            true => None,
+            false => Some(
+                destination
+                    .s3_tags
+                    .iter()
+                    .map(|(name, value)| {
+                        format!(
+                            "{}={}",
+                            utf8_percent_encode(name, TAG_COMPONENT),
+                            utf8_percent_encode(value, TAG_COMPONENT)
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("&"),
+            ),
+        };
+
+        Ok(S3Storage {
+            client: S3Client::new_with_client(
+                Self::build_client(destination)?,
+                destination.s3_region.clone(),
+            ),
+            bucket: destination.bucket.clone(),
+            metadata: destination.s3_metadata.clone(),
+            object_lock_mode: destination.s3_object_lock_mode.clone(),
+            object_lock_retention: destination.s3_object_lock_retention.clone(),
+            restore_days: destination.s3_restore_days,
+            restore_timeout: destination.s3_restore_timeout,
+            restore_tier: destination.s3_restore_tier.clone(),
+            sse_customer_key,
+            sse_kms_key_id: destination.s3_sse_kms_key_id.clone(),
+            storage_class: destination.s3_storage_class.clone(),
+            tagging,
+        })
+    }
+
+    /// Builds the http client, which trusts the CA of the `ca-file` in addition to the system's
+    /// roots; with `insecure-skip-verify`, any certificate is accepted (for testing only).
+    fn build_http_client(destination: &Destination) -> Result<HttpClient, String> {
+        if destination.s3_ca_file.is_none() && !destination.s3_insecure_skip_verify {
+            return HttpClient::new().map_err(Self::map_tls_error);
+        }
+
+        let mut tls_connector_builder = TlsConnector::builder();
+        if let Some(ca_file) = &destination.s3_ca_file {
+            let pem = fs::read(ca_file).map_err(Self::map_error)?;
+            let certificate = Certificate::from_pem(&pem).map_err(Self::map_native_tls_error)?;
+            tls_connector_builder.add_root_certificate(certificate);
+        }
+        if destination.s3_insecure_skip_verify {
+            warn!(
+                "the certificate of the destination '{}' is not verified.",
+                destination.id
+            );
+            tls_connector_builder.danger_accept_invalid_certs(true);
+        }
+        let tls_connector = tls_connector_builder
+            .build()
+            .map_err(Self::map_native_tls_error)?;
+
+        let mut http_connector = HttpConnector::new();
+        http_connector.enforce_http(false);
+        let https_connector = HttpsConnector::from((http_connector, tls_connector.into()));
+
+        Ok(HttpClient::from_connector(https_connector))
+    }
+
+    /// Builds a client with the credentials of the destination: a web identity token, the static
+    /// `access-key`/`secret-key` or a `profile`, falling back to the default chain (environment,
+    /// profile, container and instance metadata). With a `role-arn`, the role is assumed with
+    /// these credentials.
+    fn build_client(destination: &Destination) -> Result<Client, String> {
+        let http_client = Self::build_http_client(destination)?;
+
+        if let (Some(token_file), Some(role_arn)) = (
+            &destination.s3_web_identity_token_file,
+            &destination.s3_role_arn,
+        ) {
+            let provider = WebIdentityProvider::new(
+                Variable::from_text_file(token_file),
+                role_arn.clone(),
+                Some(Variable::with_value(Some(String::from(ROLE_SESSION_NAME)))),
+            );
+            let provider =
+                AutoRefreshingProvider::new(provider).map_err(Self::map_credentials_error)?;
+            return Ok(Client::new_with(provider, http_client));
+        }
+
+        let client = match (&destination.s3_access_key, &destination.s3_secret_key) {
+            (Some(access_key), Some(secret_key)) => Client::new_with(
+                StaticProvider::new_minimal(access_key.clone(), secret_key.clone()),
+                http_client,
+            ),
+            _ => match &destination.s3_profile {
+                Some(profile) => {
+                    let mut provider =
+                        ProfileProvider::new().map_err(Self::map_credentials_error)?;
+                    provider.set_profile(profile.as_str());
+                    Client::new_with(provider, http_client)
+                }
+                None => Client::new_with(ChainProvider::new(), http_client),
+            },
+        };
+
+        match &destination.s3_role_arn {
+            Some(role_arn) => {
+                let provider = StsAssumeRoleSessionCredentialsProvider::new(
+                    StsClient::new_with_client(client, destination.s3_region.clone()),
+                    role_arn.clone(),
+                    String::from(ROLE_SESSION_NAME),
+                    destination.s3_external_id.clone(),
+                    None,
+                    None,
+                    None,
+                );
+                let provider =
+                    AutoRefreshingProvider::new(provider).map_err(Self::map_credentials_error)?;
+                Ok(Client::new_with(
+                    provider,
+                    Self::build_http_client(destination)?,
+                ))
+            }
+            None => Ok(client),
+        }
+    }
+
+    /// Gets the algorithm, key and key-MD5 of the SSE-C headers, if a customer key is set.
+    fn sse_customer_fields(&self) -> (Option<String>, Option<String>, Option<String>) {
+        match &self.sse_customer_key {
+            Some(sse_customer_key) => (
+                Some(String::from("AES256")),
+                Some(sse_customer_key.key.clone()),
+                Some(sse_customer_key.key_md5.clone()),
+            ),
+            None => (None, None, None),
+        }
+    }
+
+    /// Gets the server side encryption: SSE-KMS, if a key id is set, SSE-S3 otherwise. Objects
+    /// with a customer key (SSE-C) must not contain this header.
+    fn server_side_encryption(&self) -> Option<String> {
+        if self.sse_customer_key.is_some() {
+            return None;
+        }
+
+        match self.sse_kms_key_id {
+            Some(_) => Some(String::from("aws:kms")),
+            None => Some(String::from("AES256")),
+        }
+    }
+
+    async fn head_object(&self, file_name: &str) -> Result<HeadObjectOutput, String> {
+        let (sse_customer_algorithm, sse_customer_key, sse_customer_key_md5) =
+            self.sse_customer_fields();
+        let head_object_request = HeadObjectRequest {
+            bucket: self.bucket.clone(),
+            key: file_name.to_string(),
+            sse_customer_algorithm,
+            sse_customer_key,
+            sse_customer_key_md5,
+            ..Default::default()
+        };
+
+        self.client
+            .head_object(head_object_request)
+            .await
+            .map_err(Self::map_rusoto_head_object_error)
+    }
+
+    /// Applies the object lock retention to the uploaded object. The retention is set with a
+    /// separate request, as it requires a Content-MD5, which isn't known for streamed uploads.
+    async fn lock_object(&self, file_name: &str) -> Result<(), String> {
+        let (mode, retention) = match (&self.object_lock_mode, &self.object_lock_retention) {
+            (Some(mode), Some(retention)) => (mode, retention),
+            _ => return Ok(()),
+        };
+        let retain_until_date = retention
+            .retain_until(Utc::now())?
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        info!(
+            "locking object: {} ({} until {})",
+            file_name, mode, retain_until_date
+        );
+        let put_object_retention_request = PutObjectRetentionRequest {
+            bucket: self.bucket.clone(),
+            key: file_name.to_string(),
+            retention: Some(ObjectLockRetention {
+                mode: Some(mode.clone()),
+                retain_until_date: Some(retain_until_date),
+            }),
+            ..Default::default()
+        };
+        self.client
+            .put_object_retention(put_object_retention_request)
+            .await
+            .map_err(Self::map_rusoto_put_object_retention_error)?;
+
+        Ok(())
+    }
+
+    /// Restores the object temporarily, if it's archived in GLACIER or DEEP_ARCHIVE, and waits,
+    /// until the restored copy is available, but at most for the restore timeout.
+    async fn restore_archived_object(&self, file_name: &str) -> Result<(), String> {
+        let started = Instant::now();
+        let mut restore_initiated = false;
+        loop {
+            let head = self.head_object(file_name).await?;
+            match head.storage_class.as_deref() {
+                Some("GLACIER") | Some("DEEP_ARCHIVE") => {}
+                _ => return Ok(()),
+            }
+
+            match head.restore {
+                Some(restore) if restore.contains("ongoing-request=\"false\"") => {
+                    return Ok(());
+                }
+                Some(_) => {}
+                None if !restore_initiated => {
+                    info!(
+                        "initiating restore of archived object: {} (tier: {}, days: {})",
+                        file_name, self.restore_tier, self.restore_days
+                    );
+                    let restore_object_request = RestoreObjectRequest {
+                        bucket: self.bucket.clone(),
+                        key: file_name.to_string(),
+                        restore_request: Some(RestoreRequest {
+                            days: Some(self.restore_days),
+                            glacier_job_parameters: Some(GlacierJobParameters {
+                                tier: self.restore_tier.clone(),
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    };
+                    self.client
+                        .restore_object(restore_object_request)
+                        .await
+                        .map_err(Self::map_rusoto_restore_object_error)?;
+                    restore_initiated = true;
+                }
+                None => {}
+            }
+
+            if started.elapsed() >= self.restore_timeout {
+                return Err(format!(
+                    "the restore of archived object {} didn't complete within the restore-timeout of {} seconds.",
+                    file_name,
+                    self.restore_timeout.as_secs()
+                ));
+            }
+            info!("waiting for the restore of archived object: {}", file_name);
+            tokio::time::sleep(RESTORE_POLL_INTERVAL.min(self.restore_timeout)).await;
+        }
+    }
+
+    fn map_base64_error(err: base64::DecodeError) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_credentials_error(err: CredentialsError) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_native_tls_error(err: native_tls::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_delete_object_error(err: RusotoError<DeleteObjectError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_get_object_error(err: RusotoError<GetObjectError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_head_object_error(err: RusotoError<HeadObjectError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_list_objects_error(err: RusotoError<ListObjectsV2Error>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_put_object_error(err: RusotoError<PutObjectError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_put_object_retention_error(err: RusotoError<PutObjectRetentionError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_rusoto_restore_object_error(err: RusotoError<RestoreObjectError>) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_tls_error(err: TlsError) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, file_name: &str, body: ByteStream, length: u64) -> Result<(), String> {
+        let body = body.map_err(io::Error::other);
+        let (sse_customer_algorithm, sse_customer_key, sse_customer_key_md5) =
+            self.sse_customer_fields();
+        let put_object_request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: file_name.to_string(),
+            content_length: Some(length as i64),
+            body: Some(StreamingBody::new_with_size(body, length as usize)),
+            metadata: match self.metadata.is_empty() {
+                true => None,
+                false => Some(self.metadata.clone()),
+            },
+            server_side_encryption: self.server_side_encryption(),
+            sse_customer_algorithm,
+            sse_customer_key,
+            sse_customer_key_md5,
+            ssekms_key_id: self.sse_kms_key_id.clone(),
+            storage_class: self.storage_class.clone(),
+            tagging: self.tagging.clone(),
+            ..Default::default()
+        };
+        self.client
+            .put_object(put_object_request)
+            .await
+            .map_err(Self::map_rusoto_put_object_error)?;
+
+        self.lock_object(file_name).await
+    }
+
+    /// Lists the objects page by page, as every response contains at most 1000 keys.
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let mut archive_files = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let list_objects_request = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: prefix.map(String::from),
+                continuation_token: continuation_token.clone(),
+                ..Default::default()
+            };
+
+            let objects = self
+                .client
+                .list_objects_v2(list_objects_request)
+                .await
+                .map_err(Self::map_rusoto_list_objects_error)?;
+
+            for content in objects.contents.unwrap_or_default() {
+                let name = match content.key {
+                    Some(key) => key,
+                    None => continue,
+                };
+                let modified = content.last_modified.and_then(|modified| {
+                    NaiveDateTime::parse_from_str(modified.as_str(), "%Y-%m-%dT%H:%M:%S%.fZ").ok()
+                });
+                archive_files.push(ArchiveFile {
+                    name,
+                    size: content.size.unwrap_or_default() as u64,
+                    modified,
+                });
+            }
+
+            continuation_token = match objects.is_truncated {
+                Some(true) => objects.next_continuation_token,
+                _ => None,
+            };
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(archive_files)
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        self.restore_archived_object(file_name).await?;
+
+        let (sse_customer_algorithm, sse_customer_key, sse_customer_key_md5) =
+            self.sse_customer_fields();
+        let object_request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: file_name.to_string(),
+            sse_customer_algorithm,
+            sse_customer_key,
+            sse_customer_key_md5,
+            ..Default::default()
+        };
+        let object = self
+            .client
+            .get_object(object_request)
+            .await
+            .map_err(Self::map_rusoto_get_object_error)?;
+
+        let streaming_body = match object.body {
+            Some(streaming_body) => streaming_body,
+            None => return Err(format!("no body in S3-object: {}", file_name)),
+        };
+
+        Ok((
+            Box::pin(streaming_body.map_err(Self::map_error)),
+            object.content_length.map(|length| length as u64),
+        ))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        let delete_object_request = DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key: file_name.to_string(),
+            ..Default::default()
+        };
+        self.client
+            .delete_object(delete_object_request)
+            .await
+            .map_err(Self::map_rusoto_delete_object_error)?;
+
+        Ok(())
+    }
+}
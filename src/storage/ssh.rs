@@ -0,0 +1,209 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::task::Poll;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::DateTime;
+use futures::StreamExt;
+use log::{error, info};
+use ssh2::{ErrorCode, FileStat, Session, Sftp};
+
+use crate::configuration::{destination::Destination, Configuration};
+use crate::storage::throttle::UploadThrottle;
+use crate::storage::{ArchiveFile, ByteStream, Storage};
+
+/// The sftp status code of a missing file.
+const LIBSSH2_FX_NO_SUCH_FILE: i32 = 2;
+
+pub struct SshStorage {
+    /// The session is missing, if the handshake failed; uploads fall back to `sshpass scp` then.
+    session: Option<Session>,
+    server: String,
+    username: String,
+    password: String,
+}
+
+impl SshStorage {
+    pub fn connect(destination: &Destination) -> Result<SshStorage, String> {
+        let addr = format!("{}:22", destination.server);
+        let tcp = TcpStream::connect(addr).map_err(Self::map_error)?;
+        let mut ssh2_session = Session::new().map_err(Self::map_ssh_error)?;
+        ssh2_session.set_tcp_stream(tcp);
+
+        let session = match ssh2_session.handshake() {
+            Ok(_) => {
+                ssh2_session
+                    .userauth_password(&destination.username, &destination.password)
+                    .map_err(Self::map_ssh_error)?;
+                Some(ssh2_session)
+            }
+            Err(e) => {
+                error!("ssh2_session.handshake() err: {:?}", e);
+                None
+            }
+        };
+
+        Ok(SshStorage {
+            session,
+            server: destination.server.clone(),
+            username: destination.username.clone(),
+            password: destination.password.clone(),
+        })
+    }
+
+    fn session(&self) -> Result<&Session, String> {
+        match &self.session {
+            Some(session) => Ok(session),
+            None => Err(format!("no ssh session established to: {}", self.server)),
+        }
+    }
+
+    fn sftp(&self) -> Result<Sftp, String> {
+        self.session()?.sftp().map_err(Self::map_ssh_error)
+    }
+
+    fn build_archive_file(path: &Path, stat: &FileStat) -> ArchiveFile {
+        let modified = stat
+            .mtime
+            .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0))
+            .map(|date| date.naive_utc());
+
+        ArchiveFile {
+            name: format!("{}", path.display()),
+            size: stat.size.unwrap_or_default(),
+            modified,
+        }
+    }
+
+    /// Copies the file with scp, which limits the bandwidth in Kbit/s to the given rate.
+    fn scp_with_sshpass(&self, file_name: &str, rate: Option<usize>) -> Result<(), String> {
+        let dest_address = format!("{}@{}:/", self.username, self.server);
+        let mut scp_command = std::process::Command::new("sshpass");
+        scp_command.arg("-p").arg(&self.password);
+        scp_command.arg("scp");
+        if let Some(rate) = rate {
+            scp_command
+                .arg("-l")
+                .arg((rate * 8 / 1000).max(1).to_string());
+        }
+        scp_command.arg(file_name);
+        scp_command.arg(&dest_address);
+        let status = scp_command
+            .spawn()
+            .map_err(Self::map_error)?
+            .wait()
+            .map_err(Self::map_error)?;
+        if !status.success() {
+            return Err(format!("scp of '{}' failed with: {}", file_name, status));
+        }
+
+        Ok(())
+    }
+
+    fn map_error(err: std::io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    fn map_ssh_error(err: ssh2::Error) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for SshStorage {
+    async fn put(&self, file_name: &str, mut body: ByteStream, length: u64) -> Result<(), String> {
+        let mut remote_file = self
+            .session()?
+            .scp_send(Path::new(file_name), 0o644, length, None)
+            .map_err(Self::map_ssh_error)?;
+
+        while let Some(chunk) = body.next().await {
+            remote_file.write_all(&chunk?).map_err(Self::map_error)?;
+        }
+
+        remote_file.send_eof().map_err(Self::map_ssh_error)?;
+        remote_file.wait_eof().map_err(Self::map_ssh_error)?;
+        remote_file.close().map_err(Self::map_ssh_error)?;
+        remote_file.wait_close().map_err(Self::map_ssh_error)
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let paths = self
+            .sftp()?
+            .readdir(Path::new(""))
+            .map_err(Self::map_ssh_error)?;
+
+        Ok(paths
+            .iter()
+            .filter(|(_, stat)| stat.is_file())
+            .map(|(path, stat)| Self::build_archive_file(path, stat))
+            .filter(|archive_file| match prefix {
+                Some(prefix) => archive_file.name.starts_with(prefix),
+                None => true,
+            })
+            .collect())
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let mut sftp_file = self
+            .sftp()?
+            .open(Path::new(file_name))
+            .map_err(Self::map_ssh_error)?;
+        let length = sftp_file.stat().map_err(Self::map_ssh_error)?.size;
+
+        let stream = futures::stream::poll_fn(move |_| {
+            let mut buf = vec![0; Configuration::BUFFER_SIZE];
+            match sftp_file.read(&mut buf) {
+                Ok(0) => Poll::Ready(None),
+                Ok(read_bytes) => {
+                    buf.truncate(read_bytes);
+                    Poll::Ready(Some(Ok(Bytes::from(buf))))
+                }
+                Err(err) => Poll::Ready(Some(Err(Self::map_error(err)))),
+            }
+        });
+
+        Ok((Box::pin(stream), length))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        match self.sftp()?.unlink(Path::new(file_name)) {
+            Ok(_) => Ok(()),
+            Err(err) if err.code() == ErrorCode::SFTP(LIBSSH2_FX_NO_SUCH_FILE) => Ok(()),
+            Err(err) => Err(Self::map_ssh_error(err)),
+        }
+    }
+
+    async fn stat(&self, file_name: &str) -> Result<Option<ArchiveFile>, String> {
+        let path = Path::new(file_name);
+        match self.sftp()?.stat(path) {
+            Ok(stat) if stat.is_file() => Ok(Some(Self::build_archive_file(path, &stat))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn put_file_throttled(
+        &self,
+        file_name: &str,
+        throttle: Option<&UploadThrottle>,
+    ) -> Result<(), String> {
+        if self.session.is_some() {
+            let (body, length) = crate::storage::open_file_stream(file_name).await?;
+            let body = match throttle {
+                Some(throttle) => throttle.apply(body),
+                None => body,
+            };
+            info!("uploading file: {}", file_name);
+            self.put(file_name, body, length).await?;
+        } else {
+            info!("fallback to scp");
+            let rate = throttle.and_then(|throttle| throttle.current_rate());
+            self.scp_with_sshpass(file_name, rate)?;
+        }
+        info!("file upload ok: {}", file_name);
+
+        std::fs::remove_file(file_name).map_err(Self::map_error)
+    }
+}
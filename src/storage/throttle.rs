@@ -0,0 +1,298 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::time::Instant;
+
+use crate::configuration::destination::Destination;
+use crate::configuration::rate_limit::RateLimit;
+use crate::helper::ProgressStats;
+use crate::storage::{self, ArchiveFile, ByteStream, Storage};
+
+/// A token bucket, which holds at most the tokens of one second and is refilled with the rate.
+struct TokenBucket {
+    tokens: f64,
+    updated: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new() -> TokenBucket {
+        TokenBucket {
+            tokens: 0.0,
+            updated: None,
+        }
+    }
+
+    /// Takes the tokens for the given size; waits, until the bucket isn't in debt anymore.
+    async fn acquire(&mut self, size: usize, rate: usize) {
+        let now = Instant::now();
+        let rate = rate as f64;
+        self.tokens = match self.updated {
+            Some(updated) => {
+                (self.tokens + now.duration_since(updated).as_secs_f64() * rate).min(rate)
+            }
+            None => rate,
+        };
+        self.updated = Some(now);
+
+        self.tokens -= size as f64;
+        if self.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-self.tokens / rate)).await;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.updated = None;
+    }
+}
+
+/// Limits the given stream to the rate, which currently applies. Chunks are split, so that none
+/// is bigger than the tokens of one second.
+pub fn throttle(body: ByteStream, rate_limit: Arc<RateLimit>) -> ByteStream {
+    let state = (body, TokenBucket::new(), Bytes::new());
+    let stream = futures::stream::unfold(state, move |(mut body, mut bucket, mut pending)| {
+        let rate_limit = Arc::clone(&rate_limit);
+        async move {
+            if pending.is_empty() {
+                match body.next().await {
+                    Some(Ok(chunk)) => pending = chunk,
+                    Some(Err(err)) => return Some((Err(err), (body, bucket, pending))),
+                    None => return None,
+                }
+            }
+
+            let chunk = match rate_limit.current_rate() {
+                Some(rate) => {
+                    let chunk = pending.split_to(pending.len().min(rate));
+                    bucket.acquire(chunk.len(), rate).await;
+                    chunk
+                }
+                None => {
+                    bucket.reset();
+                    std::mem::take(&mut pending)
+                }
+            };
+            Some((Ok(chunk), (body, bucket, pending)))
+        }
+    });
+
+    Box::pin(stream)
+}
+
+/// Throttles the upload of a file to the rate limit and tracks its progress.
+pub struct UploadThrottle {
+    rate_limit: Arc<RateLimit>,
+    stats: Arc<Mutex<ProgressStats>>,
+}
+
+impl UploadThrottle {
+    fn new(rate_limit: Arc<RateLimit>, length: Option<u64>) -> UploadThrottle {
+        let mut upload_stats = ProgressStats::new();
+        upload_stats.total_length = length.map(|length| length as usize);
+        upload_stats.rate_limit = rate_limit.current_rate();
+        UploadThrottle {
+            rate_limit,
+            stats: Arc::new(Mutex::new(upload_stats)),
+        }
+    }
+
+    /// Gets the rate, which applies now, for uploads, which can't be throttled as stream.
+    pub fn current_rate(&self) -> Option<usize> {
+        self.rate_limit.current_rate()
+    }
+
+    /// Throttles the given stream of the file and adds its chunks to the progress.
+    pub fn apply(&self, body: ByteStream) -> ByteStream {
+        storage::track_progress(
+            throttle(body, Arc::clone(&self.rate_limit)),
+            Arc::clone(&self.stats),
+        )
+    }
+}
+
+/// Wraps the storage of a destination with `max-upload-rate`/`max-download-rate` limits.
+pub struct ThrottledStorage {
+    storage: Box<dyn Storage>,
+    upload_rate_limit: Arc<RateLimit>,
+    download_rate_limit: Arc<RateLimit>,
+}
+
+impl ThrottledStorage {
+    pub fn new(storage: Box<dyn Storage>, destination: &Destination) -> ThrottledStorage {
+        ThrottledStorage {
+            storage,
+            upload_rate_limit: Arc::new(destination.max_upload_rate.clone()),
+            download_rate_limit: Arc::new(destination.max_download_rate.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for ThrottledStorage {
+    async fn put(&self, file_name: &str, body: ByteStream, length: u64) -> Result<(), String> {
+        let body = match self.upload_rate_limit.is_limited() {
+            true => throttle(body, Arc::clone(&self.upload_rate_limit)),
+            false => body,
+        };
+        self.storage.put(file_name, body, length).await
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        self.storage.list(prefix).await
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let (body, length) = self.storage.get(file_name).await?;
+        let body = match self.download_rate_limit.is_limited() {
+            true => throttle(body, Arc::clone(&self.download_rate_limit)),
+            false => body,
+        };
+        Ok((body, length))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        self.storage.delete(file_name).await
+    }
+
+    async fn stat(&self, file_name: &str) -> Result<Option<ArchiveFile>, String> {
+        self.storage.stat(file_name).await
+    }
+
+    /// Uploads the file through the storage's own upload path, while printing the progress.
+    async fn put_file(&self, file_name: &str) -> Result<(), String> {
+        if !self.upload_rate_limit.is_limited() {
+            return self.storage.put_file(file_name).await;
+        }
+
+        let length = fs::metadata(file_name).map(|metadata| metadata.len()).ok();
+        let throttle = UploadThrottle::new(Arc::clone(&self.upload_rate_limit), length);
+        let thread =
+            ProgressStats::spawn_output_thread(Arc::clone(&throttle.stats), "uploading...");
+        let result = self
+            .storage
+            .put_file_throttled(file_name, Some(&throttle))
+            .await;
+
+        if let Ok(mut upload_stats) = throttle.stats.lock() {
+            upload_stats.set_finished();
+        }
+        thread.join().unwrap_or_default();
+        println!();
+
+        result
+    }
+
+    async fn put_file_throttled(
+        &self,
+        file_name: &str,
+        throttle: Option<&UploadThrottle>,
+    ) -> Result<(), String> {
+        self.storage.put_file_throttled(file_name, throttle).await
+    }
+
+    async fn get_file(&self, file_name: &str) -> Result<(), String> {
+        if !self.download_rate_limit.is_limited() {
+            return self.storage.get_file(file_name).await;
+        }
+
+        let (body, length) = self.get(file_name).await?;
+        storage::write_stream_to_file(
+            body,
+            length,
+            file_name,
+            self.download_rate_limit.current_rate(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limit(rate: usize) -> Arc<RateLimit> {
+        let mut rate_limit = RateLimit::new();
+        rate_limit.rate = Some(rate);
+        Arc::new(rate_limit)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_starts_with_the_tokens_of_one_second() {
+        let mut bucket = TokenBucket::new();
+        let started = Instant::now();
+        bucket.acquire(100_000, 100_000).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(bucket.tokens, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_waits_for_its_debt() {
+        let mut bucket = TokenBucket::new();
+        bucket.acquire(100_000, 100_000).await;
+
+        let started = Instant::now();
+        bucket.acquire(20_000, 100_000).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_is_refilled_with_the_rate() {
+        let mut bucket = TokenBucket::new();
+        bucket.acquire(100_000, 100_000).await;
+        tokio::time::advance(Duration::from_millis(500)).await;
+
+        let started = Instant::now();
+        bucket.acquire(50_000, 100_000).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(bucket.tokens, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_is_full_after_a_reset() {
+        let mut bucket = TokenBucket::new();
+        bucket.acquire(100_000, 100_000).await;
+        bucket.reset();
+
+        let started = Instant::now();
+        bucket.acquire(100_000, 100_000).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_holds_at_most_one_second() {
+        let mut bucket = TokenBucket::new();
+        bucket.acquire(1_000, 1_000).await;
+        tokio::time::advance(Duration::from_secs(10)).await;
+        bucket.acquire(0, 1_000).await;
+        assert_eq!(bucket.tokens, 1_000.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttle_splits_chunks_to_the_rate() {
+        let body: ByteStream = Box::pin(futures::stream::iter(vec![Ok(Bytes::from(vec![
+            0u8;
+            25_000
+        ]))]));
+        let started = Instant::now();
+        let chunks: Vec<usize> = throttle(body, rate_limit(10_000))
+            .map(|chunk| chunk.unwrap().len())
+            .collect()
+            .await;
+        assert_eq!(chunks, [10_000, 10_000, 5_000]);
+        assert_eq!(started.elapsed(), Duration::from_millis(1_500));
+    }
+
+    #[tokio::test]
+    async fn throttle_passes_errors() {
+        let body: ByteStream = Box::pin(futures::stream::iter(vec![
+            Ok(Bytes::from_static(b"data")),
+            Err(String::from("broken")),
+        ]));
+        let chunks: Vec<Result<Bytes, String>> = throttle(body, rate_limit(1_000)).collect().await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1], Err(String::from("broken")));
+    }
+}
@@ -0,0 +1,449 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use futures::TryStreamExt;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Body, Client, Method, RequestBuilder, StatusCode};
+use xml::reader::{EventReader, XmlEvent as XmlReaderEvent};
+
+use crate::configuration::destination::Destination;
+use crate::storage::{ArchiveFile, ByteStream, Storage};
+
+/// Characters, which are kept as they are in a path segment of an url.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+	<d:prop>
+		<d:getcontentlength/>
+		<d:getlastmodified/>
+		<d:resourcetype/>
+	</d:prop>
+</d:propfind>"#;
+
+pub struct WebDavStorage {
+    client: Client,
+    url: String,
+    path_segments: Vec<String>,
+    username: String,
+    password: String,
+    /// Whether the configured path was created on the server by this instance.
+    collections_created: AtomicBool,
+}
+
+impl WebDavStorage {
+    pub fn new(destination: &Destination) -> Result<WebDavStorage, String> {
+        if destination.url.is_empty() {
+            return Err(String::from(
+                "the destination-url must be set for kind: webdav",
+            ));
+        }
+
+        let client = Client::builder().build().map_err(Self::map_error)?;
+
+        Ok(WebDavStorage {
+            client,
+            url: destination.url.trim_end_matches('/').to_string(),
+            path_segments: destination
+                .path
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(String::from)
+                .collect(),
+            username: destination.username.clone(),
+            password: destination.password.clone(),
+            collections_created: AtomicBool::new(false),
+        })
+    }
+
+    fn build_collection_url(&self, segment_count: usize) -> String {
+        let mut url = self.url.clone();
+        for segment in self.path_segments.iter().take(segment_count) {
+            url.push('/');
+            url.push_str(&utf8_percent_encode(segment, PATH_SEGMENT).to_string());
+        }
+        url.push('/');
+
+        url
+    }
+
+    fn build_file_url(&self, file_name: &str) -> String {
+        format!(
+            "{}{}",
+            self.build_collection_url(self.path_segments.len()),
+            utf8_percent_encode(file_name, PATH_SEGMENT)
+        )
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        if self.username.is_empty() {
+            return request;
+        }
+
+        request.basic_auth(&self.username, Some(&self.password))
+    }
+
+    /// Creates the configured path on the server, if it doesn't exist yet; only once for all
+    /// uploads of this instance.
+    async fn create_collections(&self) -> Result<(), String> {
+        if self.collections_created.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mkcol = Method::from_bytes(b"MKCOL").map_err(Self::map_error)?;
+        for segment_count in 1..=self.path_segments.len() {
+            let url = self.build_collection_url(segment_count);
+            let response = self
+                .request(mkcol.clone(), &url)
+                .send()
+                .await
+                .map_err(Self::map_error)?;
+            let status = response.status();
+            // 405 (Method Not Allowed) is returned, if the collection already exists.
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(format!("MKCOL {} failed with status: {}", url, status));
+            }
+        }
+        self.collections_created.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn parse_multistatus(xml: &str) -> Result<Vec<ArchiveFile>, String> {
+        let mut archive_files = Vec::new();
+        let mut current_element = String::new();
+        let mut href = String::new();
+        let mut size = 0;
+        let mut modified = None;
+        let mut is_collection = false;
+
+        for e in EventReader::new(xml.as_bytes()) {
+            match e {
+                Ok(XmlReaderEvent::StartElement { name, .. }) => {
+                    match name.local_name.as_str() {
+                        "response" => {
+                            href = String::new();
+                            size = 0;
+                            modified = None;
+                            is_collection = false;
+                        }
+                        "collection" => {
+                            is_collection = true;
+                        }
+                        _ => {}
+                    }
+                    current_element = name.local_name;
+                }
+                Ok(XmlReaderEvent::Characters(text)) => match current_element.as_str() {
+                    "href" => {
+                        href.push_str(&text);
+                    }
+                    "getcontentlength" => {
+                        size = text.trim().parse().unwrap_or_default();
+                    }
+                    "getlastmodified" => {
+                        modified = DateTime::parse_from_rfc2822(text.trim())
+                            .ok()
+                            .map(|date| date.naive_utc());
+                    }
+                    _ => {}
+                },
+                Ok(XmlReaderEvent::EndElement { name }) => {
+                    current_element = String::new();
+                    if name.local_name != "response" || is_collection {
+                        continue;
+                    }
+
+                    let file_name = match href.trim_end_matches('/').rsplit('/').next() {
+                        Some(file_name) if !file_name.is_empty() => file_name,
+                        _ => continue,
+                    };
+                    archive_files.push(ArchiveFile {
+                        name: percent_decode_str(file_name)
+                            .decode_utf8_lossy()
+                            .to_string(),
+                        size,
+                        modified,
+                    });
+                }
+                Err(err) => {
+                    return Err(format!("XML-Error: {:?}", err));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(archive_files)
+    }
+
+    fn map_error<E: std::fmt::Debug>(err: E) -> String {
+        format!("error: {:?}", err)
+    }
+}
+
+#[async_trait]
+impl Storage for WebDavStorage {
+    async fn put(&self, file_name: &str, body: ByteStream, length: u64) -> Result<(), String> {
+        self.create_collections().await?;
+
+        // The file is streamed with chunked transfer-encoding; its length is announced for servers
+        // like Nextcloud, which check it after the upload.
+        let url = self.build_file_url(file_name);
+        let response = self
+            .request(Method::PUT, &url)
+            .header("X-Expected-Entity-Length", length)
+            .body(Body::wrap_stream(body))
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "PUT {} failed with status: {}",
+                url,
+                response.status()
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: Option<&str>) -> Result<Vec<ArchiveFile>, String> {
+        let url = self.build_collection_url(self.path_segments.len());
+        let propfind = Method::from_bytes(b"PROPFIND").map_err(Self::map_error)?;
+        let response = self
+            .request(propfind, &url)
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(Vec::new()),
+            StatusCode::MULTI_STATUS => {}
+            status => {
+                return Err(format!("PROPFIND {} failed with status: {}", url, status));
+            }
+        }
+
+        let xml = response.text().await.map_err(Self::map_error)?;
+        let mut archive_files = Self::parse_multistatus(&xml)?;
+        if let Some(prefix) = prefix {
+            archive_files.retain(|archive_file| archive_file.name.starts_with(prefix));
+        }
+
+        Ok(archive_files)
+    }
+
+    async fn get(&self, file_name: &str) -> Result<(ByteStream, Option<u64>), String> {
+        let url = self.build_file_url(file_name);
+        let response = self
+            .request(Method::GET, &url)
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "GET {} failed with status: {}",
+                url,
+                response.status()
+            ));
+        }
+
+        let length = response.content_length();
+        Ok((
+            Box::pin(response.bytes_stream().map_err(Self::map_error)),
+            length,
+        ))
+    }
+
+    async fn delete(&self, file_name: &str) -> Result<(), String> {
+        let url = self.build_file_url(file_name);
+        let response = self
+            .request(Method::DELETE, &url)
+            .send()
+            .await
+            .map_err(Self::map_error)?;
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            return Err(format!("DELETE {} failed with status: {}", url, status));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves every request with "201 Created" and records the requests.
+    async fn serve(listener: TcpListener, requests: Arc<Mutex<Vec<String>>>) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            let request_text = loop {
+                let read_bytes = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read_bytes]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                let head_end = match text.find("\r\n\r\n") {
+                    Some(head_end) => head_end,
+                    None => continue,
+                };
+                let head = text[..head_end].to_lowercase();
+                if !head.contains("transfer-encoding: chunked") || text.ends_with("0\r\n\r\n") {
+                    break text;
+                }
+            };
+            requests.lock().unwrap().push(request_text);
+            stream
+                .write_all(
+                    b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+        }
+    }
+
+    fn body(content: &'static [u8]) -> ByteStream {
+        Box::pin(futures::stream::iter(vec![Ok(Bytes::from_static(content))]))
+    }
+
+    fn storage(url: &str, path: &str) -> WebDavStorage {
+        let mut destination = Destination::new();
+        destination.url = String::from(url);
+        destination.path = String::from(path);
+        WebDavStorage::new(&destination).unwrap()
+    }
+
+    #[test]
+    fn requires_an_url() {
+        assert!(WebDavStorage::new(&Destination::new()).is_err());
+    }
+
+    #[test]
+    fn builds_collection_urls() {
+        let storage = storage("https://cloud.example.com/dav/", "/backups/db server/");
+        assert_eq!(
+            storage.build_collection_url(0),
+            "https://cloud.example.com/dav/"
+        );
+        assert_eq!(
+            storage.build_collection_url(1),
+            "https://cloud.example.com/dav/backups/"
+        );
+        assert_eq!(
+            storage.build_collection_url(2),
+            "https://cloud.example.com/dav/backups/db%20server/"
+        );
+    }
+
+    #[test]
+    fn builds_file_urls() {
+        let storage = storage("https://cloud.example.com/dav", "backups");
+        assert_eq!(
+            storage.build_file_url("www_2024-03-01.tar.bz2"),
+            "https://cloud.example.com/dav/backups/www_2024-03-01.tar.bz2"
+        );
+        assert_eq!(
+            storage.build_file_url("a b#?.tar"),
+            "https://cloud.example.com/dav/backups/a%20b%23%3F.tar"
+        );
+        assert_eq!(
+            self::storage("https://cloud.example.com/dav", "").build_file_url("www.tar"),
+            "https://cloud.example.com/dav/www.tar"
+        );
+    }
+
+    #[test]
+    fn parses_multistatus_responses() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+	<d:response>
+		<d:href>/dav/backups/</d:href>
+		<d:propstat><d:prop>
+			<d:getlastmodified>Fri, 01 Mar 2024 10:00:00 GMT</d:getlastmodified>
+			<d:resourcetype><d:collection/></d:resourcetype>
+		</d:prop></d:propstat>
+	</d:response>
+	<d:response>
+		<d:href>/dav/backups/www%20data_01.tar.bz2</d:href>
+		<d:propstat><d:prop>
+			<d:getcontentlength>1024</d:getcontentlength>
+			<d:getlastmodified>Fri, 01 Mar 2024 18:30:00 GMT</d:getlastmodified>
+			<d:resourcetype/>
+		</d:prop></d:propstat>
+	</d:response>
+	<d:response>
+		<d:href>https://cloud.example.com/dav/backups/www_02.tar.bz2</d:href>
+		<d:propstat><d:prop>
+			<d:getcontentlength>2048</d:getcontentlength>
+			<d:resourcetype/>
+		</d:prop></d:propstat>
+	</d:response>
+</d:multistatus>"#;
+
+        let archive_files = WebDavStorage::parse_multistatus(xml).unwrap();
+        assert_eq!(archive_files.len(), 2);
+        assert_eq!(archive_files[0].name, "www data_01.tar.bz2");
+        assert_eq!(archive_files[0].size, 1024);
+        assert_eq!(
+            archive_files[0].modified.unwrap().to_string(),
+            "2024-03-01 18:30:00"
+        );
+        assert_eq!(archive_files[1].name, "www_02.tar.bz2");
+        assert_eq!(archive_files[1].size, 2048);
+        assert!(archive_files[1].modified.is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_multistatus_responses() {
+        assert!(WebDavStorage::parse_multistatus("<d:multistatus xmlns:d=\"DAV:\">").is_err());
+    }
+
+    #[tokio::test]
+    async fn puts_chunked_and_creates_the_collections_once() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/dav", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let server = tokio::spawn(serve(listener, Arc::clone(&requests)));
+
+        let storage = storage(&url, "backups/www");
+        storage.put("01.tar", body(b"first"), 5).await.unwrap();
+        storage.put("02.tar", body(b"second"), 6).await.unwrap();
+        server.abort();
+
+        let requests = requests.lock().unwrap();
+        let request_lines: Vec<&str> = requests
+            .iter()
+            .map(|head| head.lines().next().unwrap())
+            .collect();
+        assert_eq!(
+            request_lines,
+            [
+                "MKCOL /dav/backups/ HTTP/1.1",
+                "MKCOL /dav/backups/www/ HTTP/1.1",
+                "PUT /dav/backups/www/01.tar HTTP/1.1",
+                "PUT /dav/backups/www/02.tar HTTP/1.1",
+            ]
+        );
+        let put = requests[2].to_lowercase();
+        assert!(put.contains("transfer-encoding: chunked"), "{}", put);
+        assert!(put.contains("x-expected-entity-length: 5"), "{}", put);
+        assert!(!put.contains("content-length"), "{}", put);
+        assert!(requests[2].contains("first"));
+    }
+}
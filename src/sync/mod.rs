@@ -0,0 +1,321 @@
+use futures::StreamExt;
+use log::{info, warn};
+
+use crate::configuration::{
+    archive::Archive,
+    destination::{Destination, Kind as DestinationKind},
+    Configuration,
+};
+use crate::helper::checksum::Checksum;
+use crate::storage::Storage;
+
+pub struct Synchronization {}
+
+impl Synchronization {
+    fn find_destination(configuration: &Configuration, id: &str) -> Result<Destination, String> {
+        match configuration
+            .destinations
+            .iter()
+            .find(|destination| destination.id == id)
+        {
+            Some(destination) => Ok(destination.clone()),
+            None => Err(format!(
+                "destination '{}' not found in configuration.destinations",
+                id
+            )),
+        }
+    }
+
+    /// Reads the checksum of the given file from its sidecar file on the storage, if it has one.
+    async fn read_checksum(
+        storage: &dyn Storage,
+        file_name: &str,
+    ) -> Result<Option<String>, String> {
+        let sidecar_name = Checksum::get_sidecar_name(file_name);
+        if storage.stat(&sidecar_name).await?.is_none() {
+            return Ok(None);
+        }
+
+        let (mut body, _) = storage.get(&sidecar_name).await?;
+        let mut content = Vec::new();
+        while let Some(chunk) = body.next().await {
+            content.extend_from_slice(&chunk?);
+        }
+        Ok(Checksum::parse_sidecar(
+            &String::from_utf8_lossy(&content),
+            file_name,
+        ))
+    }
+
+    /// Checks, if both destinations have a checksum of the given file, which differ; the file
+    /// may have been replaced with another one of the same size then.
+    async fn checksums_differ(
+        source_storage: &dyn Storage,
+        target_storage: &dyn Storage,
+        file_name: &str,
+    ) -> Result<bool, String> {
+        let target_checksum = match Self::read_checksum(target_storage, file_name).await? {
+            Some(target_checksum) => target_checksum,
+            None => return Ok(false),
+        };
+        match Self::read_checksum(source_storage, file_name).await? {
+            Some(source_checksum) => Ok(source_checksum != target_checksum),
+            None => Ok(false),
+        }
+    }
+
+    /// Copies the files of the archive (with their companion files), which are missing or differ
+    /// on the target storage, from the source storage.
+    async fn sync_archive(
+        archive: &Archive,
+        source_storage: &dyn Storage,
+        target_storage: &dyn Storage,
+        target_id: &str,
+    ) -> Result<(), String> {
+        let source_files = Destination::list_storage_archive_files(source_storage, archive).await?;
+
+        for source_file in source_files {
+            let companion_files = Destination::get_companion_files(archive, &source_file.name);
+            // The companion files are replaced along with a differing archive file.
+            let checksums_differ =
+                Self::checksums_differ(source_storage, target_storage, &source_file.name).await?;
+            if checksums_differ {
+                warn!(
+                    "the checksums of '{}' differ, replacing it on '{}'.",
+                    source_file.name, target_id
+                );
+            }
+            let mut files_to_transfer = vec![source_file];
+            for companion_file in companion_files {
+                if let Some(companion_file) = source_storage.stat(&companion_file).await? {
+                    files_to_transfer.push(companion_file);
+                }
+            }
+
+            for source_file in files_to_transfer {
+                if !checksums_differ {
+                    if let Some(target_file) = target_storage.stat(&source_file.name).await? {
+                        if target_file.size == source_file.size {
+                            continue;
+                        }
+                    }
+                }
+
+                info!("transferring file: {}", source_file.name);
+                let (body, _) = source_storage.get(&source_file.name).await?;
+                target_storage
+                    .put(&source_file.name, body, source_file.size)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies all archives (with their companion files), which are missing or differ on the
+    /// target destination, from the source destination (default: the destination of each
+    /// archive) to the target destination. Files are in sync, if their sizes and, if both have
+    /// one, their checksums are equal.
+    pub async fn start(
+        configuration: Configuration,
+        source_id: Option<String>,
+        target_id: String,
+    ) -> Result<(), String> {
+        let target = Self::find_destination(&configuration, &target_id)?;
+        let source_opt = match source_id {
+            Some(source_id) => Some(Self::find_destination(&configuration, &source_id)?),
+            None => None,
+        };
+
+        let target_storage = target.storage().await?;
+
+        let mut synchronized_archives: Vec<(String, String)> = Vec::new();
+        for archive in configuration.archives {
+            let source = match &source_opt {
+                Some(source) => source.clone(),
+                None => archive.destination.clone(),
+            };
+            if source.kind == DestinationKind::None {
+                continue;
+            }
+            if source.id == target.id {
+                warn!(
+                    "skipping archive '{}': source and target destination are identical.",
+                    archive.name
+                );
+                continue;
+            }
+            let synchronized_archive = (source.id.clone(), archive.name.clone());
+            if synchronized_archives.contains(&synchronized_archive) {
+                continue;
+            }
+            synchronized_archives.push(synchronized_archive);
+
+            info!(
+                "synchronizing archive: {} ('{}' => '{}')",
+                archive.name, source.id, target.id
+            );
+            let source_storage = source.storage().await?;
+            Self::sync_archive(
+                &archive,
+                source_storage.as_ref(),
+                target_storage.as_ref(),
+                &target.id,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::backup::manifest::Manifest;
+    use crate::configuration::compression::Compression;
+    use crate::storage::directory::DirectoryStorage;
+
+    const ARCHIVE_FILE: &str = "www_2024.tar";
+
+    struct Storages {
+        root: PathBuf,
+        source: DirectoryStorage,
+        target: DirectoryStorage,
+    }
+
+    impl Storages {
+        fn new(name: &str) -> Storages {
+            let root =
+                env::temp_dir().join(format!("rusty-backup-sync-{}-{}", std::process::id(), name));
+            let storage = |directory: &str| {
+                let path = root.join(directory);
+                fs::create_dir_all(&path).unwrap();
+                let mut destination = Destination::new();
+                destination.path = path.to_string_lossy().to_string();
+                DirectoryStorage::new(&destination)
+            };
+            let source = storage("source");
+            let target = storage("target");
+            Storages {
+                root,
+                source,
+                target,
+            }
+        }
+
+        fn write(&self, directory: &str, file_name: &str, content: &str) {
+            fs::write(self.root.join(directory).join(file_name), content).unwrap();
+        }
+
+        fn read(&self, directory: &str, file_name: &str) -> Option<String> {
+            fs::read_to_string(self.root.join(directory).join(file_name)).ok()
+        }
+
+        async fn sync(&self) {
+            Synchronization::sync_archive(&archive(), &self.source, &self.target, "target")
+                .await
+                .unwrap();
+        }
+    }
+
+    impl Drop for Storages {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.root).unwrap_or_default();
+        }
+    }
+
+    fn archive() -> Archive {
+        let mut archive = Archive::new();
+        archive.name = String::from("www_{date:year}");
+        archive.compression = Compression::Tar;
+        archive
+    }
+
+    fn sidecar(checksum: &str) -> String {
+        format!("{}  {}\n", checksum, ARCHIVE_FILE)
+    }
+
+    #[tokio::test]
+    async fn copies_archive_files_with_their_companion_files() {
+        let storages = Storages::new("copy");
+        let manifest_file = Manifest::get_file_name(&archive(), ARCHIVE_FILE);
+        storages.write("source", ARCHIVE_FILE, "archive");
+        storages.write(
+            "source",
+            &Checksum::get_sidecar_name(ARCHIVE_FILE),
+            &sidecar("aa"),
+        );
+        storages.write("source", &manifest_file, "{}");
+        storages.write("source", "db_2024.tar", "other archive");
+
+        storages.sync().await;
+
+        assert_eq!(
+            storages.read("target", ARCHIVE_FILE).as_deref(),
+            Some("archive")
+        );
+        assert_eq!(
+            storages.read("target", &Checksum::get_sidecar_name(ARCHIVE_FILE)),
+            Some(sidecar("aa"))
+        );
+        assert_eq!(
+            storages.read("target", &manifest_file).as_deref(),
+            Some("{}")
+        );
+        assert!(!storages.root.join("target/db_2024.tar").exists());
+    }
+
+    #[tokio::test]
+    async fn skips_archive_files_of_the_same_size() {
+        let storages = Storages::new("skip");
+        storages.write("source", ARCHIVE_FILE, "archive");
+        storages.write("target", ARCHIVE_FILE, "ARCHIVE");
+
+        storages.sync().await;
+
+        assert_eq!(
+            storages.read("target", ARCHIVE_FILE).as_deref(),
+            Some("ARCHIVE")
+        );
+    }
+
+    #[tokio::test]
+    async fn copies_archive_files_with_another_checksum_again() {
+        let storages = Storages::new("checksum");
+        let sidecar_file = Checksum::get_sidecar_name(ARCHIVE_FILE);
+        storages.write("source", ARCHIVE_FILE, "archive");
+        storages.write("source", &sidecar_file, &sidecar("aa"));
+        storages.write("target", ARCHIVE_FILE, "ARCHIVE");
+        storages.write("target", &sidecar_file, &sidecar("bb"));
+
+        storages.sync().await;
+
+        assert_eq!(
+            storages.read("target", ARCHIVE_FILE).as_deref(),
+            Some("archive")
+        );
+        assert_eq!(storages.read("target", &sidecar_file), Some(sidecar("aa")));
+    }
+
+    #[tokio::test]
+    async fn skips_archive_files_with_the_same_checksum() {
+        let storages = Storages::new("same-checksum");
+        let sidecar_file = Checksum::get_sidecar_name(ARCHIVE_FILE);
+        storages.write("source", ARCHIVE_FILE, "archive");
+        storages.write("source", &sidecar_file, &sidecar("aa"));
+        storages.write("target", ARCHIVE_FILE, "ARCHIVE");
+        storages.write("target", &sidecar_file, &sidecar("AA"));
+
+        storages.sync().await;
+
+        assert_eq!(
+            storages.read("target", ARCHIVE_FILE).as_deref(),
+            Some("ARCHIVE")
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+
+use log::{error, info, warn};
+
+use crate::configuration::archive::Archive;
+use crate::configuration::compression::Compression;
+use crate::configuration::destination::{Destination, Kind as DestinationKind};
+use crate::configuration::Configuration;
+use crate::formatter::Formatter;
+use crate::helper::checksum::Checksum;
+use crate::restore::selection::ArchiveSelection;
+use crate::storage::{ArchiveFile, Storage};
+
+pub struct Verify {}
+
+impl Verify {
+    fn map_error(err: io::Error) -> String {
+        format!("error: {:?}", err)
+    }
+
+    /// Decrypts and decompresses the downloaded archive file and reads all of its entries, so
+    /// every checksum of the compression is checked; with `test_import`, the database dumps are
+    /// imported into scratch databases. Returns a summary of the archive file's content.
+    fn walk_archive_file(
+        archive: &Archive,
+        file_name: &str,
+        test_import: bool,
+    ) -> Result<String, String> {
+        let (reader, mut child) = archive.open_tar_stream(file_name)?;
+        let mut tar = tar::Archive::new(reader);
+
+        let mut entries = 0;
+        let mut size = 0;
+        let mut imported_dumps = 0;
+        for entry in tar.entries().map_err(Self::map_error)? {
+            let mut entry = entry.map_err(Self::map_error)?;
+            let entry_path = entry
+                .path()
+                .map_err(Self::map_error)?
+                .to_string_lossy()
+                .to_string();
+            entries += 1;
+
+            let database = archive
+                .databases
+                .iter()
+                .find(|database| database.build_dump_filename() == entry_path);
+            match database {
+                Some(database) if test_import => {
+                    let dump_file_name = format!("{}.verify", entry_path);
+                    let result = File::create(&dump_file_name)
+                        .and_then(|mut dump_file| io::copy(&mut entry, &mut dump_file))
+                        .map_err(Self::map_error)
+                        .and_then(|copied| {
+                            size += copied;
+                            info!("test-importing database dump: {}", entry_path);
+                            database
+                                .test_import(File::open(&dump_file_name).map_err(Self::map_error)?)
+                        });
+                    fs::remove_file(&dump_file_name).unwrap_or_default();
+                    result.map_err(|why| format!("test-import of '{}': {}", entry_path, why))?;
+                    imported_dumps += 1;
+                }
+                _ => size += io::copy(&mut entry, &mut io::sink()).map_err(Self::map_error)?,
+            }
+        }
+
+        // The rest of the stream is drained, so the decryption-program doesn't fail on writing.
+        io::copy(&mut tar.into_inner(), &mut io::sink()).map_err(Self::map_error)?;
+        if let Some(child) = &mut child {
+            let status = child.wait().map_err(Self::map_error)?;
+            if !status.success() {
+                return Err(format!("decryption failed with: {}", status));
+            }
+        }
+
+        let mut summary = format!(
+            "{} entries, {}",
+            entries,
+            Formatter::format_size(size as usize, 2)
+        );
+        if test_import {
+            summary.push_str(&format!(", {} database dumps imported", imported_dumps));
+        }
+        Ok(summary)
+    }
+
+    async fn verify_archive_file(
+        storage: &dyn Storage,
+        archive: &Archive,
+        archive_file: &ArchiveFile,
+        test_import: bool,
+    ) -> Result<String, String> {
+        storage.get_file(&archive_file.name).await?;
+
+        let sidecar_name = Checksum::get_sidecar_name(&archive_file.name);
+        let checksum_summary = match storage.stat(&sidecar_name).await? {
+            Some(_) => {
+                storage.get_file(&sidecar_name).await?;
+                Checksum::verify(&archive_file.name)?;
+                "checksum ok"
+            }
+            None => {
+                warn!("no checksum file found for '{}'.", archive_file.name);
+                "no checksum"
+            }
+        };
+
+        let content_summary = Self::walk_archive_file(archive, &archive_file.name, test_import)?;
+        Ok(format!("{}, {}", checksum_summary, content_summary))
+    }
+
+    /// Downloads the selected archive file (or all matching archive files with `all_versions`) of
+    /// every archive and verifies its checksum and content; fails, if any archive file is broken.
+    pub async fn start(
+        configuration: Configuration,
+        archive_selection: ArchiveSelection,
+        all_versions: bool,
+        test_import: bool,
+    ) -> Result<(), String> {
+        fs::create_dir_all(&configuration.working_directory).map_err(Self::map_error)?;
+        env::set_current_dir(&configuration.working_directory).map_err(Self::map_error)?;
+
+        let mut verified_files = 0;
+        let mut failed_files = 0;
+        for archive in &configuration.archives {
+            if archive.destination.kind == DestinationKind::None {
+                warn!(
+                    "the archive '{}' has no destination to verify.",
+                    archive.name
+                );
+                continue;
+            }
+            if archive.compression == Compression::None {
+                warn!(
+                    "the archive '{}' has no compression and so no archive files.",
+                    archive.name
+                );
+                continue;
+            }
+
+            let storage = archive.destination.storage().await?;
+            let archive_files =
+                Destination::list_storage_archive_files(storage.as_ref(), archive).await?;
+            let mut archive_files = archive_selection.filter(archive, archive_files);
+            if !all_versions && archive_files.len() > 1 {
+                archive_files.drain(..archive_files.len() - 1);
+            }
+            if archive_files.is_empty() {
+                warn!(
+                    "no archive file found on destination '{}'.",
+                    archive.destination.id
+                );
+            }
+
+            for archive_file in archive_files {
+                info!("verifying archive file: {}", archive_file.name);
+                let result = Self::verify_archive_file(
+                    storage.as_ref(),
+                    archive,
+                    &archive_file,
+                    test_import,
+                )
+                .await;
+                fs::remove_file(&archive_file.name).unwrap_or_default();
+                fs::remove_file(Checksum::get_sidecar_name(&archive_file.name)).unwrap_or_default();
+
+                verified_files += 1;
+                match result {
+                    Ok(summary) => println!(
+                        "{} (destination: {}): OK ({})",
+                        archive_file.name, archive.destination.id, summary
+                    ),
+                    Err(why) => {
+                        error!("{}", why);
+                        println!(
+                            "{} (destination: {}): FAILED ({})",
+                            archive_file.name, archive.destination.id, why
+                        );
+                        failed_files += 1;
+                    }
+                }
+            }
+        }
+
+        match failed_files {
+            0 => Ok(()),
+            _ => Err(format!(
+                "{} of {} archive files failed the verification.",
+                failed_files, verified_files
+            )),
+        }
+    }
+}
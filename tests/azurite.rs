@@ -0,0 +1,34 @@
+//! Backup and restore of an archive with a local Azurite container as Azure destination.
+//!
+//! The test is ignored by default, as it requires a running Azurite with an existing container:
+//!
+//! ```sh
+//! docker run -d -p 10000:10000 mcr.microsoft.com/azure-storage/azurite azurite-blob \
+//!     --blobHost 0.0.0.0
+//! docker run --rm --network host mcr.microsoft.com/azure-cli az storage container create \
+//!     --name backups --connection-string "UseDevelopmentStorage=true"
+//! AZURITE_ENDPOINT=http://127.0.0.1:10000/devstoreaccount1 \
+//!     cargo test --test azurite -- --ignored
+//! ```
+
+mod common;
+
+use std::env;
+
+/// The well-known account key of the Azurite development storage account.
+const DEVSTORE_ACCOUNT_KEY: &str =
+    "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+#[test]
+#[ignore]
+fn backup_and_restore_with_azurite() {
+    let endpoint = env::var("AZURITE_ENDPOINT").expect("AZURITE_ENDPOINT must be set");
+    let container = env::var("AZURITE_CONTAINER").unwrap_or_else(|_| String::from("backups"));
+
+    common::backup_and_restore(
+        "azurite",
+        &format!(
+            r#"<destination kind="azure" id="azurite" account="devstoreaccount1" account-key="{DEVSTORE_ACCOUNT_KEY}" container="{container}" endpoint="{endpoint}" path="rusty-backup"/>"#
+        ),
+    );
+}
@@ -0,0 +1,63 @@
+//! Backup and restore of an archive through the rusty-backup binary, shared by the tests
+//! against local storage servers.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn run(settings_file: &Path, mode: &str) {
+    let status = Command::new(env!("CARGO_BIN_EXE_rusty-backup"))
+        .arg("--backup-settings-file")
+        .arg(settings_file)
+        .arg("--mode")
+        .arg(mode)
+        .status()
+        .expect("rusty-backup could not be started");
+    assert!(status.success(), "{} failed with: {}", mode, status);
+}
+
+/// Backs up a file to the destination with the id `name`, removes it and restores it.
+pub fn backup_and_restore(name: &str, destination: &str) {
+    let test_directory =
+        env::temp_dir().join(format!("rusty-backup-{}-{}", name, std::process::id()));
+    let source_directory = test_directory.join("source");
+    let source_file = source_directory.join("file.txt");
+    fs::create_dir_all(&source_directory).unwrap();
+    fs::write(&source_file, "content to backup").unwrap();
+
+    let settings_file = test_directory.join("backup_settings.xml");
+    fs::write(
+        &settings_file,
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<backup-configuration working-directory="{working_directory}">
+	<destinations>
+		{destination}
+	</destinations>
+	<archives>
+		<archive name="{name}_{process_id}_{{date:year}}{{date:month}}{{date:day}}" compression="tar.bz2" destination="{name}">
+			<directories>
+				<directory name="{source_directory}"/>
+			</directories>
+		</archive>
+	</archives>
+</backup-configuration>
+"#,
+            working_directory = test_directory.join("work").display(),
+            process_id = std::process::id(),
+            source_directory = source_directory.display(),
+        ),
+    )
+    .unwrap();
+
+    run(&settings_file, "backup");
+    fs::remove_file(&source_file).unwrap();
+    run(&settings_file, "restore");
+
+    assert_eq!(
+        fs::read_to_string(&source_file).unwrap(),
+        "content to backup"
+    );
+    fs::remove_dir_all(&test_directory).unwrap_or_default();
+}
@@ -0,0 +1,29 @@
+//! Backup and restore of an archive with a local fake-gcs-server as Google Cloud Storage
+//! destination.
+//!
+//! The test is ignored by default, as it requires a running fake-gcs-server with an existing
+//! bucket:
+//!
+//! ```sh
+//! mkdir -p /tmp/gcs/backups
+//! docker run -d -p 4443:4443 -v /tmp/gcs:/data fsouza/fake-gcs-server -scheme http
+//! FAKE_GCS_ENDPOINT=http://127.0.0.1:4443 cargo test --test fake_gcs -- --ignored
+//! ```
+
+mod common;
+
+use std::env;
+
+#[test]
+#[ignore]
+fn backup_and_restore_with_fake_gcs() {
+    let endpoint = env::var("FAKE_GCS_ENDPOINT").expect("FAKE_GCS_ENDPOINT must be set");
+    let bucket = env::var("FAKE_GCS_BUCKET").unwrap_or_else(|_| String::from("backups"));
+
+    common::backup_and_restore(
+        "fake_gcs",
+        &format!(
+            r#"<destination kind="gcs" id="fake_gcs" bucket="{bucket}" endpoint="{endpoint}" path="rusty-backup"/>"#
+        ),
+    );
+}
@@ -0,0 +1,38 @@
+//! Backup and restore of an archive with a local MinIO container as S3 destination.
+//!
+//! The test is ignored by default, as it requires a running MinIO with an existing bucket:
+//!
+//! ```sh
+//! docker run -d -p 9000:9000 -e MINIO_ROOT_USER=minio -e MINIO_ROOT_PASSWORD=minio123 \
+//!     minio/minio server /data
+//! docker run --rm --network host --entrypoint sh minio/mc -c \
+//!     "mc alias set local http://127.0.0.1:9000 minio minio123 && mc mb -p local/backups"
+//! MINIO_ENDPOINT=http://127.0.0.1:9000 MINIO_ACCESS_KEY=minio MINIO_SECRET_KEY=minio123 \
+//!     MINIO_BUCKET=backups cargo test --test minio -- --ignored
+//! ```
+//!
+//! For a MinIO with TLS, set `MINIO_CA_FILE` to the CA of its certificate.
+
+mod common;
+
+use std::env;
+
+#[test]
+#[ignore]
+fn backup_and_restore_with_minio() {
+    let endpoint = env::var("MINIO_ENDPOINT").expect("MINIO_ENDPOINT must be set");
+    let access_key = env::var("MINIO_ACCESS_KEY").unwrap_or_else(|_| String::from("minio"));
+    let secret_key = env::var("MINIO_SECRET_KEY").unwrap_or_else(|_| String::from("minio123"));
+    let bucket = env::var("MINIO_BUCKET").unwrap_or_else(|_| String::from("backups"));
+    let ca_file = match env::var("MINIO_CA_FILE") {
+        Ok(ca_file) => format!(" ca-file=\"{}\"", ca_file),
+        Err(_) => String::new(),
+    };
+
+    common::backup_and_restore(
+        "minio",
+        &format!(
+            r#"<destination kind="s3" id="minio" bucket="{bucket}" endpoint="{endpoint}" path-style="true" access-key="{access_key}" secret-key="{secret_key}"{ca_file}/>"#
+        ),
+    );
+}
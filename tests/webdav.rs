@@ -0,0 +1,27 @@
+//! Backup and restore of an archive with a local WebDAV server as destination.
+//!
+//! The test is ignored by default, as it requires a running WebDAV server:
+//!
+//! ```sh
+//! docker run -d -p 8080:80 -e USERNAME=webdav -e PASSWORD=webdav bytemark/webdav
+//! WEBDAV_URL=http://127.0.0.1:8080 cargo test --test webdav -- --ignored
+//! ```
+
+mod common;
+
+use std::env;
+
+#[test]
+#[ignore]
+fn backup_and_restore_with_webdav() {
+    let url = env::var("WEBDAV_URL").expect("WEBDAV_URL must be set");
+    let username = env::var("WEBDAV_USERNAME").unwrap_or_else(|_| String::from("webdav"));
+    let password = env::var("WEBDAV_PASSWORD").unwrap_or_else(|_| String::from("webdav"));
+
+    common::backup_and_restore(
+        "webdav",
+        &format!(
+            r#"<destination kind="webdav" id="webdav" url="{url}" path="backups/rusty backup" username="{username}" password="{password}"/>"#
+        ),
+    );
+}